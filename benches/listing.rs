@@ -0,0 +1,44 @@
+//! Parsing of large listing pages, e.g. spec directories with hundreds of versions. Run with
+//! `cargo bench --bench listing`.
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use get_3gpp_spec::{ListQuery, list_from_body, parse_spec_number};
+use reqwest::Url;
+
+/// A listing page with `rows` version files, in the markup served by 3GPP.
+fn listing(rows: usize) -> String {
+    let mut html = String::from(
+        "<table><thead><tr><th>name</th><th>date</th><th>size</th></tr></thead><tbody>",
+    );
+    for i in 0..rows {
+        let major = (b'a' + (i / 100 % 26) as u8) as char;
+        let name = format!("23501-{}{}{}.zip", major, i / 10 % 10, i % 10);
+        html.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>20{:02}/{:02}/{:02} 14:30</td>\
+             <td>{} KB</td></tr>",
+            name,
+            name,
+            10 + i % 15,
+            1 + i % 12,
+            1 + i % 28,
+            100 + i,
+        ));
+    }
+    html.push_str("</tbody></table>");
+    html
+}
+
+fn parse_listing(c: &mut Criterion) {
+    let url = Url::parse("https://www.3gpp.org/ftp/Specs/archive/23_series/23.501/").unwrap();
+    let query = ListQuery::new(parse_spec_number("23.501").unwrap());
+    for rows in [100, 2000] {
+        let body = listing(rows);
+        assert_eq!(list_from_body(&body, &url, &query).unwrap().len(), rows);
+        c.bench_function(&format!("list_from_body/{}", rows), |b| {
+            b.iter(|| list_from_body(black_box(&body), &url, &query).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, parse_listing);
+criterion_main!(benches);
@@ -0,0 +1,206 @@
+//! Batch files for `--spec-number-file`: one spec per line, each optionally followed by filters
+//! of its own, e.g. `23.501 --release 16`.
+
+use clap::Parser;
+use get_3gpp_spec::{Clock, ListQuery, PartialVersion, RelativeDuration, SpecRef, YearMonth};
+use std::io::BufRead;
+
+/// Filters given on one line of a batch file. They override the same filters given on the
+/// command line for that line's spec only.
+#[derive(clap::Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineOptions {
+    /// Release number or name
+    #[arg(short, long, value_parser = get_3gpp_spec::parse_release)]
+    release: Option<u32>,
+
+    /// Date string, YYYY-MM
+    #[arg(short, long)]
+    date: Option<YearMonth>,
+
+    /// Only specs dated within this duration before now
+    #[arg(long, value_name = "DURATION")]
+    since: Option<RelativeDuration>,
+
+    /// Only versions matching VERSION
+    #[arg(long, value_name = "VERSION")]
+    spec_version: Option<PartialVersion>,
+
+    /// Exclude versions whose editorial component is below N
+    #[arg(long, value_name = "N")]
+    min_editorial: Option<u32>,
+
+    /// Include draft versions
+    #[arg(long, overrides_with = "exclude_drafts")]
+    include_drafts: bool,
+
+    /// Exclude draft versions
+    #[arg(long, overrides_with = "include_drafts")]
+    exclude_drafts: bool,
+
+    /// Descend into subdirectory rows, up to DEPTH levels
+    #[arg(long, value_name = "DEPTH", num_args = 0..=1, default_missing_value = "1")]
+    recursive: Option<u32>,
+}
+
+impl LineOptions {
+    /// Override the filters of `query` with those given on the line, taking `--since` relative
+    /// to the current time of `clock`.
+    pub fn apply(&self, query: &mut ListQuery, clock: &dyn Clock) {
+        if let Some(release) = self.release {
+            query.release = Some(release);
+        }
+        if let Some(date) = self.date {
+            query.date_filter = Some(date);
+        }
+        if let Some(since) = self.since {
+            query.since = since.cutoff(clock);
+        }
+        if let Some(version) = self.spec_version {
+            query.version = Some(version);
+        }
+        if let Some(min) = self.min_editorial {
+            query.min_editorial = Some(min);
+        }
+        if self.include_drafts || self.exclude_drafts {
+            query.include_drafts = self.include_drafts;
+        }
+        if let Some(depth) = self.recursive {
+            query.max_depth = depth;
+        }
+    }
+}
+
+/// One line of a batch file: a spec reference and its options.
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+struct Line {
+    /// Spec number, optionally with a release, e.g. "23.501 Rel-16", or an alias
+    #[arg(required = true)]
+    spec: Vec<String>,
+
+    #[command(flatten)]
+    options: LineOptions,
+}
+
+/// A spec of a batch file with the number of the line it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchLine {
+    pub line: usize,
+    pub options: LineOptions,
+}
+
+/// Read the specs of the batch file `reader` named `name`, resolving their references with
+/// `resolve`. Blank lines and lines starting with `#` are skipped. As with `-`, an invalid line
+/// yields an error naming its line number in its place, so the remaining lines still run.
+pub fn read_batch(
+    reader: impl BufRead,
+    name: &str,
+    resolve: impl Fn(&str) -> Result<SpecRef, String>,
+) -> Vec<Result<(SpecRef, BatchLine), String>> {
+    let mut specs = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                specs.push(Err(format!("failed to read '{}': {}", name, e)));
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = Line::try_parse_from(line.split_whitespace())
+            .map_err(|e| clap_message(&e))
+            .and_then(|parsed| {
+                let spec_ref = resolve(&parsed.spec.join(" "))?;
+                Ok((
+                    spec_ref,
+                    BatchLine {
+                        line: i + 1,
+                        options: parsed.options,
+                    },
+                ))
+            });
+        specs.push(parsed.map_err(|e| format!("{} line {}: {}", name, i + 1, e)));
+    }
+    specs
+}
+
+/// The first line of a clap error, without its `error: ` prefix or usage.
+fn clap_message(e: &clap::Error) -> String {
+    let rendered = e.to_string();
+    let first = rendered.lines().next().unwrap_or_default();
+    first.strip_prefix("error: ").unwrap_or(first).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_batch;
+    use chrono::{TimeZone, Utc};
+    use get_3gpp_spec::{FixedClock, ListQuery, PartialVersion, YearMonth, parse_spec_ref};
+
+    #[test]
+    fn batch_file_with_line_options() {
+        let file = "# fetch set\n\
+                    23.501\n\
+                    \n\
+                    23.502 --release Rel-16 --exclude-drafts\n\
+                    38.331 Rel-15 --spec-version 15.4 -d 2019-01 --recursive\n\
+                    29.500 --bogus\n\
+                    23.501 --min-editorial 2\n\
+                    23.502 --since 6m\n";
+        let specs = read_batch(file.as_bytes(), "specs.txt", parse_spec_ref);
+        assert_eq!(specs.len(), 6);
+        assert_eq!(
+            specs[3].as_ref().unwrap_err(),
+            "specs.txt line 6: unexpected argument '--bogus' found"
+        );
+
+        let clock = FixedClock(Utc.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap());
+        let queries: Vec<(usize, ListQuery)> = specs
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(|(spec_ref, batch)| {
+                let mut query = ListQuery::from(spec_ref);
+                batch.options.apply(&mut query, &clock);
+                (batch.line, query)
+            })
+            .collect();
+        let lines: Vec<_> = queries.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [2, 4, 5, 7, 8]);
+
+        let plain = &queries[0].1;
+        assert_eq!(plain.spec_number.to_string(), "23.501");
+        assert_eq!(plain.release, None);
+        assert!(plain.include_drafts);
+        assert_eq!(plain.min_editorial, None);
+
+        let release = &queries[1].1;
+        assert_eq!(release.spec_number.to_string(), "23.502");
+        assert_eq!(release.release, Some(16));
+        assert!(!release.include_drafts);
+
+        let filtered = &queries[2].1;
+        assert_eq!(filtered.spec_number.to_string(), "38.331");
+        assert_eq!(filtered.release, Some(15));
+        assert_eq!(
+            filtered.version,
+            Some("15.4".parse::<PartialVersion>().unwrap())
+        );
+        assert_eq!(
+            filtered.date_filter,
+            Some("2019-01".parse::<YearMonth>().unwrap())
+        );
+        assert_eq!(filtered.max_depth, 1);
+
+        assert_eq!(queries[3].1.min_editorial, Some(2));
+        assert_eq!(queries[3].1.since, None);
+
+        // `--since` is taken relative to the clock, clamped to the end of shorter months.
+        assert_eq!(
+            queries[4].1.since,
+            Utc.with_ymd_and_hms(2023, 9, 30, 12, 0, 0).single()
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! On-disk cache of listing pages, so that repeated runs against the same spec directories do not
+//! fetch them again while they are fresh.
+//!
+//! Each listing is kept in its own file, named after the SHA-256 of its URL, whose first line
+//! holds the time it was fetched (as read from the query's clock) and its URL.
+
+use crate::sha256_hex;
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long a cached listing is used by default before it is fetched again.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Directory of cached listing pages and how long they stay fresh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingCache {
+    pub dir: PathBuf,
+    /// How long a cached listing is used before it is fetched again.
+    pub ttl: Duration,
+    /// Fetch a listing again if its cached copy is older than this, even if it is younger than
+    /// `ttl`: a one-off freshness override that leaves the configured TTL alone.
+    pub max_age: Option<Duration>,
+}
+
+impl ListingCache {
+    /// Cache in `dir` with the default TTL and no `max_age`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ListingCache {
+            dir: dir.into(),
+            ttl: DEFAULT_CACHE_TTL,
+            max_age: None,
+        }
+    }
+
+    fn path(&self, url: &Url) -> PathBuf {
+        self.dir
+            .join(format!("{}.listing", sha256_hex(url.as_str().as_bytes())))
+    }
+
+    /// The cached listing page of `url`, unless it is missing, unreadable, or was fetched longer
+    /// than `ttl` or `max_age` before `now`.
+    pub fn get(&self, url: &Url, now: DateTime<Utc>) -> Option<String> {
+        let text = std::fs::read_to_string(self.path(url)).ok()?;
+        let (header, body) = text.split_once('\n')?;
+        let (fetched, cached_url) = header.split_once(' ')?;
+        if cached_url != url.as_str() {
+            return None;
+        }
+        let fetched = DateTime::parse_from_rfc3339(fetched).ok()?;
+        // A listing fetched "in the future", e.g. under a clock that was wrong, counts as new.
+        let age = (now - fetched.with_timezone(&Utc))
+            .to_std()
+            .unwrap_or_default();
+        let limit = self
+            .max_age
+            .map_or(self.ttl, |max_age| max_age.min(self.ttl));
+        (age <= limit).then(|| body.to_string())
+    }
+
+    /// Store `body` as the listing page of `url`, fetched at `now`.
+    pub fn put(&self, url: &Url, body: &str, now: DateTime<Utc>) -> Result<(), String> {
+        let path = self.path(url);
+        let part = path.with_extension("part");
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&part, format!("{} {}\n{}", now.to_rfc3339(), url, body)))
+            .and_then(|_| std::fs::rename(&part, &path))
+            .map_err(|e| {
+                format!(
+                    "failed to cache listing '{}' in '{}': {}",
+                    url,
+                    path.display(),
+                    e
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ListingCache;
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use crate::{FixedClock, ListQuery, SharedClock, list, parse_spec_number};
+    use chrono::{DateTime, TimeDelta, Utc};
+    use reqwest::blocking::Client;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn max_age_forces_refetch_within_ttl() {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = fetches.clone();
+        let server = MockServer::start(move |req| match req.path.as_str() {
+            "/23_series/23.501/" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Response::ok(
+                    "<table><thead><tr><th>name</th><th>date</th></tr></thead><tbody>\
+                     <tr><td><a href=\"23501-g40.zip\">23501-g40.zip</a></td>\
+                     <td>2020/03/27 14:30</td></tr></tbody></table>",
+                )
+            }
+            _ => Response::not_found(),
+        });
+        let client = Client::new();
+        let fetched: DateTime<Utc> = "2024-05-01T12:00:00Z".parse().unwrap();
+        let cache = ListingCache::new(scratch_dir("cache-max-age"));
+        let list_at = |minutes: i64, max_age: Option<Duration>| {
+            let query = ListQuery {
+                base_url: server.url("/"),
+                cache: Some(ListingCache {
+                    max_age,
+                    ..cache.clone()
+                }),
+                clock: SharedClock::new(FixedClock(fetched + TimeDelta::minutes(minutes))),
+                ..ListQuery::new(parse_spec_number("23.501").unwrap())
+            };
+            assert_eq!(list(&client, &query).unwrap().len(), 1);
+            fetches.load(Ordering::SeqCst)
+        };
+
+        assert_eq!(list_at(0, None), 1);
+        // Ten minutes later the entry is still within the one-hour TTL.
+        assert_eq!(list_at(10, None), 1);
+        assert_eq!(list_at(10, Some(Duration::from_secs(15 * 60))), 1);
+        // A max age below the entry's age of ten minutes fetches the listing again.
+        assert_eq!(list_at(10, Some(Duration::from_secs(5 * 60))), 2);
+        assert_eq!(list_at(15, None), 2);
+        // Past the TTL, the listing is fetched again whatever the max age.
+        assert_eq!(list_at(80, Some(Duration::from_secs(24 * 3600))), 3);
+    }
+}
@@ -0,0 +1,150 @@
+//! Checkpoint of a batch download for `--resume-batch`, so an interrupted batch can be run again
+//! without fetching the files it already completed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointFile {
+    /// URLs of the items downloaded successfully.
+    completed: BTreeSet<String>,
+}
+
+/// Items of a batch downloaded successfully so far, saved to a JSON file after each one.
+/// Shared by the download threads of the batch.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    completed: Mutex<BTreeSet<String>>,
+}
+
+impl Checkpoint {
+    /// Read the checkpoint at `path`, or start an empty one if the file does not exist.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let completed = match std::fs::read_to_string(path) {
+            Ok(json) => {
+                serde_json::from_str::<CheckpointFile>(&json)
+                    .map_err(|e| format!("invalid checkpoint '{}': {}", path.display(), e))?
+                    .completed
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(e) => {
+                return Err(format!(
+                    "failed to read checkpoint '{}': {}",
+                    path.display(),
+                    e
+                ));
+            }
+        };
+        Ok(Checkpoint {
+            path: path.to_path_buf(),
+            completed: Mutex::new(completed),
+        })
+    }
+
+    /// Whether the item at `url` was already downloaded.
+    pub fn is_completed(&self, url: &str) -> bool {
+        self.lock().contains(url)
+    }
+
+    /// Number of items recorded as completed.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record the item at `url` as downloaded and save the checkpoint. The file is written next to
+    /// the checkpoint and renamed over it, so an interruption never leaves it half written.
+    pub fn complete(&self, url: &str) -> Result<(), String> {
+        let mut completed = self.lock();
+        if !completed.insert(url.to_string()) {
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(&CheckpointFile {
+            completed: completed.clone(),
+        })
+        .map_err(|e| format!("failed to serialize checkpoint: {}", e))?;
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, json)
+            .and_then(|()| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| {
+                format!(
+                    "failed to write checkpoint '{}': {}",
+                    self.path.display(),
+                    e
+                )
+            })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeSet<String>> {
+        self.completed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checkpoint;
+    use crate::download::{DownloadOptions, download_url_to_path};
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use reqwest::blocking::Client;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn resumed_batch_fetches_remaining_items() {
+        let fetched = Arc::new(Mutex::new(Vec::new()));
+        let server = {
+            let fetched = Arc::clone(&fetched);
+            MockServer::start(move |req| {
+                fetched.lock().unwrap().push(req.path.clone());
+                Response::ok("PK\x03\x04")
+            })
+        };
+        let dir = scratch_dir("checkpoint");
+        let path = dir.join("batch.json");
+        let urls: Vec<_> = ["/a.zip", "/b.zip", "/c.zip", "/d.zip"]
+            .iter()
+            .map(|p| server.url(p))
+            .collect();
+        let run = |urls: &[String]| {
+            let checkpoint = Checkpoint::open(&path).unwrap();
+            for url in urls.iter().filter(|url| !checkpoint.is_completed(url)) {
+                let name = url.rsplit('/').next().unwrap();
+                download_url_to_path(
+                    &Client::new(),
+                    url,
+                    &dir.join(name),
+                    &DownloadOptions::default(),
+                )
+                .unwrap();
+                checkpoint.complete(url).unwrap();
+            }
+        };
+
+        // An interrupted batch that completed the first two items.
+        run(&urls[..2]);
+        assert_eq!(Checkpoint::open(&path).unwrap().len(), 2);
+        assert!(!dir.join("batch.json.tmp").exists());
+
+        fetched.lock().unwrap().clear();
+        run(&urls);
+        assert_eq!(*fetched.lock().unwrap(), ["/c.zip", "/d.zip"]);
+        let checkpoint = Checkpoint::open(&path).unwrap();
+        assert!(urls.iter().all(|url| checkpoint.is_completed(url)));
+
+        std::fs::write(&path, "not json").unwrap();
+        let err = Checkpoint::open(&path).unwrap_err();
+        assert!(err.starts_with("invalid checkpoint"), "{}", err);
+        assert!(
+            Checkpoint::open(&dir.join("missing.json"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use crate::{CookieJar, Error};
+use reqwest::blocking::Client;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Options for the HTTP client shared by listing and downloading.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientOptions {
+    /// Proxy URL for all requests. Without it, the `HTTP_PROXY`/`HTTPS_PROXY` environment
+    /// variables are honored.
+    pub proxy: Option<String>,
+    /// Accept invalid TLS certificates (e.g. self-signed ones on internal mirrors).
+    pub insecure: bool,
+    /// PEM file with an additional root certificate to trust, e.g. a private mirror's CA.
+    pub cacert: Option<PathBuf>,
+    /// Timeout for each request, from connecting until the body has been read.
+    pub timeout: Option<Duration>,
+    /// Maximum number of redirects to follow for one request; 0 does not follow redirects.
+    /// Without it, reqwest's default limit of 10 applies.
+    pub max_redirects: Option<usize>,
+    /// Offer HTTP/2 during the TLS handshake (ALPN), for servers that multiplex requests. It is
+    /// used over `https` if the server picks it, and HTTP/1.1 otherwise; plain `http` stays on
+    /// HTTP/1.1. Without it, only HTTP/1.1 is offered.
+    pub http2: bool,
+    /// Speak HTTP/2 from the first byte of every connection without negotiating it ("prior
+    /// knowledge"), e.g. to a cleartext `h2c` mirror. A server that does not speak HTTP/2 fails
+    /// every request.
+    pub http2_prior_knowledge: bool,
+    /// How long an idle pooled connection is kept open. Without it, reqwest's default of 90
+    /// seconds applies.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Interval of TCP keep-alive probes on open connections; none are sent without it.
+    pub tcp_keepalive: Option<Duration>,
+    /// Extra headers sent with every request, as name and value, e.g. a mirror's API key. See
+    /// `parse_header`.
+    pub headers: Vec<(String, String)>,
+    /// Cookie store of the client: the cookies set by every response, redirects included, are
+    /// recorded in it and sent back with later requests, redirects included.
+    pub cookies: Option<CookieJar>,
+}
+
+/// Parse a header given as `Name: Value`, with whitespace around the name and value ignored.
+/// The name must be a valid header name and the value must not contain control characters.
+pub fn parse_header(header: &str) -> Result<(String, String), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("invalid header '{}': expected 'Name: Value'", header))?;
+    let (name, value) = (name.trim(), value.trim());
+    reqwest::header::HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| format!("invalid header name '{}' in '{}'", name, header))?;
+    reqwest::header::HeaderValue::from_str(value).map_err(|_| {
+        format!(
+            "invalid value for header '{}': control characters are not allowed",
+            name
+        )
+    })?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Whether a header named `name` likely carries a secret, such as credentials or an API key.
+fn is_secret_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [
+        "auth", "cookie", "key", "token", "secret", "password", "session",
+    ]
+    .iter()
+    .any(|marker| name.contains(marker))
+}
+
+/// `name: value` for logs, with the value replaced by `<redacted>` if the header likely carries a
+/// secret.
+pub fn redact_header(name: &str, value: &str) -> String {
+    match is_secret_header(name) {
+        true => format!("{}: <redacted>", name),
+        false => format!("{}: {}", name, value),
+    }
+}
+
+/// Header map of `headers` (see `ClientOptions::headers`). Values of headers that likely carry a
+/// secret are marked sensitive, so that reqwest leaves them out of its debug output.
+fn header_map(headers: &[(String, String)]) -> Result<reqwest::header::HeaderMap, String> {
+    let mut map = reqwest::header::HeaderMap::new();
+    for (name, value) in headers {
+        let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid header name '{}'", name))?;
+        let mut header_value = reqwest::header::HeaderValue::from_str(value).map_err(|_| {
+            format!(
+                "invalid value for header '{}': control characters are not allowed",
+                name
+            )
+        })?;
+        header_value.set_sensitive(is_secret_header(name));
+        log::info!("sending header {}", redact_header(name, value));
+        map.append(header_name, header_value);
+    }
+    Ok(map)
+}
+
+/// Parse and validate a proxy URL: an `http` or `https` proxy, or a SOCKS5 proxy, which
+/// resolves host names itself with `socks5h` rather than having them resolved locally.
+pub fn parse_proxy(proxy: &str) -> Result<reqwest::Proxy, String> {
+    let url =
+        reqwest::Url::parse(proxy).map_err(|e| format!("invalid proxy URL '{}': {}", proxy, e))?;
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => {}
+        scheme => {
+            return Err(format!(
+                "unsupported proxy scheme '{}' in '{}': expected http, https, socks5 or socks5h",
+                scheme, proxy
+            ));
+        }
+    }
+    reqwest::Proxy::all(url).map_err(|e| format!("invalid proxy URL '{}': {}", proxy, e))
+}
+
+/// Load a PEM-encoded certificate from `path`.
+pub fn load_certificate(path: &Path) -> Result<reqwest::Certificate, String> {
+    let pem = std::fs::read(path)
+        .map_err(|e| format!("failed to read CA certificate '{}': {}", path.display(), e))?;
+    if !pem.windows(27).any(|w| w == b"-----BEGIN CERTIFICATE-----") {
+        return Err(format!(
+            "invalid CA certificate '{}': no PEM certificate found",
+            path.display()
+        ));
+    }
+    reqwest::Certificate::from_pem(&pem)
+        .map_err(|e| format!("invalid CA certificate '{}': {}", path.display(), e))
+}
+
+/// Time left until `deadline` for a request to `url`, for `--timeout-total`: `None` without a
+/// deadline, and `Error::Timeout` once it has passed.
+pub(crate) fn time_left(deadline: Option<Instant>, url: &str) -> Result<Option<Duration>, Error> {
+    let Some(deadline) = deadline else {
+        return Ok(None);
+    };
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(left) if !left.is_zero() => Ok(Some(left)),
+        _ => Err(Error::Timeout {
+            url: url.to_string(),
+        }),
+    }
+}
+
+/// Whether `deadline` is set and has passed, i.e. whether a failed request was cut short by it.
+pub(crate) fn deadline_passed(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Display `error` followed by the errors that caused it, e.g. why a redirect was refused, which
+/// reqwest's own message leaves out.
+pub(crate) fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    message
+}
+
+/// Redirect policy following at most `max` redirects, failing with an error naming the length
+/// of the chain when there are more.
+fn redirect_policy(max: usize) -> reqwest::redirect::Policy {
+    match max {
+        0 => reqwest::redirect::Policy::none(),
+        _ => reqwest::redirect::Policy::custom(move |attempt| {
+            let chain = attempt.previous().len();
+            match chain > max {
+                true => attempt.error(format!(
+                    "redirect chain of {} exceeds the limit of {} (--max-redirects)",
+                    chain, max
+                )),
+                false => attempt.follow(),
+            }
+        }),
+    }
+}
+
+/// Build the HTTP client used for every request of one invocation. The client keeps a pool of
+/// connections, so passing the same client to `list` and the downloads reuses them instead of
+/// opening (and for HTTPS, handshaking) a new connection per request.
+pub fn build_client(options: &ClientOptions) -> Result<Client, String> {
+    let mut builder = Client::builder();
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(parse_proxy(proxy)?);
+    }
+    if options.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(path) = &options.cacert {
+        builder = builder.add_root_certificate(load_certificate(path)?);
+    }
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(max) = options.max_redirects {
+        builder = builder.redirect(redirect_policy(max));
+    }
+    if options.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    } else if !options.http2 {
+        builder = builder.http1_only();
+    }
+    if let Some(timeout) = options.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if let Some(interval) = options.tcp_keepalive {
+        builder = builder.tcp_keepalive(interval);
+    }
+    if !options.headers.is_empty() {
+        builder = builder.default_headers(header_map(&options.headers)?);
+    }
+    if let Some(jar) = &options.cookies {
+        builder = builder.cookie_provider(std::sync::Arc::new(jar.clone()));
+    }
+    builder
+        .build()
+        .map_err(|e| format!("failed to build HTTP client: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ClientOptions, build_client, error_chain, load_certificate, parse_header, parse_proxy,
+        redact_header,
+    };
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn proxy_schemes() {
+        assert!(parse_proxy("http://proxy.example.com:3128").is_ok());
+        assert!(parse_proxy("https://proxy.example.com:3128").is_ok());
+
+        assert!(parse_proxy("socks5://127.0.0.1:1080").is_ok());
+        assert!(parse_proxy("socks5h://127.0.0.1:1080").is_ok());
+        let err = parse_proxy("ftp://proxy.example.com").unwrap_err();
+        assert!(err.contains("unsupported proxy scheme 'ftp'"), "{}", err);
+        assert!(parse_proxy("not a url").is_err());
+    }
+
+    #[test]
+    fn requests_go_through_proxy() {
+        let proxy = MockServer::start(|req| Response::ok(format!("{} {}", req.method, req.path)));
+        let client = build_client(&ClientOptions {
+            proxy: Some(proxy.url("")),
+            ..ClientOptions::default()
+        })
+        .unwrap();
+
+        let body = client
+            .get("http://spec-mirror.invalid/23_series/23.501")
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert_eq!(body, "GET http://spec-mirror.invalid/23_series/23.501");
+    }
+
+    #[test]
+    fn requests_go_through_socks5_proxy() {
+        let server = MockServer::start(|req| Response::ok(format!("{} {}", req.method, req.path)));
+        let upstream = server.url("").trim_start_matches("http://").to_string();
+        let targets = Arc::new(Mutex::new(Vec::new()));
+        let proxy = {
+            let targets = Arc::clone(&targets);
+            MockServer::start_socks5(upstream, move |target| {
+                targets.lock().unwrap().push(target.to_string())
+            })
+        };
+        let client = build_client(&ClientOptions {
+            proxy: Some(proxy.url("")),
+            ..ClientOptions::default()
+        })
+        .unwrap();
+
+        let body = client
+            .get("http://spec-mirror.invalid/23_series/23.501/")
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert_eq!(body, "GET /23_series/23.501/");
+        // With `socks5h`, the proxy is given the host name to resolve.
+        assert_eq!(*targets.lock().unwrap(), ["spec-mirror.invalid:80"]);
+    }
+
+    #[test]
+    fn insecure_accepts_untrusted_certificate() {
+        let server = MockServer::start_tls(|_| Response::ok("secure"));
+        let url = server.url("/23_series/23.501");
+
+        let client = build_client(&ClientOptions::default()).unwrap();
+        assert!(client.get(&url).send().is_err());
+
+        let client = build_client(&ClientOptions {
+            insecure: true,
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        let body = client.get(&url).send().unwrap().text().unwrap();
+        assert_eq!(body, "secure");
+    }
+
+    #[test]
+    fn cacert_trusts_private_ca() {
+        let server = MockServer::start_tls(|_| Response::ok("trusted"));
+        let cacert = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources/tls/ca.pem");
+        assert!(load_certificate(&cacert).is_ok());
+
+        let client = build_client(&ClientOptions {
+            cacert: Some(cacert),
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        let body = client.get(server.url("/")).send().unwrap().text().unwrap();
+        assert_eq!(body, "trusted");
+    }
+
+    #[test]
+    fn cacert_errors() {
+        let dir = scratch_dir("client-cacert-errors");
+        let missing = dir.join("missing.pem");
+        let err = load_certificate(&missing).unwrap_err();
+        assert!(err.contains("failed to read CA certificate"), "{}", err);
+
+        let garbage = dir.join("garbage.pem");
+        std::fs::write(&garbage, "not a certificate").unwrap();
+        let err = build_client(&ClientOptions {
+            cacert: Some(garbage),
+            ..ClientOptions::default()
+        })
+        .unwrap_err();
+        assert!(err.contains("invalid CA certificate"), "{}", err);
+    }
+
+    #[test]
+    fn http2_and_keepalive_options() {
+        let server = MockServer::start(|_| Response::ok("listing"));
+        let url = server.url("/23_series/23.501");
+
+        let tuned = build_client(&ClientOptions {
+            pool_idle_timeout: Some(Duration::from_secs(5)),
+            tcp_keepalive: Some(Duration::from_secs(30)),
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        assert_eq!(tuned.get(&url).send().unwrap().text().unwrap(), "listing");
+
+        // The mock servers only speak HTTP/1.1: offering HTTP/2 falls back to it, over plain
+        // HTTP and when the TLS handshake does not pick HTTP/2.
+        let http2 = build_client(&ClientOptions {
+            http2: true,
+            insecure: true,
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        assert_eq!(http2.get(&url).send().unwrap().text().unwrap(), "listing");
+        let tls = MockServer::start_tls(|_| Response::ok("secure"));
+        let resp = http2.get(tls.url("/23_series/23.501")).send().unwrap();
+        assert_eq!(resp.version(), reqwest::Version::HTTP_11);
+        assert_eq!(resp.text().unwrap(), "secure");
+
+        // With prior knowledge, the HTTP/2 connection preface is rejected.
+        let prior_knowledge = build_client(&ClientOptions {
+            http2_prior_knowledge: true,
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        assert!(prior_knowledge.get(&url).send().is_err());
+    }
+
+    #[test]
+    fn custom_headers() {
+        let headers: Vec<_> = ["X-Api-Key: s3cr3t", "Accept-Language:en ", "X-Mirror: a:b"]
+            .iter()
+            .map(|h| parse_header(h).unwrap())
+            .collect();
+        assert_eq!(
+            headers[1],
+            ("Accept-Language".to_string(), "en".to_string())
+        );
+        assert_eq!(headers[2], ("X-Mirror".to_string(), "a:b".to_string()));
+
+        let err = parse_header("X-Api-Key").unwrap_err();
+        assert!(err.contains("expected 'Name: Value'"), "{}", err);
+        let err = parse_header("X Api: 1").unwrap_err();
+        assert!(err.contains("invalid header name 'X Api'"), "{}", err);
+        let err = parse_header("X-Api-Key: a\u{7}b").unwrap_err();
+        assert!(err.contains("control characters"), "{}", err);
+
+        assert_eq!(
+            redact_header("X-Api-Key", "s3cr3t"),
+            "X-Api-Key: <redacted>"
+        );
+        assert_eq!(
+            redact_header("Authorization", "Bearer x"),
+            "Authorization: <redacted>"
+        );
+        assert_eq!(
+            redact_header("Accept-Language", "en"),
+            "Accept-Language: en"
+        );
+
+        let server = MockServer::start(|req| {
+            let echo = ["x-api-key", "accept-language", "x-mirror"]
+                .iter()
+                .map(|name| req.header(name).unwrap_or("-"))
+                .collect::<Vec<_>>()
+                .join("|");
+            Response::ok(echo)
+        });
+        let client = build_client(&ClientOptions {
+            headers,
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        for path in ["/23_series/23.501", "/23_series/23.501/23501-g40.zip"] {
+            let body = client.get(server.url(path)).send().unwrap().text().unwrap();
+            assert_eq!(body, "s3cr3t|en|a:b");
+        }
+    }
+
+    #[test]
+    fn timeout_aborts_slow_request() {
+        let server = MockServer::start(|req| {
+            if req.path == "/slow" {
+                std::thread::sleep(Duration::from_secs(2));
+            }
+            Response::ok("done")
+        });
+        let client = build_client(&ClientOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..ClientOptions::default()
+        })
+        .unwrap();
+
+        assert!(client.get(server.url("/fast")).send().is_ok());
+        let err = client.get(server.url("/slow")).send().unwrap_err();
+        assert!(err.is_timeout(), "{}", err);
+    }
+
+    #[test]
+    fn max_redirects() {
+        let server = MockServer::start(|req| match req.path.strip_prefix("/hop/") {
+            Some("5") => Response::ok("arrived"),
+            Some(n) => {
+                let next = n.parse::<u32>().unwrap() + 1;
+                Response::new(302, "").header("Location", &format!("/hop/{}", next))
+            }
+            None => Response::not_found(),
+        });
+        let client = |max| {
+            build_client(&ClientOptions {
+                max_redirects: Some(max),
+                ..ClientOptions::default()
+            })
+            .unwrap()
+        };
+
+        let body = client(5).get(server.url("/hop/0")).send().unwrap().text();
+        assert_eq!(body.unwrap(), "arrived");
+
+        let err = client(2).get(server.url("/hop/0")).send().unwrap_err();
+        assert!(err.is_redirect(), "{}", err);
+        let message = error_chain(&err);
+        assert!(
+            message.contains("redirect chain of 3 exceeds the limit of 2"),
+            "{}",
+            message
+        );
+
+        let response = client(0).get(server.url("/hop/0")).send().unwrap();
+        assert_eq!(response.status(), 302);
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+/// Source of the current time for date-relative features such as `--since`.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock reading the system time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that always reports the same instant, for deterministic tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// Clock shared by all clones of a `ListQuery`, the system clock by default.
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock + Send + Sync>);
+
+impl SharedClock {
+    pub fn new(clock: impl Clock + Send + Sync + 'static) -> Self {
+        SharedClock(Arc::new(clock))
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        SharedClock::new(SystemClock)
+    }
+}
+
+impl Clock for SharedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0.now()
+    }
+}
+
+impl std::fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SharedClock").finish_non_exhaustive()
+    }
+}
+
+/// Clocks are equal if they are shared clones of the same one.
+impl PartialEq for SharedClock {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for SharedClock {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, FixedClock};
+    use crate::RelativeDuration;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn fixed_clock_cutoff() {
+        let clock = FixedClock(Utc.with_ymd_and_hms(2024, 1, 15, 8, 0, 0).unwrap());
+        assert_eq!(clock.now(), clock.now());
+        let since: RelativeDuration = "1m".parse().unwrap();
+        assert_eq!(
+            since.cutoff(&clock),
+            Utc.with_ymd_and_hms(2023, 12, 15, 8, 0, 0).single()
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Shell completion scripts generated from the `clap` command definition.
+
+use clap::{Command, ValueEnum};
+use std::fmt::Write;
+
+/// Shells for which a completion script can be generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Flag forms (`--long` and `-s`) of every visible option of `cmd`.
+fn flags(cmd: &Command) -> Vec<String> {
+    let mut flags = Vec::new();
+    for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+        if let Some(long) = arg.get_long() {
+            flags.push(format!("--{}", long));
+        }
+        if let Some(short) = arg.get_short() {
+            flags.push(format!("-{}", short));
+        }
+    }
+    flags
+}
+
+/// First line of an argument's help text, with characters special to the shells removed.
+fn help(arg: &clap::Arg) -> String {
+    arg.get_help()
+        .map(|h| h.to_string())
+        .unwrap_or_default()
+        .lines()
+        .next()
+        .unwrap_or("")
+        .replace(['\'', '[', ']', ':', '"'], "")
+}
+
+/// Render the completion script for `shell` for the command `cmd`.
+pub fn generate(shell: Shell, cmd: &Command) -> String {
+    let name = cmd.get_name();
+    let ident = name.replace('-', "_");
+    let mut out = String::new();
+
+    match shell {
+        Shell::Bash => {
+            let _ = writeln!(out, "_{}() {{", ident);
+            let _ = writeln!(out, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"");
+            let _ = writeln!(
+                out,
+                "    COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )",
+                flags(cmd).join(" ")
+            );
+            let _ = writeln!(out, "}}");
+            let _ = writeln!(out, "complete -F _{} {}", ident, name);
+        }
+        Shell::Zsh => {
+            let _ = writeln!(out, "#compdef {}", name);
+            let _ = writeln!(out, "_arguments \\");
+            for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+                let value = match arg.get_action().takes_values() {
+                    true => ":value:",
+                    false => "",
+                };
+                if let Some(long) = arg.get_long() {
+                    let _ = writeln!(out, "  '--{}[{}]{}' \\", long, help(arg), value);
+                }
+                if let Some(short) = arg.get_short() {
+                    let _ = writeln!(out, "  '-{}[{}]{}' \\", short, help(arg), value);
+                }
+            }
+            let _ = writeln!(out, "  '*::spec number:'");
+        }
+        Shell::Fish => {
+            for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+                if arg.get_long().is_none() && arg.get_short().is_none() {
+                    continue;
+                }
+                let _ = write!(out, "complete -c {}", name);
+                if let Some(long) = arg.get_long() {
+                    let _ = write!(out, " -l {}", long);
+                }
+                if let Some(short) = arg.get_short() {
+                    let _ = write!(out, " -s {}", short);
+                }
+                if arg.get_action().takes_values() {
+                    let _ = write!(out, " -r");
+                }
+                let _ = writeln!(out, " -d '{}'", help(arg));
+            }
+        }
+        Shell::Powershell => {
+            let quoted: Vec<String> = flags(cmd).iter().map(|f| format!("'{}'", f)).collect();
+            let _ = writeln!(
+                out,
+                "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{",
+                name
+            );
+            let _ = writeln!(
+                out,
+                "    param($wordToComplete, $commandAst, $cursorPosition)"
+            );
+            let _ = writeln!(out, "    @({}) |", quoted.join(", "));
+            let _ = writeln!(
+                out,
+                "        Where-Object {{ $_ -like \"$wordToComplete*\" }} |"
+            );
+            let _ = writeln!(
+                out,
+                "        ForEach-Object {{ [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterName', $_) }}"
+            );
+            let _ = writeln!(out, "}}");
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Shell, generate};
+    use crate::Args;
+    use clap::{CommandFactory, Parser, ValueEnum};
+
+    #[test]
+    fn scripts_for_every_shell() {
+        let cmd = Args::command();
+        for shell in Shell::value_variants() {
+            let script = generate(*shell, &cmd);
+            assert!(!script.is_empty(), "{:?}", shell);
+            assert!(script.contains("get-3gpp-spec"), "{:?}", shell);
+            assert!(script.contains("release"), "{:?}", shell);
+            assert!(!script.contains("generate-completions"), "{:?}", shell);
+        }
+    }
+
+    #[test]
+    fn flag_without_spec_number() {
+        let args =
+            Args::try_parse_from(["get-3gpp-spec", "--generate-completions", "fish"]).unwrap();
+        assert_eq!(args.generate_completions, Some(Shell::Fish));
+        assert!(args.spec_numbers.is_empty());
+
+        assert!(Args::try_parse_from(["get-3gpp-spec"]).is_err());
+    }
+}
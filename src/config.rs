@@ -0,0 +1,419 @@
+//! Config file holding defaults for common options, merged under the command line flags.
+//!
+//! The file is a flat TOML document of `key = value` pairs, for example:
+//!
+//! ```toml
+//! base-url = "https://mirror.example.com/Specs/archive/"
+//! proxy = "http://proxy.example.com:3128"
+//! timeout = 30
+//! retries = 2
+//! output-dir = "specs"
+//! format = "text"
+//! cache-dir = "/var/cache/get-3gpp-spec"
+//! cache-ttl = "6h"
+//! ```
+//!
+//! An `[aliases]` table maps friendly names to spec numbers, so that e.g. `get-3gpp-spec NAS`
+//! lists 24.501:
+//!
+//! ```toml
+//! [aliases]
+//! NAS = "24.501"
+//! NGAP = "38.413"
+//! ```
+//!
+//! Only the subset of TOML needed for these keys is understood: strings, integers, booleans,
+//! comments and the `[aliases]` table. Other tables and arrays are rejected.
+
+use crate::{Args, Format, parse_interval};
+use clap::ValueEnum;
+use get_3gpp_spec::{SpecNumber, SpecRef, parse_spec_number, parse_spec_ref};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Name of the config file inside the user's config directory.
+const CONFIG_FILE: &str = "get-3gpp-spec/config.toml";
+
+/// Options read from a config file. Unset keys leave the command line defaults untouched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub base_url: Option<String>,
+    pub proxy: Option<String>,
+    /// Per-request timeout in seconds.
+    pub timeout: Option<u64>,
+    pub retries: Option<u32>,
+    pub output_dir: Option<PathBuf>,
+    pub format: Option<Format>,
+    pub cache_dir: Option<PathBuf>,
+    /// How long cached listings stay fresh, written like `--cache-ttl`.
+    pub cache_ttl: Option<Duration>,
+    /// Friendly names for spec numbers, from the `[aliases]` table.
+    pub aliases: BTreeMap<String, SpecNumber>,
+}
+
+/// Table of the config file a line belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Root,
+    Aliases,
+}
+
+/// Value on the right-hand side of a config line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+        }
+    }
+}
+
+/// Parse a basic (`"..."`) or literal (`'...'`) string starting at the beginning of `s`.
+/// Returns the string and the rest of the input after the closing quote.
+fn parse_string(s: &str) -> Result<(String, &str), String> {
+    let quote = s.chars().next().ok_or("expected a value")?;
+    let mut out = String::new();
+    let mut chars = s[1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((out, &s[i + 2..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(c) => return Err(format!("unsupported escape '\\{}'", c)),
+                None => break,
+            },
+            c => out.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+/// Parse the value of a `key = value` line, rejecting anything but a trailing comment after it.
+fn parse_value(s: &str) -> Result<Value, String> {
+    let (value, rest) = match s.chars().next() {
+        Some('"') | Some('\'') => {
+            let (string, rest) = parse_string(s)?;
+            (Value::String(string), rest)
+        }
+        _ => {
+            let end = s.find('#').unwrap_or(s.len());
+            let token = s[..end].trim();
+            let value = match token {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                _ => Value::Integer(
+                    token
+                        .replace('_', "")
+                        .parse()
+                        .map_err(|_| format!("unsupported value '{}'", token))?,
+                ),
+            };
+            (value, &s[end..])
+        }
+    };
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected '{}' after value", rest));
+    }
+    Ok(value)
+}
+
+impl Config {
+    /// Parse the text of a config file.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+        let mut section = Section::Root;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            config
+                .set_line(&mut section, line)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        Ok(config)
+    }
+
+    fn set_line(&mut self, section: &mut Section, line: &str) -> Result<(), String> {
+        if let Some(header) = line.strip_prefix('[') {
+            let end = header.find(']').ok_or("unterminated table header")?;
+            *section = match header[..end].trim() {
+                "aliases" => Section::Aliases,
+                table => return Err(format!("unknown table '{}'", table)),
+            };
+            return Ok(());
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("expected 'key = value', got '{}'", line))?;
+        let key = key.trim().trim_matches('"');
+        let value = parse_value(value.trim())?;
+
+        if *section == Section::Aliases {
+            let Value::String(spec) = value else {
+                return Err(format!(
+                    "alias '{}' must be a spec number string, got {}",
+                    key,
+                    value.type_name()
+                ));
+            };
+            let spec_number =
+                parse_spec_number(&spec).map_err(|e| format!("alias '{}': {}", key, e))?;
+            self.aliases.insert(key.to_string(), spec_number);
+            return Ok(());
+        }
+
+        let mismatch =
+            |expected: &str| format!("'{}' must be {}, got {}", key, expected, value.type_name());
+
+        match (key.replace('_', "-").as_str(), &value) {
+            ("base-url" | "mirror", Value::String(s)) => self.base_url = Some(s.clone()),
+            ("proxy", Value::String(s)) => self.proxy = Some(s.clone()),
+            ("output-dir", Value::String(s)) => self.output_dir = Some(PathBuf::from(s)),
+            ("cache-dir", Value::String(s)) => self.cache_dir = Some(PathBuf::from(s)),
+            ("cache-ttl", Value::String(s)) => self.cache_ttl = Some(parse_interval(s)?),
+            ("timeout", Value::Integer(n)) => {
+                self.timeout =
+                    Some(u64::try_from(*n).map_err(|_| format!("invalid timeout {}", n))?)
+            }
+            ("retries", Value::Integer(n)) => {
+                self.retries =
+                    Some(u32::try_from(*n).map_err(|_| format!("invalid retries {}", n))?)
+            }
+            ("format", Value::String(s)) => {
+                self.format =
+                    Some(Format::from_str(s, true).map_err(|_| format!("invalid format '{}'", s))?)
+            }
+            (
+                "base-url" | "mirror" | "proxy" | "output-dir" | "format" | "cache-dir"
+                | "cache-ttl",
+                _,
+            ) => {
+                return Err(mismatch("a string"));
+            }
+            ("timeout" | "retries", _) => return Err(mismatch("a nonnegative integer")),
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+        Ok(())
+    }
+
+    /// Read and parse the config file at `path`.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config '{}': {}", path.display(), e))?;
+        Config::parse(&text).map_err(|e| format!("invalid config '{}': {}", path.display(), e))
+    }
+
+    /// Load the config for this invocation: the file at `path` if given, otherwise the file at
+    /// the default location if it exists, otherwise an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        match path {
+            Some(path) => Config::read(path),
+            None => match default_path(|name| std::env::var_os(name)) {
+                Some(path) if path.is_file() => Config::read(&path),
+                _ => Ok(Config::default()),
+            },
+        }
+    }
+
+    /// Fill every option not given on the command line from this config.
+    pub fn apply(&self, args: &mut Args) {
+        args.base_url = args.base_url.take().or_else(|| self.base_url.clone());
+        args.proxy = args.proxy.take().or_else(|| self.proxy.clone());
+        args.timeout = args.timeout.or(self.timeout);
+        args.retries = args.retries.or(self.retries);
+        args.output_dir = args.output_dir.take().or_else(|| self.output_dir.clone());
+        args.format = args.format.or(self.format);
+        args.cache_dir = args.cache_dir.take().or_else(|| self.cache_dir.clone());
+        args.cache_ttl = args.cache_ttl.or(self.cache_ttl);
+    }
+
+    /// Parse the positional spec argument with `parse_spec_ref`, or if it is not a valid spec
+    /// reference, look it up in the alias table (case-insensitively). Returns the alias name
+    /// used, if any, along with the reference.
+    pub fn resolve_spec_ref(&self, input: &str) -> Result<(SpecRef, Option<&str>), String> {
+        let err = match parse_spec_ref(input) {
+            Ok(spec_ref) => return Ok((spec_ref, None)),
+            Err(e) => e,
+        };
+        let (name, spec_number) = self
+            .aliases
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(input.trim()))
+            .ok_or(err)?;
+        let spec_ref = SpecRef {
+            spec_number: spec_number.clone(),
+            release: None,
+        };
+        Ok((spec_ref, Some(name.as_str())))
+    }
+}
+
+/// Default config file location: `get-3gpp-spec/config.toml` under `%APPDATA%` on Windows, or
+/// under `$XDG_CONFIG_HOME` (falling back to `$HOME/.config`) elsewhere. `var` looks up an
+/// environment variable.
+pub fn default_path(var: impl Fn(&str) -> Option<std::ffi::OsString>) -> Option<PathBuf> {
+    let dir = match cfg!(windows) {
+        true => PathBuf::from(var("APPDATA")?),
+        false => match var("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(var("HOME")?).join(".config"),
+        },
+    };
+    Some(dir.join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, default_path};
+    use crate::{Args, Format};
+    use clap::Parser;
+    use get_3gpp_spec::parse_spec_number;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    const CONFIG: &str = r#"
+        # Defaults for the internal mirror
+        mirror = "https://mirror.example.com/Specs/archive/"
+        proxy = 'http://proxy.example.com:3128'  # corporate proxy
+        timeout = 30
+        retries = 2
+        output_dir = "specs"
+        format = "atom"
+        cache_dir = "cache"
+        cache_ttl = "6h"
+
+        [aliases]
+        NAS = "24.501"
+        "NGAP" = "38.413"  # NG application protocol
+    "#;
+
+    #[test]
+    fn parse_config() {
+        assert_eq!(
+            Config::parse(CONFIG),
+            Ok(Config {
+                base_url: Some("https://mirror.example.com/Specs/archive/".to_string()),
+                proxy: Some("http://proxy.example.com:3128".to_string()),
+                timeout: Some(30),
+                retries: Some(2),
+                output_dir: Some(PathBuf::from("specs")),
+                format: Some(Format::Atom),
+                cache_dir: Some(PathBuf::from("cache")),
+                cache_ttl: Some(Duration::from_secs(6 * 3600)),
+                aliases: [
+                    ("NAS".to_string(), parse_spec_number("24.501").unwrap()),
+                    ("NGAP".to_string(), parse_spec_number("38.413").unwrap()),
+                ]
+                .into(),
+            })
+        );
+        assert_eq!(Config::parse("# nothing\n\n"), Ok(Config::default()));
+
+        let err = |text: &str| Config::parse(text).unwrap_err();
+        assert_eq!(err("colour = \"red\""), "line 1: unknown key 'colour'");
+        assert_eq!(
+            err("\ntimeout = \"30\""),
+            "line 2: 'timeout' must be a nonnegative integer, got a string"
+        );
+        assert_eq!(err("retries = -1"), "line 1: invalid retries -1");
+        assert_eq!(err("[mirror]"), "line 1: unknown table 'mirror'");
+        assert_eq!(
+            err("[aliases]\nNAS = 24501"),
+            "line 2: alias 'NAS' must be a spec number string, got an integer"
+        );
+        assert!(err("[aliases]\nNAS = \"NAS\"").starts_with("line 2: alias 'NAS': invalid"));
+        assert_eq!(err("proxy = \"http://x"), "line 1: unterminated string");
+        assert_eq!(err("format = \"xml\""), "line 1: invalid format 'xml'");
+        assert!(err("cache-ttl = \"6 hours\"").starts_with("line 1: invalid interval '6 hours'"));
+    }
+
+    #[test]
+    fn cli_overrides_config() {
+        let path =
+            std::env::temp_dir().join(format!("get-3gpp-spec-config-{}.toml", std::process::id()));
+        std::fs::write(&path, CONFIG).unwrap();
+        let config = Config::load(Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut args = Args::try_parse_from([
+            "get-3gpp-spec",
+            "23.501",
+            "--proxy",
+            "http://other-proxy:8080",
+            "--retries",
+            "0",
+            "--max-age",
+            "5min",
+        ])
+        .unwrap();
+        config.apply(&mut args);
+        assert_eq!(
+            args.base_url.as_deref(),
+            Some("https://mirror.example.com/Specs/archive/")
+        );
+        assert_eq!(args.proxy.as_deref(), Some("http://other-proxy:8080"));
+        assert_eq!(args.timeout, Some(30));
+        assert_eq!(args.retries, Some(0));
+        assert_eq!(args.output_dir, Some(PathBuf::from("specs")));
+        assert_eq!(args.format, Some(Format::Atom));
+        assert_eq!(args.cache_dir, Some(PathBuf::from("cache")));
+        assert_eq!(args.cache_ttl, Some(Duration::from_secs(6 * 3600)));
+        assert_eq!(args.max_age, Some(Duration::from_secs(300)));
+
+        assert!(Config::load(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn spec_aliases() {
+        let config = Config::parse(CONFIG).unwrap();
+        let resolve = |input| {
+            config
+                .resolve_spec_ref(input)
+                .map(|(spec_ref, alias)| (spec_ref.spec_number.to_string(), alias))
+        };
+        assert_eq!(resolve("NAS"), Ok(("24.501".to_string(), Some("NAS"))));
+        assert_eq!(resolve("ngap"), Ok(("38.413".to_string(), Some("NGAP"))));
+        assert_eq!(resolve("23.501"), Ok(("23.501".to_string(), None)));
+
+        let err = resolve("RRC").unwrap_err();
+        assert!(err.contains("invalid spec_number 'RRC'"), "{}", err);
+        assert!(Config::default().resolve_spec_ref("NAS").is_err());
+    }
+
+    #[test]
+    fn default_location() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.into())
+        };
+        if cfg!(windows) {
+            assert_eq!(
+                default_path(env(&[("APPDATA", r"C:\Users\me\AppData\Roaming")])),
+                Some(PathBuf::from(r"C:\Users\me\AppData\Roaming").join(super::CONFIG_FILE))
+            );
+        } else {
+            assert_eq!(
+                default_path(env(&[("XDG_CONFIG_HOME", "/xdg"), ("HOME", "/home/me")])),
+                Some(PathBuf::from("/xdg/get-3gpp-spec/config.toml"))
+            );
+            assert_eq!(
+                default_path(env(&[("HOME", "/home/me")])),
+                Some(PathBuf::from("/home/me/.config/get-3gpp-spec/config.toml"))
+            );
+        }
+        assert_eq!(default_path(env(&[])), None);
+    }
+}
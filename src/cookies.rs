@@ -0,0 +1,354 @@
+//! Minimal cookie jar carrying session cookies from listing requests into downloads, for mirrors
+//! behind a login portal. The jar is also the cookie store of the client (see
+//! `ClientOptions::cookies`), so that cookies set by a redirect, e.g. the session cookie set on
+//! the redirect from a login portal back to the listing, are seen and sent along the redirects.
+
+use reqwest::Url;
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue, SET_COOKIE};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cookie {
+    name: String,
+    value: String,
+    /// Host that set the cookie, or the value of its `Domain` attribute.
+    domain: String,
+    /// Whether the cookie had no `Domain` attribute and is only sent back to `domain` itself.
+    host_only: bool,
+    /// Path the cookie is sent to, with the paths below it: its `Path` attribute, otherwise the
+    /// directory of the URL that set it.
+    path: String,
+    /// Whether the cookie had the `Secure` attribute and is only sent over `https`.
+    secure: bool,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header value received from `url`. Returns the cookie and whether
+    /// it is expired, i.e. asks for a stored cookie of the same name to be removed.
+    fn parse(header: &str, url: &Url) -> Option<(Cookie, bool)> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            domain: url.host_str()?.to_ascii_lowercase(),
+            host_only: true,
+            path: default_path(url.path()).to_string(),
+            secure: false,
+        };
+        let mut expired = false;
+        for attribute in parts {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            match key.trim().to_ascii_lowercase().as_str() {
+                "domain" => {
+                    let domain = value.trim().trim_start_matches('.').to_ascii_lowercase();
+                    // A server may not set cookies for a domain it does not belong to.
+                    if !domain.is_empty() && domain_matches(&cookie.domain, &domain) {
+                        cookie.domain = domain;
+                        cookie.host_only = false;
+                    }
+                }
+                "path" if value.trim().starts_with('/') => cookie.path = value.trim().to_string(),
+                "secure" => cookie.secure = true,
+                "max-age" => expired = value.trim().parse::<i64>().is_ok_and(|age| age <= 0),
+                _ => {}
+            }
+        }
+        Some((cookie, expired))
+    }
+
+    /// Whether the cookie is sent with a request to `url`.
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let domain = match self.host_only {
+            true => host.eq_ignore_ascii_case(&self.domain),
+            false => domain_matches(&host.to_ascii_lowercase(), &self.domain),
+        };
+        domain && path_matches(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/// Path of a cookie set by a response to a URL with `path` and no `Path` attribute: the
+/// directory of `path`, e.g. `/23_series` for `/23_series/23.501`.
+fn default_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(end) => &path[..end],
+    }
+}
+
+/// Whether a request for `path` is at or below the cookie path `cookie_path`, e.g. `/files/a.zip`
+/// for `/files` but not `/filesystem`.
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path.strip_prefix(cookie_path)
+        .is_some_and(|rest| rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+/// Whether `host` is `domain` or one of its subdomains.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Cookies set by the responses of one invocation, shared by all clones of the jar (e.g. the one
+/// in a `ListQuery`, the one in `DownloadOptions` and the client's cookie store). The domain,
+/// path and `Secure` flag of a cookie are honored, so that e.g. a session cookie set over
+/// `https` is never sent over plain `http`. Expiry dates are ignored, except that `Max-Age=0`
+/// removes a cookie.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<Cookie>>>,
+}
+
+/// Jars are equal if they share their cookies.
+impl PartialEq for CookieJar {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cookies, &other.cookies)
+    }
+}
+
+impl Eq for CookieJar {}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the cookies set by the `Set-Cookie` headers of a response from `url`.
+    pub fn store(&self, url: &Url, headers: &HeaderMap) {
+        self.store_values(url, &mut headers.get_all(SET_COOKIE).iter());
+    }
+
+    fn store_values(&self, url: &Url, values: &mut dyn Iterator<Item = &HeaderValue>) {
+        let mut cookies = self.cookies.lock().unwrap_or_else(|e| e.into_inner());
+        for header in values {
+            let Some((cookie, expired)) = header.to_str().ok().and_then(|h| Cookie::parse(h, url))
+            else {
+                continue;
+            };
+            cookies.retain(|c| {
+                c.name != cookie.name || c.domain != cookie.domain || c.path != cookie.path
+            });
+            if !expired {
+                log::debug!("storing cookie '{}' for {}", cookie.name, cookie.domain);
+                cookies.push(cookie);
+            }
+        }
+    }
+
+    /// Value of the `Cookie` header for a request to `url`, or `None` if no cookie applies.
+    pub fn header(&self, url: &Url) -> Option<String> {
+        let cookies = self.cookies.lock().unwrap_or_else(|e| e.into_inner());
+        let pairs: Vec<_> = cookies
+            .iter()
+            .filter(|c| c.matches(url))
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+}
+
+/// The jar as the cookie store of a client, which sees the responses to every request, redirects
+/// included.
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, values: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        self.store_values(url, values);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.header(url)
+            .and_then(|header| HeaderValue::from_str(&header).ok())
+    }
+}
+
+/// Add the `Cookie` header for `url` from `jar`, if any, to `request`.
+pub(crate) fn with_cookies(
+    request: RequestBuilder,
+    jar: Option<&CookieJar>,
+    url: &Url,
+) -> RequestBuilder {
+    match jar.and_then(|jar| jar.header(url)) {
+        Some(cookies) => request.header(COOKIE, cookies),
+        None => request,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CookieJar;
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use crate::{
+        ClientOptions, DownloadOptions, ListQuery, build_client, download_url_to_path, list,
+        parse_spec_number,
+    };
+    use reqwest::Url;
+    use reqwest::blocking::Client;
+    use reqwest::header::{HeaderMap, HeaderValue, SET_COOKIE};
+
+    fn set_cookies(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(SET_COOKIE, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn jar_matches_domains() {
+        let jar = CookieJar::new();
+        let portal = Url::parse("https://portal.example.com/login").unwrap();
+        jar.store(
+            &portal,
+            &set_cookies(&[
+                "session=abc; Path=/; HttpOnly",
+                "sso=xyz; Domain=.example.com; Secure",
+                "evil=1; Domain=other.com",
+                "dl=1; Domain=example.com; Path=/files",
+            ]),
+        );
+        assert_eq!(
+            jar.header(&portal).as_deref(),
+            Some("session=abc; sso=xyz; evil=1")
+        );
+        let files = Url::parse("https://files.example.com/23501-g40.zip").unwrap();
+        assert_eq!(jar.header(&files).as_deref(), Some("sso=xyz"));
+        assert_eq!(jar.header(&Url::parse("https://other.com/").unwrap()), None);
+
+        // `Path` limits a cookie to the path and the paths below it, and `Secure` to `https`.
+        let url = |url| Url::parse(url).unwrap();
+        assert_eq!(
+            jar.header(&url("https://files.example.com/files/23501-g40.zip"))
+                .as_deref(),
+            Some("sso=xyz; dl=1")
+        );
+        assert_eq!(
+            jar.header(&url("https://files.example.com/filesystem"))
+                .as_deref(),
+            Some("sso=xyz")
+        );
+        assert_eq!(
+            jar.header(&url("http://files.example.com/files/23501-g40.zip"))
+                .as_deref(),
+            Some("dl=1")
+        );
+        jar.store(
+            &url("https://portal.example.com/a/b/c"),
+            &set_cookies(&["deep=1"]),
+        );
+        assert_eq!(
+            jar.header(&url("https://portal.example.com/a/b/d"))
+                .as_deref(),
+            Some("session=abc; sso=xyz; evil=1; deep=1")
+        );
+        assert_eq!(
+            jar.header(&url("https://portal.example.com/a/c"))
+                .as_deref(),
+            Some("session=abc; sso=xyz; evil=1")
+        );
+        jar.store(
+            &url("https://portal.example.com/a/b/c"),
+            &set_cookies(&["deep=; Max-Age=0"]),
+        );
+
+        jar.store(&portal, &set_cookies(&["session=def", "evil=; Max-Age=0"]));
+        assert_eq!(jar.header(&portal).as_deref(), Some("sso=xyz; session=def"));
+        assert_eq!(jar.clone(), jar);
+        assert_ne!(CookieJar::new(), jar);
+    }
+
+    #[test]
+    fn session_cookie_carried_into_download() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(
+                "<table><thead><tr><th>name</th><th>date</th></tr></thead><tbody>\
+                 <tr><td><a href=\"/files/23501-g40.zip\">23501-g40.zip</a></td>\
+                 <td>2020/03/27 14:30</td></tr></tbody></table>",
+            )
+            .header("Set-Cookie", "session=abc123; Path=/; HttpOnly"),
+            "/files/23501-g40.zip" => match req.header("Cookie") {
+                Some("session=abc123") => Response::ok(b"PK\x03\x04spec".to_vec()),
+                _ => Response::new(403, "login required"),
+            },
+            _ => Response::not_found(),
+        });
+        let client = Client::new();
+        let dir = scratch_dir("cookies-session");
+        let dest = dir.join("23501-g40.zip");
+        let url = server.url("/files/23501-g40.zip");
+
+        let err = download_url_to_path(&client, &url, &dest, &DownloadOptions::default());
+        assert!(err.unwrap_err().contains("403"));
+
+        let jar = CookieJar::new();
+        let query = ListQuery {
+            base_url: server.url("/"),
+            cookies: Some(jar.clone()),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        assert_eq!(list(&client, &query).unwrap().len(), 1);
+        let options = DownloadOptions {
+            cookies: Some(jar),
+            ..DownloadOptions::default()
+        };
+        let download = download_url_to_path(&client, &url, &dest, &options).unwrap();
+        assert_eq!(std::fs::read(download.path).unwrap(), b"PK\x03\x04spec");
+    }
+
+    #[test]
+    fn cookie_set_on_redirect_is_kept() {
+        // A login portal: the listing redirects to the login page without a session, which sets
+        // the session cookie on its redirect back to the listing.
+        let server = MockServer::start(|req| match (req.path.as_str(), req.header("Cookie")) {
+            ("/23_series/23.501/", Some("session=abc123")) => Response::ok(
+                "<table><thead><tr><th>name</th><th>date</th></tr></thead><tbody>\
+                 <tr><td><a href=\"/files/23501-g40.zip\">23501-g40.zip</a></td>\
+                 <td>2020/03/27 14:30</td></tr></tbody></table>",
+            ),
+            ("/23_series/23.501/", _) => Response::new(302, "").header("Location", "/login"),
+            ("/login", _) => Response::new(302, "")
+                .header("Location", "/23_series/23.501/")
+                .header("Set-Cookie", "session=abc123; HttpOnly"),
+            ("/files/23501-g40.zip", Some("session=abc123")) => {
+                Response::ok(b"PK\x03\x04spec".to_vec())
+            }
+            ("/files/23501-g40.zip", _) => Response::new(403, "login required"),
+            _ => Response::not_found(),
+        });
+        let jar = CookieJar::new();
+        let client = build_client(&ClientOptions {
+            cookies: Some(jar.clone()),
+            ..ClientOptions::default()
+        })
+        .unwrap();
+        let query = ListQuery {
+            base_url: server.url("/"),
+            cookies: Some(jar.clone()),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        assert_eq!(list(&client, &query).unwrap().len(), 1);
+        assert_eq!(
+            jar.header(&Url::parse(&server.url("/files/")).unwrap())
+                .as_deref(),
+            Some("session=abc123")
+        );
+
+        let dir = scratch_dir("cookies-redirect");
+        let url = server.url("/files/23501-g40.zip");
+        let options = DownloadOptions {
+            cookies: Some(jar),
+            ..DownloadOptions::default()
+        };
+        let download =
+            download_url_to_path(&client, &url, &dir.join("23501-g40.zip"), &options).unwrap();
+        assert_eq!(std::fs::read(download.path).unwrap(), b"PK\x03\x04spec");
+    }
+}
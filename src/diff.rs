@@ -0,0 +1,243 @@
+//! Comparison of two listings, for reporting what changed since a saved listing.
+
+use crate::SpecItem;
+use std::path::Path;
+
+/// Changes between two listings, keyed by spec number and version. Each list keeps the order of
+/// the listing it was taken from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// Items of the current listing whose version was not in the previous one.
+    pub added: Vec<SpecItem>,
+    /// Items of the previous listing whose version is no longer listed.
+    pub removed: Vec<SpecItem>,
+    /// Items of the current listing whose version was listed before too.
+    pub unchanged: Vec<SpecItem>,
+}
+
+impl Diff {
+    /// Whether the listings have the same versions.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// One line per removed item prefixed with `-`, then one per added item prefixed with `+`.
+impl std::fmt::Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for item in &self.removed {
+            writeln!(f, "- {}", item)?;
+        }
+        for item in &self.added {
+            writeln!(f, "+ {}", item)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `a` and `b` are the same version of the same spec. Unversioned items are the same
+/// if they have the same URL.
+fn same_version(a: &SpecItem, b: &SpecItem) -> bool {
+    a.spec == b.spec
+        && match (a.version, b.version) {
+            (None, None) => a.url == b.url,
+            (a, b) => a == b,
+        }
+}
+
+/// Compare the `previous` listing with the `current` one.
+pub fn diff(previous: &[SpecItem], current: &[SpecItem]) -> Diff {
+    let (unchanged, added) = current
+        .iter()
+        .cloned()
+        .partition(|item| previous.iter().any(|p| same_version(p, item)));
+    let removed = previous
+        .iter()
+        .filter(|item| !current.iter().any(|c| same_version(c, item)))
+        .cloned()
+        .collect();
+    Diff {
+        added,
+        removed,
+        unchanged,
+    }
+}
+
+/// Tracker of successive listings for `--watch`, reporting the items that appeared since the
+/// previous poll.
+#[derive(Debug, Clone, Default)]
+pub struct Watcher {
+    previous: Option<Vec<SpecItem>>,
+}
+
+impl Watcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the `current` listing and return its items that are new since the previous call:
+    /// versions that were not listed, and versions listed with an older date. The first call
+    /// only records the listing and returns nothing.
+    pub fn poll(&mut self, current: Vec<SpecItem>) -> Vec<SpecItem> {
+        let new = match &self.previous {
+            None => Vec::new(),
+            Some(previous) => current
+                .iter()
+                .filter(|item| {
+                    previous
+                        .iter()
+                        .filter(|p| same_version(p, item))
+                        .all(|p| p.date < item.date)
+                })
+                .cloned()
+                .collect(),
+        };
+        self.previous = Some(current);
+        new
+    }
+}
+
+/// Read a listing saved with `--format json`.
+pub fn read_listing(path: &Path) -> Result<Vec<SpecItem>, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read listing '{}': {}", path.display(), e))?;
+    serde_json::from_str(&json).map_err(|e| format!("invalid listing '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Watcher, diff, read_listing};
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use crate::{ListQuery, SpecItem, Version, list, parse_spec_number};
+    use chrono::{TimeZone, Utc};
+    use reqwest::blocking::Client;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn item(spec: &str, minor: u32) -> SpecItem {
+        SpecItem {
+            spec: parse_spec_number(spec).unwrap(),
+            version: Some(Version {
+                major: 16,
+                minor,
+                editorial: 0,
+            }),
+            release: None,
+            date: Utc.with_ymd_and_hms(2020, minor, 1, 0, 0, 0).unwrap(),
+            url: format!("https://example.com/{}-{}.zip", spec, minor),
+            filename: String::new(),
+            draft: false,
+            size: None,
+        }
+    }
+
+    /// `spec version` of each item.
+    fn versions(items: &[SpecItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|i| format!("{} {}", i.spec, i.version.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn added_only() {
+        let previous = [item("23.501", 3), item("23.501", 4)];
+        let current = [item("23.501", 3), item("23.501", 4), item("23.501", 5)];
+        let d = diff(&previous, &current);
+        assert_eq!(versions(&d.added), ["23.501 16.5.0"]);
+        assert!(d.removed.is_empty());
+        assert_eq!(versions(&d.unchanged), ["23.501 16.3.0", "23.501 16.4.0"]);
+        assert_eq!(
+            d.to_string(),
+            "+ 23.501 16. 5. 0 @ 2020-05-01T00:00:00+00:00 (https://example.com/23.501-5.zip)\n"
+        );
+
+        let d = diff(&[], &previous);
+        assert_eq!(d.added, previous);
+    }
+
+    #[test]
+    fn removed_only() {
+        let previous = [item("23.501", 3), item("23.501", 4)];
+        let current = [item("23.501", 4)];
+        let d = diff(&previous, &current);
+        assert!(d.added.is_empty());
+        assert_eq!(versions(&d.removed), ["23.501 16.3.0"]);
+        assert_eq!(versions(&d.unchanged), ["23.501 16.4.0"]);
+        assert!(d.to_string().starts_with("- 23.501 16. 3. 0"));
+
+        let d = diff(&current, &current);
+        assert!(d.is_empty());
+        assert_eq!(d.to_string(), "");
+    }
+
+    #[test]
+    fn mixed() {
+        let previous = [item("23.501", 3), item("23.501", 4), item("24.501", 4)];
+        let mut moved = item("23.501", 4);
+        moved.url = "https://mirror.example.com/23501-g40.zip".to_string();
+        let current = [moved, item("23.501", 5), item("24.501", 5)];
+        let d = diff(&previous, &current);
+        assert_eq!(versions(&d.added), ["23.501 16.5.0", "24.501 16.5.0"]);
+        assert_eq!(versions(&d.removed), ["23.501 16.3.0", "24.501 16.4.0"]);
+        assert_eq!(d.unchanged, [current[0].clone()]);
+        assert!(!d.is_empty());
+    }
+
+    #[test]
+    fn watch_reports_new_version_once() {
+        let polls = AtomicUsize::new(0);
+        let server = MockServer::start(move |req| {
+            if req.path != "/23_series/23.501/" {
+                return Response::not_found();
+            }
+            let mut rows = String::from(
+                "<tr><td><a href=\"https://example.com/23501-g40.zip\">23501-g40.zip</a></td>\
+                 <td>2020/03/27 14:30</td></tr>",
+            );
+            if polls.fetch_add(1, Ordering::SeqCst) > 0 {
+                rows.push_str(
+                    "<tr><td><a href=\"https://example.com/23501-g50.zip\">23501-g50.zip</a></td>\
+                     <td>2020/06/12 10:02</td></tr>",
+                );
+            }
+            Response::ok(format!(
+                "<table><thead><tr><th>name</th><th>date</th></tr></thead>\
+                 <tbody>{}</tbody></table>",
+                rows
+            ))
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let client = Client::new();
+        let mut watcher = Watcher::new();
+
+        let events: Vec<Vec<SpecItem>> = (0..3)
+            .map(|_| watcher.poll(list(&client, &query).unwrap()))
+            .collect();
+        assert!(events[0].is_empty());
+        assert_eq!(versions(&events[1]), ["23.501 16.5.0"]);
+        assert!(events[2].is_empty());
+
+        let mut reuploaded = item("23.501", 5);
+        watcher.poll(vec![reuploaded.clone()]);
+        assert!(watcher.poll(vec![reuploaded.clone()]).is_empty());
+        reuploaded.date = Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(watcher.poll(vec![reuploaded.clone()]), [reuploaded]);
+    }
+
+    #[test]
+    fn saved_listing_round_trip() {
+        let dir = scratch_dir("diff-saved-listing");
+        let items = vec![item("23.501", 4), item("24.501", 5)];
+        let path = dir.join("listing.json");
+        std::fs::write(&path, serde_json::to_string(&items).unwrap()).unwrap();
+        assert_eq!(read_listing(&path).unwrap(), items);
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(read_listing(&path).unwrap_err().contains("invalid listing"));
+        let err = read_listing(&dir.join("missing.json")).unwrap_err();
+        assert!(err.contains("failed to read listing"), "{}", err);
+    }
+}
@@ -0,0 +1,1535 @@
+use crate::CookieJar;
+use crate::client::{deadline_passed, error_chain, time_left};
+use crate::sha256::Sha256;
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Outcome of a completed download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    pub path: PathBuf,
+    /// Number of bytes written.
+    pub size: u64,
+    /// Lowercase hex SHA-256 digest of the written bytes.
+    pub sha256: String,
+    /// Value of the response's `Last-Modified` header, if present and valid.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Set the modification time of the file of `download` to its `Last-Modified` time, otherwise
+/// to `listed`, the date the file was listed with, for `--preserve-mtime`. Without either, the
+/// modification time is left as is.
+pub fn preserve_mtime(download: &Download, listed: Option<DateTime<Utc>>) -> Result<(), String> {
+    let Some(time) = download.last_modified.or(listed) else {
+        return Ok(());
+    };
+    let path = &download.path;
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(time.into()))
+        .map_err(|e| {
+            format!(
+                "failed to set the modification time of '{}': {}",
+                path.display(),
+                e
+            )
+        })
+}
+
+/// How one download of a batch ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadStatus {
+    Ok,
+    /// Not fetched because the local copy is already up to date.
+    Skipped,
+    Failed,
+}
+
+/// Outcome of one download of a batch, as listed by `--summary-json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DownloadOutcome {
+    pub url: String,
+    /// Local path the file was, or would have been, written to.
+    pub dest: String,
+    pub status: DownloadStatus,
+    /// Why the download failed.
+    pub error: Option<String>,
+}
+
+/// Totals over a batch of downloads, printed after `--all`, and the outcome of each download
+/// recorded with `record_item`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BatchSummary {
+    pub downloaded: usize,
+    /// Items not fetched because the local copy is already up to date.
+    pub skipped: usize,
+    pub failed: usize,
+    /// Bytes written by the successful downloads.
+    pub bytes: u64,
+    #[serde(rename = "elapsed_secs", serialize_with = "serialize_secs")]
+    pub elapsed: Duration,
+    pub items: Vec<DownloadOutcome>,
+}
+
+fn serialize_secs<S: serde::Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+impl BatchSummary {
+    /// Add `result` to the totals.
+    pub fn record(&mut self, result: &Result<Download, String>) {
+        match result {
+            Ok(download) => {
+                self.downloaded += 1;
+                self.bytes += download.size;
+            }
+            Err(_) => self.failed += 1,
+        }
+    }
+
+    /// Add `result` of downloading `url` to `dest` to the totals, and list its outcome.
+    pub fn record_item(&mut self, url: &str, dest: &Path, result: &Result<Download, String>) {
+        self.record(result);
+        self.items.push(DownloadOutcome {
+            url: url.to_string(),
+            dest: dest.display().to_string(),
+            status: match result {
+                Ok(_) => DownloadStatus::Ok,
+                Err(_) => DownloadStatus::Failed,
+            },
+            error: result.as_ref().err().cloned(),
+        });
+    }
+
+    /// Count the item at `url` as skipped, e.g. because a resumed batch already downloaded it to
+    /// `dest`, and list its outcome.
+    pub fn record_skipped(&mut self, url: &str, dest: &Path) {
+        self.skipped += 1;
+        self.items.push(DownloadOutcome {
+            url: url.to_string(),
+            dest: dest.display().to_string(),
+            status: DownloadStatus::Skipped,
+            error: None,
+        });
+    }
+
+    /// Write the summary to `path` as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize summary: {}", e))?;
+        std::fs::write(path, json)
+            .map_err(|e| format!("failed to write summary '{}': {}", path.display(), e))
+    }
+
+    /// Average throughput in bytes per second, or 0 if no time elapsed.
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.bytes as f64 / secs,
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "downloaded {} file{} ({}) in {:.1}s at {}/s; {} skipped, {} failed",
+            self.downloaded,
+            if self.downloaded == 1 { "" } else { "s" },
+            format_bytes(self.bytes),
+            self.elapsed.as_secs_f64(),
+            format_bytes(self.throughput() as u64),
+            self.skipped,
+            self.failed
+        )
+    }
+}
+
+/// Format a byte count with a binary unit, e.g. `1.5 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Options controlling how a file is downloaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadOptions {
+    /// Refuse to write a `.zip` destination unless the body starts with a ZIP signature.
+    pub check_content: bool,
+    /// Fail instead of fetching, or while fetching, after this instant (`--timeout-total`).
+    pub deadline: Option<Instant>,
+    /// Send the cookies of this jar, e.g. a session cookie set by a listing, and store the
+    /// cookies set by the response.
+    pub cookies: Option<CookieJar>,
+    /// Whether to check downloads against the `.sha256` sidecar published next to them.
+    pub sidecar: SidecarCheck,
+    /// Times to reconnect when the connection drops while the body is received (see
+    /// `receive`).
+    pub reconnects: u32,
+}
+
+/// Checking of a download against the SHA-256 digest in a sidecar file at its URL with
+/// `.sha256` appended, as published by some mirrors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SidecarCheck {
+    #[default]
+    Off,
+    /// Verify against the sidecar if it can be fetched, otherwise warn and skip the check.
+    IfPresent,
+    /// Fail the download if the sidecar cannot be fetched.
+    Required,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            check_content: true,
+            deadline: None,
+            cookies: None,
+            sidecar: SidecarCheck::Off,
+            reconnects: 0,
+        }
+    }
+}
+
+/// Signatures a ZIP archive can start with: a local file header, an empty archive's
+/// end-of-central-directory record, or a spanned archive marker.
+const ZIP_SIGNATURES: &[&[u8]] = &[b"PK\x03\x04", b"PK\x05\x06", b"PK\x07\x08"];
+
+/// Magic bytes of a gzip stream.
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+
+/// Whether `body` starts with a ZIP signature.
+fn is_zip_content(body: &[u8]) -> bool {
+    ZIP_SIGNATURES.iter().any(|sig| body.starts_with(sig))
+}
+
+/// Check that `body` downloaded from `url` is a ZIP archive, so that e.g. an HTML error page
+/// served with status 200 is not saved under a `.zip` name.
+fn check_zip_content(url: &str, content_type: Option<&str>, body: &[u8]) -> Result<(), String> {
+    if is_zip_content(body) {
+        return Ok(());
+    }
+    if body.starts_with(GZIP_MAGIC) {
+        return Err(format!(
+            "refusing to save '{}': expected a ZIP archive but got gzip-compressed data that does \
+             not decode to one; the server likely applied a transfer encoding to the archive \
+             (use --no-content-check to override)",
+            url
+        ));
+    }
+    Err(format!(
+        "refusing to save '{}': expected a ZIP archive but got {} (use --no-content-check to override)",
+        url,
+        content_type.unwrap_or("content without a ZIP signature")
+    ))
+}
+
+/// Body of a fetched file with its content type.
+struct Fetched {
+    content_type: Option<String>,
+    content: Vec<u8>,
+}
+
+/// Headers of a response describing its body.
+#[derive(Debug, Default)]
+struct BodyHeaders {
+    content_type: Option<String>,
+    /// `Content-Encoding` the body was sent with, decoded once the body is complete.
+    encoding: Option<String>,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl BodyHeaders {
+    fn of(resp: &reqwest::blocking::Response) -> Self {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        BodyHeaders {
+            content_type: header(reqwest::header::CONTENT_TYPE),
+            encoding: header(reqwest::header::CONTENT_ENCODING),
+            last_modified: header(reqwest::header::LAST_MODIFIED)
+                .and_then(|v| DateTime::parse_from_rfc2822(&v).ok())
+                .map(|d| d.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// Destination of a body received by `receive`, which continues after the bytes it holds.
+trait Sink: Write {
+    /// Number of bytes received so far.
+    fn received(&mut self) -> std::io::Result<u64>;
+    /// Drop the bytes received after the first `len`.
+    fn keep(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+impl Sink for Vec<u8> {
+    fn received(&mut self) -> std::io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+
+    fn keep(&mut self, len: u64) -> std::io::Result<()> {
+        self.truncate(len as usize);
+        Ok(())
+    }
+}
+
+/// A `.part` file, whose length on disk is the number of bytes received.
+impl Sink for File {
+    fn received(&mut self) -> std::io::Result<u64> {
+        self.metadata().map(|metadata| metadata.len())
+    }
+
+    fn keep(&mut self, len: u64) -> std::io::Result<()> {
+        self.set_len(len)?;
+        self.seek(SeekFrom::End(0)).map(|_| ())
+    }
+}
+
+/// Receive the body of `url` over HTTP(S) into `sink`, returning the headers describing it. If
+/// `sink` already holds bytes, the request continues after them with a `Range` header, which the
+/// server may ignore and send the whole file instead, replacing them.
+///
+/// If the connection drops while the body is received, `url` is requested again, up to
+/// `options.reconnects` times. When the server supports range requests, i.e. it answered with
+/// `206 Partial Content` or `Accept-Ranges: bytes`, the request asks for the bytes after those
+/// already in `sink`; otherwise the body is received again from the start. Chunks are written to
+/// `sink` as they arrive, so a download that fails keeps the bytes received before it failed.
+fn receive(
+    client: &Client,
+    url: &str,
+    options: &DownloadOptions,
+    sink: &mut impl Sink,
+) -> Result<BodyHeaders, String> {
+    let deadline = options.deadline;
+    let timeout = || crate::Error::Timeout {
+        url: url.to_string(),
+    };
+    let sink_error = |e: std::io::Error| format!("failed to save '{}': {}", url, e);
+    let mut resp = send_from(client, url, options, sink)?;
+    let headers = BodyHeaders::of(&resp);
+    let ranges = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT
+        || resp
+            .headers()
+            .get(reqwest::header::ACCEPT_RANGES)
+            .is_some_and(|v| v == "bytes");
+
+    let mut buf = vec![0; 64 * 1024];
+    let mut reconnects = 0;
+    loop {
+        let e = match resp.read(&mut buf) {
+            Ok(0) => return Ok(headers),
+            Ok(n) => {
+                sink.write_all(&buf[..n]).map_err(sink_error)?;
+                continue;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => e,
+        };
+        if deadline_passed(deadline) {
+            return Err(timeout().to_string());
+        }
+        if reconnects == options.reconnects {
+            return Err(format!("failed to read response body for '{}': {}", url, e));
+        }
+        reconnects += 1;
+        if !ranges {
+            sink.keep(0).map_err(sink_error)?;
+        }
+        log::warn!(
+            "connection to '{}' dropped: {}; reconnecting from byte {} (reconnect {} of {})",
+            url,
+            e,
+            sink.received().map_err(sink_error)?,
+            reconnects,
+            options.reconnects
+        );
+        resp = send_from(client, url, options, sink)?;
+    }
+}
+
+/// Send a `GET` request for the bytes of `url` after those in `sink`, i.e. from the length of a
+/// `.part` file on disk, emptying `sink` if the server sends the whole file instead.
+///
+/// The bytes are only appended if the server answers with a range starting right after them in
+/// a file larger than what `sink` holds. Otherwise `sink` cannot be a prefix of the file, e.g.
+/// it is a complete file that failed verification, or the range cannot be appended, and the
+/// whole file is requested: whether the server says so with `416 Range Not Satisfiable` or
+/// answers with another range.
+fn send_from(
+    client: &Client,
+    url: &str,
+    options: &DownloadOptions,
+    sink: &mut impl Sink,
+) -> Result<reqwest::blocking::Response, String> {
+    let sink_error = |e: std::io::Error| format!("failed to save '{}': {}", url, e);
+    let offset = sink.received().map_err(sink_error)?;
+    let mut resp = send_get(client, url, options, offset)?;
+    if offset == 0 {
+        return Ok(resp);
+    }
+    let restart = |reason: String| {
+        log::warn!(
+            "cannot resume '{}' from byte {}: {}; restarting the download",
+            url,
+            offset,
+            reason
+        );
+    };
+    match (resp.status(), content_range(&resp)) {
+        (reqwest::StatusCode::PARTIAL_CONTENT, Some((start, total)))
+            if start == offset && total.is_none_or(|total| offset < total) =>
+        {
+            return Ok(resp);
+        }
+        (reqwest::StatusCode::PARTIAL_CONTENT, Some((start, Some(total)))) if start == offset => {
+            restart(format!("the file has only {} bytes", total));
+        }
+        (reqwest::StatusCode::PARTIAL_CONTENT, _) => {
+            restart("the server sent another range".to_string());
+        }
+        (reqwest::StatusCode::RANGE_NOT_SATISFIABLE, _) => {
+            restart("the server has no bytes after it".to_string());
+        }
+        // The whole file, which replaces the bytes in `sink`.
+        _ => {
+            log::info!("'{}' was sent whole instead of from byte {}", url, offset);
+            sink.keep(0).map_err(sink_error)?;
+            return Ok(resp);
+        }
+    }
+    resp = send_get(client, url, options, 0)?;
+    sink.keep(0).map_err(sink_error)?;
+    Ok(resp)
+}
+
+/// First byte of the range a `206 Partial Content` response holds and the size of the whole
+/// file, unless unknown (`*`), from its `Content-Range` header, e.g. 700 and 2004 for
+/// `bytes 700-2003/2004`.
+fn content_range(resp: &reqwest::blocking::Response) -> Option<(u64, Option<u64>)> {
+    let range = resp.headers().get(reqwest::header::CONTENT_RANGE)?;
+    let (range, total) = range
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+/// Send a `GET` request for `url` from byte `offset` on, failing unless it is answered with a
+/// success status, or with `416 Range Not Satisfiable` for a non-zero `offset`.
+fn send_get(
+    client: &Client,
+    url: &str,
+    options: &DownloadOptions,
+    offset: u64,
+) -> Result<reqwest::blocking::Response, String> {
+    let deadline = options.deadline;
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    if let Ok(parsed) = reqwest::Url::parse(url) {
+        request = crate::cookies::with_cookies(request, options.cookies.as_ref(), &parsed);
+    }
+    if let Some(left) = time_left(deadline, url).map_err(|e| e.to_string())? {
+        request = request.timeout(left);
+    }
+    let timeout = || crate::Error::Timeout {
+        url: url.to_string(),
+    };
+    let resp = request
+        .send()
+        .map_err(|e| match deadline_passed(deadline) {
+            true => timeout().to_string(),
+            false => format!("request failed for '{}': {}", url, error_chain(&e)),
+        })?;
+    if let Some(jar) = &options.cookies {
+        jar.store(resp.url(), resp.headers());
+    }
+
+    let unsatisfiable = offset > 0 && resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE;
+    if !resp.status().is_success() && !unsatisfiable {
+        return Err(format!(
+            "failed to download '{}': status {}",
+            url,
+            resp.status()
+        ));
+    }
+    Ok(resp)
+}
+
+/// `body` of `url` decoded if it was sent with a `Content-Encoding`: some mirrors compress a
+/// `.zip` again for transfer, and the file saved must be the archive.
+fn decoded(url: &str, encoding: Option<&str>, body: Vec<u8>) -> Result<Vec<u8>, String> {
+    match encoding {
+        Some(encoding) => crate::inflate::decode(encoding, body).map_err(|e| {
+            format!(
+                "failed to decode '{}' sent with Content-Encoding '{}': {}",
+                url, encoding, e
+            )
+        }),
+        None => Ok(body),
+    }
+}
+
+/// Fetch `url` into memory, over FTP for `ftp://` URLs, checking that the body is a ZIP archive
+/// if `check_zip` holds; a body that is a gzip-compressed ZIP archive is decoded. The deadline of
+/// `options` is only checked before an FTP transfer.
+fn fetch(
+    client: &Client,
+    url: &str,
+    options: &DownloadOptions,
+    check_zip: bool,
+) -> Result<Fetched, String> {
+    let fetched = match url.starts_with("ftp://") {
+        true => {
+            time_left(options.deadline, url).map_err(|e| e.to_string())?;
+            Fetched {
+                content_type: None,
+                content: crate::ftp::retrieve(url)?,
+            }
+        }
+        false => {
+            let mut content = Vec::new();
+            let headers = receive(client, url, options, &mut content)?;
+            Fetched {
+                content: decoded(url, headers.encoding.as_deref(), content)?,
+                content_type: headers.content_type,
+            }
+        }
+    };
+    checked(url, fetched, check_zip)
+}
+
+/// `fetched`, the whole body of `url`, checked to be a ZIP archive if `check_zip` holds (see
+/// `fetch`).
+fn checked(url: &str, fetched: Fetched, check_zip: bool) -> Result<Fetched, String> {
+    if !check_zip {
+        return Ok(fetched);
+    }
+    // A `.zip` gzipped again without a `Content-Encoding` header to say so.
+    if fetched.content.starts_with(GZIP_MAGIC)
+        && let Ok(decoded) = crate::inflate::decode("gzip", fetched.content.clone())
+        && is_zip_content(&decoded)
+    {
+        log::info!("'{}' was gzip-compressed; saving the decoded archive", url);
+        return Ok(Fetched {
+            content: decoded,
+            ..fetched
+        });
+    }
+    check_zip_content(url, fetched.content_type.as_deref(), &fetched.content)?;
+    Ok(fetched)
+}
+
+/// Byte a download of `url` into the `.part` file `part` resumes from: the length on disk of
+/// what an earlier download streamed into it before it was interrupted, or 0 without one or for
+/// FTP. `receive` requests the bytes after it, and starts over unless the server answers with
+/// those bytes of a file larger than the `.part` file (see `send_from`). A `.part` file cannot
+/// be checked against a checksum sidecar, which covers the whole file, so a resumed download is
+/// only verified once complete.
+fn resume_offset(url: &str, part: &Path) -> u64 {
+    if url.starts_with("ftp://") {
+        return 0;
+    }
+    let len = std::fs::metadata(part).map_or(0, |metadata| metadata.len());
+    if len > 0 {
+        log::info!("resuming '{}' from byte {}", url, len);
+    }
+    len
+}
+
+/// Receive the body of `url` into the `.part` file `part`, continuing after the prefix an
+/// earlier download left in it (see `resume_offset`), and flush it to disk.
+fn receive_part(
+    client: &Client,
+    url: &str,
+    part: &Path,
+    options: &DownloadOptions,
+) -> Result<BodyHeaders, String> {
+    let write_error = |e: std::io::Error| format!("failed to write to '{}': {}", part.display(), e);
+    if url.starts_with("ftp://") {
+        time_left(options.deadline, url).map_err(|e| e.to_string())?;
+        let content = crate::ftp::retrieve(url)?;
+        File::create(part)
+            .and_then(|mut file| file.write_all(&content).and_then(|()| file.sync_all()))
+            .map_err(write_error)?;
+        return Ok(BodyHeaders::default());
+    }
+    let offset = resume_offset(url, part);
+    let mut file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(part)
+        .map_err(|e| format!("failed to create file '{}': {}", part.display(), e))?;
+    file.keep(offset).map_err(write_error)?;
+    match receive(client, url, options, &mut file) {
+        Ok(headers) => {
+            file.sync_all().map_err(write_error)?;
+            Ok(headers)
+        }
+        Err(e) => {
+            // Keep what was received for a later download to resume from, but nothing else.
+            if file.received().is_ok_and(|len| len == 0) {
+                let _ = std::fs::remove_file(part);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Decode the complete body of `url` in `part` if it was sent with a `Content-Encoding`, and check
+/// that it is a ZIP archive if `check_zip` holds, as `checked` does for a body in memory. A `.part`
+/// file that fails the check is removed, so that a later download does not resume from it.
+fn finish_part(
+    url: &str,
+    part: &Path,
+    headers: &BodyHeaders,
+    check_zip: bool,
+) -> Result<(), String> {
+    let read_error = |e: std::io::Error| format!("failed to read '{}': {}", part.display(), e);
+    let write_error = |e: std::io::Error| format!("failed to write to '{}': {}", part.display(), e);
+    let rewrite = |content: &[u8]| {
+        File::create(part)
+            .and_then(|mut file| file.write_all(content).and_then(|()| file.sync_all()))
+            .map_err(write_error)
+    };
+    if let Some(encoding) = &headers.encoding {
+        let content = std::fs::read(part).map_err(read_error)?;
+        rewrite(&decoded(url, Some(encoding), content)?)?;
+    }
+    if !check_zip {
+        return Ok(());
+    }
+    let mut head = Vec::new();
+    File::open(part)
+        .and_then(|file| file.take(4).read_to_end(&mut head))
+        .map_err(read_error)?;
+    if is_zip_content(&head) {
+        return Ok(());
+    }
+    // Not an archive, or one gzip-compressed again: small enough to check in memory.
+    let fetched = Fetched {
+        content_type: headers.content_type.clone(),
+        content: std::fs::read(part).map_err(read_error)?,
+    };
+    match checked(url, fetched, true) {
+        Ok(fetched) => rewrite(&fetched.content),
+        Err(e) => {
+            let _ = std::fs::remove_file(part);
+            Err(e)
+        }
+    }
+}
+
+/// Size and SHA-256 hex digest of the file at `path`, read in chunks.
+fn hash_file(path: &Path) -> Result<(u64, String), String> {
+    let read_error = |e: std::io::Error| format!("failed to read '{}': {}", path.display(), e);
+    let mut file = File::open(path).map_err(read_error)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => return Ok((size, hasher.finish_hex())),
+            Ok(n) => {
+                hasher.update(&buf[..n]);
+                size += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(read_error(e)),
+        }
+    }
+}
+
+/// Whether `path` has a `.zip` extension.
+fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Headers of a file reported by a `HEAD` request, for `--head-only`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Metadata {
+    pub url: String,
+    pub status: u16,
+    /// Value of the `Content-Length` header, if present and valid.
+    pub content_length: Option<u64>,
+    /// Value of the `Last-Modified` header, if present and a valid HTTP date.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = self.content_length.map(format_bytes);
+        let modified = self.last_modified.map(|d| d.to_rfc3339());
+        write!(
+            f,
+            "{} {} {} {}",
+            self.status,
+            size.as_deref().unwrap_or("-"),
+            modified.as_deref().unwrap_or("-"),
+            self.url
+        )
+    }
+}
+
+/// Fetch the headers of `url` with a `HEAD` request, without its body. A response with any
+/// status is returned, so that e.g. a missing file is reported with its 404, except for 405 and
+/// 501, with which servers reject `HEAD` itself. `ftp://` URLs are not supported.
+pub fn fetch_metadata(client: &Client, url: &str) -> Result<Metadata, String> {
+    if url.starts_with("ftp://") {
+        return Err(format!(
+            "cannot fetch headers of '{}': HEAD is not supported over FTP",
+            url
+        ));
+    }
+    let resp = client
+        .head(url)
+        .send()
+        .map_err(|e| format!("request failed for '{}': {}", url, error_chain(&e)))?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::METHOD_NOT_ALLOWED
+        || status == reqwest::StatusCode::NOT_IMPLEMENTED
+    {
+        return Err(format!(
+            "server rejected HEAD request for '{}': status {}",
+            url, status
+        ));
+    }
+
+    let header = |name| resp.headers().get(name).and_then(|v| v.to_str().ok());
+    Ok(Metadata {
+        url: url.to_string(),
+        status: status.as_u16(),
+        content_length: header(reqwest::header::CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+        last_modified: header(reqwest::header::LAST_MODIFIED)
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+            .map(|d| d.with_timezone(&Utc)),
+    })
+}
+
+/// Temporary file a download to `dest` is written to before being renamed to `dest`: `dest` with
+/// `.part` appended, e.g. `23501-g40.zip.part`.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// Download `url` and write the response body to `dest`. `ftp://` URLs are fetched over FTP.
+///
+/// The body is streamed to `part_path(dest)` and renamed to `dest` once complete, so `dest` only
+/// ever holds a complete file. A download that fails leaves the `.part` file behind with the
+/// bytes received so far. A `.part` file left by an earlier download is resumed from with a range
+/// request, unless the server cannot send the bytes after it (see `resume_offset`).
+pub fn download_url_to_path(
+    client: &Client,
+    url: &str,
+    dest: &Path,
+    options: &DownloadOptions,
+) -> Result<Download, String> {
+    download_verified(client, url, dest, options, |download| {
+        match options.sidecar {
+            SidecarCheck::Off => Ok(()),
+            _ => verify_sidecar(client, url, download, options),
+        }
+    })
+}
+
+/// First field of a `.sha256` sidecar, e.g. `<digest>  23501-g40.zip` as written by `sha256sum`,
+/// if it is a SHA-256 hex digest.
+fn parse_sidecar(body: &[u8]) -> Option<&str> {
+    let digest = std::str::from_utf8(body).ok()?.split_whitespace().next()?;
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit())).then_some(digest)
+}
+
+/// Check `download` of `url` against the digest in the sidecar at `{url}.sha256`. A sidecar
+/// that cannot be fetched is only an error with `SidecarCheck::Required`.
+fn verify_sidecar(
+    client: &Client,
+    url: &str,
+    download: &Download,
+    options: &DownloadOptions,
+) -> Result<(), String> {
+    let sidecar_url = format!("{}.sha256", url);
+    let body = match fetch(client, &sidecar_url, options, false) {
+        Ok(fetched) => fetched.content,
+        Err(e) if options.sidecar == SidecarCheck::Required => {
+            return Err(format!("no checksum sidecar for '{}': {}", url, e));
+        }
+        Err(e) => {
+            log::warn!("not verifying '{}' against a checksum sidecar: {}", url, e);
+            return Ok(());
+        }
+    };
+    let expected = parse_sidecar(&body).ok_or_else(|| {
+        format!(
+            "invalid checksum sidecar '{}': expected a SHA-256 hex digest",
+            sidecar_url
+        )
+    })?;
+    if !download.sha256.eq_ignore_ascii_case(expected) {
+        return Err(format!(
+            "SHA-256 mismatch for '{}': sidecar has {}, got {}",
+            url, expected, download.sha256
+        ));
+    }
+    Ok(())
+}
+
+/// `download_url_to_path`, renaming the `.part` file to `dest` only if `verify` accepts the
+/// download.
+pub(crate) fn download_verified(
+    client: &Client,
+    url: &str,
+    dest: &Path,
+    options: &DownloadOptions,
+    verify: impl FnOnce(&Download) -> Result<(), String>,
+) -> Result<Download, String> {
+    let part = part_path(dest);
+    let check_zip = options.check_content && is_zip(dest);
+    let headers = receive_part(client, url, &part, options)?;
+    finish_part(url, &part, &headers, check_zip)?;
+    let (size, sha256) = hash_file(&part)?;
+
+    let download = Download {
+        path: dest.to_path_buf(),
+        size,
+        sha256,
+        last_modified: headers.last_modified,
+    };
+    verify(&download)?;
+    std::fs::rename(&part, dest).map_err(|e| {
+        format!(
+            "failed to rename '{}' to '{}': {}",
+            part.display(),
+            dest.display(),
+            e
+        )
+    })?;
+    Ok(download)
+}
+
+/// Download `url` and write the response body to `out`, e.g. stdout for `--output -`, returning
+/// the number of bytes written. The content check applies if the URL path ends in `.zip`.
+///
+/// The body is copied to `out` as it arrives, after its first bytes were checked to be a ZIP
+/// signature. Bytes written cannot be taken back, so a dropped connection is not reconnected. A
+/// body that must be decoded first, sent with a `Content-Encoding` or a gzip-compressed archive,
+/// is received whole, as is one fetched over FTP.
+pub fn download_url_to_writer(
+    client: &Client,
+    url: &str,
+    out: &mut impl Write,
+    options: &DownloadOptions,
+) -> Result<u64, String> {
+    let path = reqwest::Url::parse(url).map(|u| u.path().to_string());
+    let check_zip = options.check_content && path.is_ok_and(|p| is_zip(Path::new(&p)));
+    if url.starts_with("ftp://") {
+        return write_whole(url, &fetch(client, url, options, check_zip)?.content, out);
+    }
+    let mut resp = send_get(client, url, options, 0)?;
+    let headers = BodyHeaders::of(&resp);
+    let read_error =
+        |e: std::io::Error| format!("failed to read response body for '{}': {}", url, e);
+    let mut head = Vec::new();
+    (&mut resp)
+        .take(ZIP_SIGNATURES[0].len() as u64)
+        .read_to_end(&mut head)
+        .map_err(read_error)?;
+    if headers.encoding.is_none() && !(check_zip && head.starts_with(GZIP_MAGIC)) {
+        if check_zip {
+            check_zip_content(url, headers.content_type.as_deref(), &head)?;
+        }
+        return std::io::copy(&mut head.as_slice().chain(resp), out)
+            .and_then(|written| out.flush().map(|()| written))
+            .map_err(|e| format!("failed to copy '{}' to output: {}", url, e));
+    }
+    let mut content = head;
+    resp.read_to_end(&mut content).map_err(read_error)?;
+    let fetched = Fetched {
+        content: decoded(url, headers.encoding.as_deref(), content)?,
+        content_type: headers.content_type,
+    };
+    write_whole(url, &checked(url, fetched, check_zip)?.content, out)
+}
+
+/// Write `content`, the whole body of `url`, to `out`, returning its length.
+fn write_whole(url: &str, content: &[u8], out: &mut impl Write) -> Result<u64, String> {
+    out.write_all(content)
+        .and_then(|()| out.flush())
+        .map_err(|e| format!("failed to write '{}' to output: {}", url, e))?;
+    Ok(content.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BatchSummary, Download, DownloadOptions, SidecarCheck, download_url_to_path,
+        download_url_to_writer, download_verified, fetch_metadata, format_bytes, part_path,
+        preserve_mtime,
+    };
+    use crate::sha256::sha256_hex;
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use chrono::{TimeZone, Utc};
+    use reqwest::blocking::Client;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn download_writes_body() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23501-g00.zip" => Response::ok(b"PK\x03\x04spec".to_vec()),
+            _ => Response::not_found(),
+        });
+        let dir = scratch_dir("download-writes-body");
+        let dest = dir.join("23501-g00.zip");
+
+        let download = download_url_to_path(
+            &Client::new(),
+            &server.url("/23501-g00.zip"),
+            &dest,
+            &DownloadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(download.path, dest);
+        assert_eq!(download.size, 8);
+        assert_eq!(download.sha256, sha256_hex(b"PK\x03\x04spec"));
+        assert_eq!(std::fs::read(&dest).unwrap(), b"PK\x03\x04spec");
+        assert!(!part_path(&dest).exists());
+
+        let missing = download_url_to_path(
+            &Client::new(),
+            &server.url("/missing.zip"),
+            &dir.join("missing.zip"),
+            &DownloadOptions::default(),
+        );
+        assert!(missing.unwrap_err().contains("status 404"));
+    }
+
+    #[test]
+    fn preserved_mtime() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23501-g40.zip" => Response::ok(b"PK\x03\x04spec".to_vec()),
+            _ => Response::ok(b"PK\x03\x04spec".to_vec())
+                .header("Last-Modified", "Fri, 27 Mar 2020 14:30:00 GMT"),
+        });
+        let dir = scratch_dir("preserve-mtime");
+        let mtime = |download: &Download| {
+            let modified = std::fs::metadata(&download.path).unwrap().modified();
+            chrono::DateTime::<Utc>::from(modified.unwrap())
+        };
+        let download = |name: &str| {
+            download_url_to_path(
+                &Client::new(),
+                &server.url(&format!("/{}", name)),
+                &dir.join(name),
+                &DownloadOptions::default(),
+            )
+            .unwrap()
+        };
+
+        // The listing date of an item served without Last-Modified.
+        let listed = Utc.with_ymd_and_hms(2019, 12, 20, 10, 0, 0).unwrap();
+        let plain = download("23501-g40.zip");
+        assert_eq!(plain.last_modified, None);
+        preserve_mtime(&plain, Some(listed)).unwrap();
+        assert_eq!(mtime(&plain), listed);
+
+        let modified = download("23501-h10.zip");
+        let last_modified = Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap();
+        assert_eq!(modified.last_modified, Some(last_modified));
+        preserve_mtime(&modified, Some(listed)).unwrap();
+        assert_eq!(mtime(&modified), last_modified);
+
+        // Without a date, the time of the download is kept.
+        let undated = download("23501-i00.zip");
+        let before = mtime(&undated);
+        preserve_mtime(
+            &Download {
+                last_modified: None,
+                ..undated.clone()
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(mtime(&undated), before);
+    }
+
+    #[test]
+    fn resume_from_part_file() {
+        let body: Vec<u8> = b"PK\x03\x04"
+            .iter()
+            .copied()
+            .chain((0..2000).map(|i| i as u8))
+            .collect();
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let server = {
+            let (body, ranges) = (body.clone(), Arc::clone(&ranges));
+            MockServer::start(move |req| {
+                let range = req.header("range").map(str::to_string);
+                ranges.lock().unwrap().push(range.clone());
+                let start = range
+                    .and_then(|r| r.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok())
+                    .filter(|_| req.path != "/no-ranges.zip");
+                match start {
+                    // A lax server answers a range past the end with an empty one, not 416.
+                    Some(start) if start >= body.len() && req.path == "/lax.zip" => {
+                        Response::new(206, Vec::new()).header(
+                            "Content-Range",
+                            &format!("bytes {}-{}/{}", start, body.len() - 1, body.len()),
+                        )
+                    }
+                    Some(start) if start >= body.len() => Response::new(416, Vec::new()),
+                    Some(start) => Response::new(206, body[start..].to_vec()).header(
+                        "Content-Range",
+                        &format!("bytes {}-{}/{}", start, body.len() - 1, body.len()),
+                    ),
+                    // The connection drops partway through the first download.
+                    None if req.path == "/23501-g40.zip" => {
+                        Response::ok(body.clone()).truncated(700)
+                    }
+                    None => Response::ok(body.clone()),
+                }
+            })
+        };
+        let dir = scratch_dir("download-resume");
+        let download = |name: &str| {
+            ranges.lock().unwrap().clear();
+            let result = download_url_to_path(
+                &Client::new(),
+                &server.url(&format!("/{}", name)),
+                &dir.join(name),
+                &DownloadOptions::default(),
+            );
+            (result, ranges.lock().unwrap().clone())
+        };
+        let resumed = |name: &str, part: Option<&[u8]>| {
+            let dest = dir.join(name);
+            if let Some(part) = part {
+                std::fs::write(part_path(&dest), part).unwrap();
+            }
+            let (download, ranges) = download(name);
+            assert_eq!(download.unwrap().sha256, sha256_hex(&body));
+            assert_eq!(std::fs::read(&dest).unwrap(), body);
+            assert!(!part_path(&dest).exists());
+            ranges
+        };
+
+        // An interrupted download leaves the bytes it received in the `.part` file, which the
+        // next download completes with a range request.
+        let (interrupted, _) = download("23501-g40.zip");
+        assert!(interrupted.is_err());
+        let part = std::fs::read(part_path(&dir.join("23501-g40.zip"))).unwrap();
+        assert_eq!(part, body[..700]);
+        assert_eq!(
+            resumed("23501-g40.zip", None),
+            [Some("bytes=700-".to_string())]
+        );
+        // A `.part` file larger than the remote file cannot be a prefix of it.
+        let mut oversized = body.clone();
+        oversized.extend_from_slice(b"garbage");
+        assert_eq!(
+            resumed("23501-g50.zip", Some(&oversized)),
+            [Some(format!("bytes={}-", oversized.len())), None]
+        );
+        // As is one of the same size, e.g. a download that failed verification.
+        assert_eq!(
+            resumed("23501-g60.zip", Some(&body)),
+            [Some(format!("bytes={}-", body.len())), None]
+        );
+        // The size of the file in the Content-Range also shows the `.part` file is not a prefix.
+        assert_eq!(
+            resumed("lax.zip", Some(&oversized)),
+            [Some(format!("bytes={}-", oversized.len())), None]
+        );
+        // A server ignoring the range sends the whole file, which replaces the prefix.
+        assert_eq!(
+            resumed("no-ranges.zip", Some(&body[..700])),
+            [Some("bytes=700-".to_string())]
+        );
+    }
+
+    #[test]
+    fn reconnect_after_dropped_connection() {
+        let body: Vec<u8> = b"PK\x03\x04"
+            .iter()
+            .copied()
+            .chain((0..5000).map(|i| i as u8))
+            .collect();
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let server = {
+            let (body, ranges) = (body.clone(), Arc::clone(&ranges));
+            MockServer::start(move |req| {
+                let range = req.header("range").map(str::to_string);
+                let mut ranges = ranges.lock().unwrap();
+                ranges.push(range.clone());
+                let start: Option<usize> =
+                    range.and_then(|r| r.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok());
+                let response = match (req.path.as_str(), start) {
+                    ("/no-ranges.zip", _) => Response::ok(body.clone()),
+                    ("/misplaced-range.zip", Some(_)) => Response::new(206, body[10..].to_vec())
+                        .header(
+                            "Content-Range",
+                            &format!("bytes 10-{}/{}", body.len() - 1, body.len()),
+                        ),
+                    (_, Some(start)) => Response::new(206, body[start..].to_vec()).header(
+                        "Content-Range",
+                        &format!("bytes {}-{}/{}", start, body.len() - 1, body.len()),
+                    ),
+                    (_, None) => Response::ok(body.clone()).header("Accept-Ranges", "bytes"),
+                };
+                // The connection drops after 1000 bytes of the first two responses.
+                match ranges.len() {
+                    1 | 2 => response.truncated(1000),
+                    _ => response,
+                }
+            })
+        };
+        let dir = scratch_dir("download-reconnect");
+        let download = |name: &str, reconnects| {
+            ranges.lock().unwrap().clear();
+            let dest = dir.join(name);
+            let options = DownloadOptions {
+                reconnects,
+                ..DownloadOptions::default()
+            };
+            let result = download_url_to_path(
+                &Client::new(),
+                &server.url(&format!("/{}", name)),
+                &dest,
+                &options,
+            );
+            if result.is_ok() {
+                assert_eq!(std::fs::read(&dest).unwrap(), body);
+            }
+            (result, ranges.lock().unwrap().clone())
+        };
+
+        // Each reconnect asks for the bytes after those received so far.
+        let (result, requested) = download("23501-g40.zip", 2);
+        assert_eq!(result.unwrap().sha256, sha256_hex(&body));
+        assert_eq!(
+            requested,
+            [
+                None,
+                Some("bytes=1000-".to_string()),
+                Some("bytes=2000-".to_string())
+            ]
+        );
+
+        // Without range support, the body is received again from the start.
+        let (result, requested) = download("no-ranges.zip", 2);
+        assert!(result.is_ok());
+        assert_eq!(requested, [None, None, None]);
+
+        let (result, requested) = download("23501-g50.zip", 1);
+        let err = result.unwrap_err();
+        assert!(err.starts_with("failed to read response body"), "{}", err);
+        assert_eq!(requested.len(), 2);
+        assert!(!dir.join("23501-g50.zip").exists());
+        // The reconnect appended to the `.part` file from its length on disk.
+        let part = std::fs::read(part_path(&dir.join("23501-g50.zip"))).unwrap();
+        assert_eq!(part, body[..2000]);
+
+        // A server answering with a range starting elsewhere than requested is asked for the
+        // whole file instead.
+        let (result, requested) = download("misplaced-range.zip", 1);
+        assert!(result.is_ok());
+        assert_eq!(requested, [None, Some("bytes=1000-".to_string()), None]);
+    }
+
+    #[test]
+    fn failed_download_leaves_only_part_file() {
+        let body: Vec<u8> = b"PK\x03\x04"
+            .iter()
+            .copied()
+            .chain((0..200_000).map(|i| i as u8))
+            .collect();
+        let server = {
+            let body = body.clone();
+            MockServer::start(move |req| match req.path.as_str() {
+                // The connection drops partway through the body.
+                "/23501-g40.zip" => Response::ok(body.clone()).truncated(150_000),
+                _ => Response::ok(b"PK\x03\x04spec".to_vec()),
+            })
+        };
+        let dir = scratch_dir("download-part-file");
+        let dest = dir.join("23501-g40.zip");
+        assert_eq!(part_path(&dest), dir.join("23501-g40.zip.part"));
+
+        let err = download_url_to_path(
+            &Client::new(),
+            &server.url("/23501-g40.zip"),
+            &dest,
+            &DownloadOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.starts_with("failed to read response body"), "{}", err);
+        assert!(!dest.exists());
+        // The bytes received before the connection dropped were streamed to the `.part` file.
+        assert_eq!(std::fs::read(part_path(&dest)).unwrap(), body[..150_000]);
+
+        let dest = dir.join("23501-g00.zip");
+
+        let err = download_verified(
+            &Client::new(),
+            &server.url("/23501-g00.zip"),
+            &dest,
+            &DownloadOptions::default(),
+            |_| Err("interrupted".to_string()),
+        )
+        .unwrap_err();
+        assert_eq!(err, "interrupted");
+        assert!(!dest.exists());
+        assert_eq!(std::fs::read(part_path(&dest)).unwrap(), b"PK\x03\x04spec");
+
+        download_url_to_path(
+            &Client::new(),
+            &server.url("/23501-g00.zip"),
+            &dest,
+            &DownloadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"PK\x03\x04spec");
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[test]
+    fn sidecar_verification() {
+        let digest = sha256_hex(b"PK\x03\x04spec");
+        let sidecar = format!("{}  23501-g40.zip\n", digest);
+        let server = MockServer::start(move |req| match req.path.as_str() {
+            "/23501-g40.zip" | "/23501-g41.zip" | "/23501-g42.zip" | "/23501-g43.zip" => {
+                Response::ok(b"PK\x03\x04spec".to_vec())
+            }
+            "/23501-g40.zip.sha256" => Response::ok(sidecar.clone()),
+            "/23501-g41.zip.sha256" => Response::ok(format!("{}\n", "0".repeat(64))),
+            "/23501-g43.zip.sha256" => Response::ok("not a digest"),
+            _ => Response::not_found(),
+        });
+        let dir = scratch_dir("download-sidecar");
+        let client = Client::new();
+        let download = |name: &str, sidecar| {
+            let options = DownloadOptions {
+                sidecar,
+                ..DownloadOptions::default()
+            };
+            download_url_to_path(
+                &client,
+                &server.url(&format!("/{}", name)),
+                &dir.join(name),
+                &options,
+            )
+        };
+
+        let matching = download("23501-g40.zip", SidecarCheck::Required).unwrap();
+        assert_eq!(matching.sha256, digest);
+        assert!(dir.join("23501-g40.zip").exists());
+
+        let err = download("23501-g41.zip", SidecarCheck::IfPresent).unwrap_err();
+        assert!(err.starts_with("SHA-256 mismatch for '"), "{}", err);
+        assert!(err.contains(&format!("sidecar has {}, got {}", "0".repeat(64), digest)));
+        assert!(!dir.join("23501-g41.zip").exists());
+        assert!(part_path(&dir.join("23501-g41.zip")).exists());
+
+        download("23501-g42.zip", SidecarCheck::IfPresent).unwrap();
+        assert!(dir.join("23501-g42.zip").exists());
+        let err = download("23501-g42.zip", SidecarCheck::Required).unwrap_err();
+        assert!(err.starts_with("no checksum sidecar for '"), "{}", err);
+        assert!(err.contains("status 404"), "{}", err);
+
+        let err = download("23501-g43.zip", SidecarCheck::IfPresent).unwrap_err();
+        assert!(err.contains("expected a SHA-256 hex digest"), "{}", err);
+        let unchecked = download("23501-g43.zip", SidecarCheck::Off).unwrap();
+        assert_eq!(unchecked.sha256, digest);
+    }
+
+    #[test]
+    fn download_to_writer() {
+        let payload: Vec<u8> = b"PK\x03\x04".iter().copied().chain(0..=255).collect();
+        let body = payload.clone();
+        let server = MockServer::start(move |req| match req.path.as_str() {
+            "/23501-g00.zip" => Response::ok(body.clone()),
+            "/error.zip" => Response::ok("<html>error</html>"),
+            "/dropped.zip" => Response::ok(vec![b'P'; 200_000]).truncated(100_000),
+            _ => Response::not_found(),
+        });
+        let client = Client::new();
+        let options = DownloadOptions::default();
+
+        let mut out = Vec::new();
+        let size =
+            download_url_to_writer(&client, &server.url("/23501-g00.zip"), &mut out, &options)
+                .unwrap();
+        assert_eq!(size, payload.len() as u64);
+        assert_eq!(out, payload);
+
+        let mut out = Vec::new();
+        let err = download_url_to_writer(&client, &server.url("/error.zip"), &mut out, &options)
+            .unwrap_err();
+        assert!(err.contains("expected a ZIP archive"), "{}", err);
+        assert!(out.is_empty());
+
+        // The body is streamed: what arrived before the connection dropped was written.
+        let options = DownloadOptions {
+            check_content: false,
+            ..options
+        };
+        let mut out = Vec::new();
+        let err = download_url_to_writer(&client, &server.url("/dropped.zip"), &mut out, &options)
+            .unwrap_err();
+        assert!(err.starts_with("failed to copy"), "{}", err);
+        assert_eq!(out.len(), 100_000);
+    }
+
+    #[test]
+    fn head_only_metadata() {
+        let server = MockServer::start(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("HEAD", "/23501-g40.zip") => {
+                Response::ok(vec![0; 1536]).header("Last-Modified", "Fri, 27 Mar 2020 14:30:00 GMT")
+            }
+            ("HEAD", "/no-dates.zip") => Response::ok(""),
+            ("HEAD", "/rejected.zip") => Response::new(405, "method not allowed"),
+            ("HEAD", _) => Response::not_found(),
+            _ => Response::new(500, "body requested"),
+        });
+        let client = Client::new();
+
+        let metadata = fetch_metadata(&client, &server.url("/23501-g40.zip")).unwrap();
+        assert_eq!(metadata.status, 200);
+        assert_eq!(metadata.content_length, Some(1536));
+        assert_eq!(
+            metadata.last_modified,
+            Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).single()
+        );
+        assert_eq!(
+            metadata.to_string(),
+            format!(
+                "200 1.5 KiB 2020-03-27T14:30:00+00:00 {}",
+                server.url("/23501-g40.zip")
+            )
+        );
+
+        let metadata = fetch_metadata(&client, &server.url("/no-dates.zip")).unwrap();
+        assert_eq!(metadata.content_length, Some(0));
+        assert_eq!(metadata.last_modified, None);
+
+        let missing = fetch_metadata(&client, &server.url("/missing.zip")).unwrap();
+        assert_eq!(missing.status, 404);
+
+        let err = fetch_metadata(&client, &server.url("/rejected.zip")).unwrap_err();
+        assert!(err.contains("rejected HEAD"), "{}", err);
+        assert!(err.contains("405"), "{}", err);
+    }
+
+    #[test]
+    fn content_check() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/real.zip" => Response::ok(b"PK\x03\x04zipdata".to_vec())
+                .header("Content-Type", "application/zip"),
+            "/error.zip" => Response::ok("<html><body>Service unavailable</body></html>")
+                .header("Content-Type", "text/html; charset=utf-8"),
+            _ => Response::not_found(),
+        });
+        let dir = scratch_dir("download-content-check");
+        let client = Client::new();
+        let checked = DownloadOptions::default();
+
+        let ok = download_url_to_path(
+            &client,
+            &server.url("/real.zip"),
+            &dir.join("real.zip"),
+            &checked,
+        );
+        assert_eq!(ok.unwrap().size, 11);
+
+        let dest = dir.join("error.zip");
+        let err =
+            download_url_to_path(&client, &server.url("/error.zip"), &dest, &checked).unwrap_err();
+        assert!(err.contains("text/html"), "{}", err);
+        assert!(!dest.exists());
+
+        let unchecked = DownloadOptions {
+            check_content: false,
+            ..DownloadOptions::default()
+        };
+        let download =
+            download_url_to_path(&client, &server.url("/error.zip"), &dest, &unchecked).unwrap();
+        assert!(
+            std::fs::read_to_string(download.path)
+                .unwrap()
+                .contains("Service unavailable")
+        );
+    }
+
+    #[test]
+    fn gzip_wrapped_zip() {
+        // gzip of "PK\x03\x04doubly wrapped spec".
+        const GZIPPED: [u8; 43] = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x0b, 0xf0, 0x66, 0x66,
+            0x49, 0xc9, 0x2f, 0x4d, 0xca, 0xa9, 0x54, 0x28, 0x2f, 0x4a, 0x2c, 0x28, 0x48, 0x4d,
+            0x51, 0x28, 0x2e, 0x48, 0x4d, 0x06, 0x00, 0x65, 0x97, 0x93, 0xba, 0x17, 0x00, 0x00,
+            0x00,
+        ];
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/encoded.zip" => Response::ok(GZIPPED.to_vec()).header("Content-Encoding", "gzip"),
+            "/unlabeled.zip" => Response::ok(GZIPPED.to_vec()),
+            "/html.zip" => Response::ok(GZIPPED[..20].to_vec()),
+            _ => Response::not_found(),
+        });
+        let dir = scratch_dir("download-gzip-wrapped");
+        let client = Client::new();
+        let options = DownloadOptions::default();
+
+        for name in ["encoded.zip", "unlabeled.zip"] {
+            let dest = dir.join(name);
+            let download =
+                download_url_to_path(&client, &server.url(&format!("/{}", name)), &dest, &options)
+                    .unwrap();
+            assert_eq!(
+                std::fs::read(&dest).unwrap(),
+                b"PK\x03\x04doubly wrapped spec"
+            );
+            assert_eq!(download.size, 23);
+        }
+
+        let dest = dir.join("html.zip");
+        let err =
+            download_url_to_path(&client, &server.url("/html.zip"), &dest, &options).unwrap_err();
+        assert!(err.contains("transfer encoding"), "{}", err);
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn download_over_ftp() {
+        let server = MockServer::start_ftp(|req| match req.path.as_str() {
+            "/23501-g40.zip" => Response::ok(b"PK\x03\x04spec".to_vec()),
+            _ => Response::not_found(),
+        });
+        let dir = scratch_dir("download-over-ftp");
+        let dest = dir.join("23501-g40.zip");
+
+        let download = download_url_to_path(
+            &Client::new(),
+            &server.url("/23501-g40.zip"),
+            &dest,
+            &DownloadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(download.size, 8);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"PK\x03\x04spec");
+    }
+
+    #[test]
+    fn byte_formatting() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn batch_summary() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/a.zip" => Response::ok(vec![b'P'; 1024]),
+            "/b.zip" => Response::ok(vec![b'P'; 2048]),
+            _ => Response::not_found(),
+        });
+        let dir = scratch_dir("download-batch-summary");
+        let client = Client::new();
+        let options = DownloadOptions {
+            check_content: false,
+            ..DownloadOptions::default()
+        };
+
+        let mut summary = BatchSummary::default();
+        for name in ["a.zip", "b.zip", "c.zip"] {
+            let url = server.url(&format!("/{}", name));
+            summary.record(&download_url_to_path(
+                &client,
+                &url,
+                &dir.join(name),
+                &options,
+            ));
+        }
+        summary.skipped = 1;
+        summary.elapsed = std::time::Duration::from_millis(1500);
+
+        assert_eq!(summary.downloaded, 2);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.bytes, 3072);
+        assert_eq!(
+            summary.to_string(),
+            "downloaded 2 files (3.0 KiB) in 1.5s at 2.0 KiB/s; 1 skipped, 1 failed"
+        );
+    }
+
+    #[test]
+    fn batch_summary_json() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/a.zip" => Response::ok(b"PK\x03\x04a".to_vec()),
+            "/c.zip" => Response::ok(b"PK\x03\x04c".to_vec()),
+            _ => Response::not_found(),
+        });
+        let dir = scratch_dir("download-batch-summary-json");
+        let client = Client::new();
+        let mut summary = BatchSummary::default();
+        for name in ["a.zip", "b.zip", "c.zip"] {
+            let url = server.url(&format!("/{}", name));
+            let dest = dir.join(name);
+            let result = download_url_to_path(&client, &url, &dest, &DownloadOptions::default());
+            summary.record_item(&url, &dest, &result);
+        }
+        summary.elapsed = std::time::Duration::from_millis(250);
+
+        let path = dir.join("summary.json");
+        summary.write(&path).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["downloaded"], 2);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["skipped"], 0);
+        assert_eq!(json["bytes"], 10);
+        assert_eq!(json["elapsed_secs"], 0.25);
+        let items = json["items"].as_array().unwrap();
+        let statuses: Vec<_> = items
+            .iter()
+            .map(|i| i["status"].as_str().unwrap())
+            .collect();
+        assert_eq!(statuses, ["ok", "failed", "ok"]);
+        assert_eq!(items[0]["url"], server.url("/a.zip"));
+        assert_eq!(items[0]["dest"], dir.join("a.zip").display().to_string());
+        assert_eq!(items[0]["error"], serde_json::Value::Null);
+        assert!(
+            items[1]["error"].as_str().unwrap().contains("404"),
+            "{}",
+            items[1]
+        );
+        assert!(!dir.join("b.zip").exists());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::SpecNumber;
+use std::sync::Arc;
+
+/// Errors returned when listing spec directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The spec directory was fetched successfully but its listing has no rows at all.
+    EmptyDirectory(SpecNumber),
+    /// No connection could be made to the server of `url`, e.g. because its host name does not
+    /// resolve or nothing listens on its port.
+    Connect { url: String, source: ConnectError },
+    /// The request for `url` failed before a response was received, e.g. a network error.
+    Request { url: String, message: String },
+    /// The server answered the request for `url` with a non-success status code.
+    Status { url: String, status: u16 },
+    /// The URL built for a spec directory, `attempted_url`, is not below the archive root
+    /// `base`, which happens when the spec number contains path characters such as `/` or `..`.
+    SecurityCheck { attempted_url: String, base: String },
+    /// The release filter excluded every listed version of `spec`; `available` holds the
+    /// releases that are listed, in ascending order.
+    ReleaseNotFound {
+        spec: SpecNumber,
+        release: u32,
+        available: Vec<u32>,
+    },
+    /// The deadline set with `--timeout-total` passed before the request for `url` completed.
+    Timeout { url: String },
+    /// Any other failure, described by a human-friendly message.
+    Other(String),
+}
+
+impl Error {
+    /// Short machine-readable name of the variant, e.g. `"status"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::EmptyDirectory(_) => "empty-directory",
+            Error::Connect { .. } => "connect",
+            Error::Request { .. } => "request",
+            Error::Status { .. } => "status",
+            Error::SecurityCheck { .. } => "security-check",
+            Error::ReleaseNotFound { .. } => "release-not-found",
+            Error::Timeout { .. } => "timeout",
+            Error::Other(_) => "other",
+        }
+    }
+
+    /// URL of the failed request, if the error is about one.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Error::Connect { url, .. }
+            | Error::Request { url, .. }
+            | Error::Status { url, .. }
+            | Error::Timeout { url } => Some(url),
+            Error::SecurityCheck { attempted_url, .. } => Some(attempted_url),
+            _ => None,
+        }
+    }
+
+    /// HTTP status code of the response, if the server answered with an error status.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::EmptyDirectory(spec) => write!(f, "spec directory for {} is empty", spec),
+            Error::Connect { url, source } => {
+                let host = reqwest::Url::parse(url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(str::to_string))
+                    .unwrap_or_else(|| url.clone());
+                write!(
+                    f,
+                    "cannot connect to '{}' to fetch '{}': {}; check the network connection, \
+                     the proxy settings and the base URL",
+                    host, url, source
+                )
+            }
+            Error::Request { url, message } => {
+                write!(f, "failed to fetch URL '{}': {}", url, message)
+            }
+            Error::Status { url, status } => match reqwest::StatusCode::from_u16(*status) {
+                Ok(code) => write!(f, "failed to fetch URL '{}': status code {}", url, code),
+                Err(_) => write!(f, "failed to fetch URL '{}': status code {}", url, status),
+            },
+            Error::SecurityCheck {
+                attempted_url,
+                base,
+            } => {
+                let parse = |url: &str| reqwest::Url::parse(url).ok();
+                let constraint = match (parse(attempted_url), parse(base)) {
+                    (Some(a), Some(b)) if a.origin() != b.origin() => {
+                        "it points to a different scheme, host or port"
+                    }
+                    _ => "its path escapes the base path",
+                };
+                write!(
+                    f,
+                    "security check failed: URL '{}' is not below the base URL '{}', as {}; \
+                     the spec number likely contains invalid path characters such as '/' or '..'",
+                    attempted_url, base, constraint
+                )
+            }
+            Error::ReleaseNotFound {
+                spec,
+                release,
+                available,
+            } => {
+                let available: Vec<_> = available.iter().map(|r| r.to_string()).collect();
+                write!(
+                    f,
+                    "no items of {} for release {}; available: {}",
+                    spec,
+                    release,
+                    available.join(", ")
+                )
+            }
+            Error::Timeout { url } => {
+                write!(f, "total timeout exceeded while fetching '{}'", url)
+            }
+            Error::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Connect { source, .. } => Some(&*source.0),
+            _ => None,
+        }
+    }
+}
+
+/// Connection error underlying an `Error::Connect`, shared so that `Error` stays cloneable.
+/// Errors are equal if they share it.
+#[derive(Debug, Clone)]
+pub struct ConnectError(Arc<reqwest::Error>);
+
+impl ConnectError {
+    pub fn new(error: reqwest::Error) -> Self {
+        ConnectError(Arc::new(error))
+    }
+}
+
+impl PartialEq for ConnectError {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ConnectError {}
+
+/// The innermost cause of the error, e.g. the DNS failure or the refused connection, as reqwest
+/// repeats its causes in its own message.
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut cause: &dyn std::error::Error = &*self.0;
+        while let Some(source) = cause.source() {
+            cause = source;
+        }
+        write!(f, "{}", cause)
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Error::Other(msg)
+    }
+}
@@ -0,0 +1,180 @@
+//! Atom feed rendering of listing results, for subscribing to spec updates.
+
+use crate::{BASE_URL, SpecItem, SpecNumber};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+/// Escape the characters that are special in XML text and attribute values.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Render `items` listed for `specs` as an Atom feed with one `<entry>` per item, titled with
+/// its spec number and version and linking to its URL.
+///
+/// The feed's `<id>` is the directory URL of a single spec, or the archive root with the spec
+/// numbers as fragment for several. Its `<updated>` is the date of the newest item, or `now`
+/// when there are no items, so that an empty listing still renders a valid feed.
+pub fn atom_feed(specs: &[SpecNumber], items: &[SpecItem], now: DateTime<Utc>) -> String {
+    let updated = items.iter().map(|i| i.date).max().unwrap_or(now);
+    let names: Vec<String> = specs.iter().map(|s| s.to_string()).collect();
+    let id = match specs {
+        [spec] => format!("{}{}_series/{}", BASE_URL, spec.series, spec),
+        _ => format!("{}#{}", BASE_URL, names.join(",")),
+    };
+    let mut out = String::new();
+
+    let _ = writeln!(out, r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    let _ = writeln!(out, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    let _ = writeln!(out, "  <id>{}</id>", escape(&id));
+    let _ = writeln!(
+        out,
+        "  <title>3GPP {} versions</title>",
+        escape(&names.join(", "))
+    );
+    let _ = writeln!(out, "  <updated>{}</updated>", updated.to_rfc3339());
+    let _ = writeln!(out, "  <link href=\"{}\"/>", escape(&id));
+    let _ = writeln!(out, "  <author><name>3GPP</name></author>");
+    for item in items {
+        let _ = writeln!(out, "  <entry>");
+        let _ = writeln!(out, "    <id>{}</id>", escape(&item.url));
+        // Unversioned items are titled with their filename instead.
+        let label = match item.version {
+            Some(version) => version.to_string(),
+            None => item.filename().to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "    <title>{} {}</title>",
+            escape(&item.spec.to_string()),
+            escape(&label)
+        );
+        let _ = writeln!(out, "    <updated>{}</updated>", item.date.to_rfc3339());
+        let _ = writeln!(out, "    <link href=\"{}\"/>", escape(&item.url));
+        let _ = writeln!(out, "  </entry>");
+    }
+    let _ = writeln!(out, "</feed>");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::atom_feed;
+    use crate::{SpecItem, Version, parse_spec_number};
+    use chrono::{TimeZone, Utc};
+    use scraper::{Html, Selector};
+
+    fn item(minor: u32, day: u32, url: &str) -> SpecItem {
+        SpecItem {
+            spec: parse_spec_number("23.501").unwrap(),
+            version: Some(Version {
+                major: 16,
+                minor,
+                editorial: 0,
+            }),
+            release: None,
+            date: Utc.with_ymd_and_hms(2020, 6, day, 10, 0, 0).unwrap(),
+            url: url.to_string(),
+            filename: String::new(),
+            draft: false,
+            size: None,
+        }
+    }
+
+    /// Text of every element matching `selector` in `feed`.
+    fn texts(feed: &Html, selector: &str) -> Vec<String> {
+        let selector = Selector::parse(selector).unwrap();
+        feed.select(&selector)
+            .map(|e| e.text().collect::<String>())
+            .collect()
+    }
+
+    #[test]
+    fn entries_for_items() {
+        let spec = parse_spec_number("23.501").unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let items = [
+            item(4, 1, "https://example.com/23501-g40.zip"),
+            item(5, 12, "https://example.com/23501-g50.zip?a=1&b=2"),
+        ];
+        let xml = atom_feed(&[spec], &items, now);
+        assert!(xml.starts_with("<?xml version=\"1.0\""));
+        assert!(xml.contains("?a=1&amp;b=2"));
+
+        let feed = Html::parse_fragment(&xml);
+        assert_eq!(texts(&feed, "feed > entry").len(), 2);
+        assert_eq!(
+            texts(&feed, "entry > title"),
+            ["23.501 16.4.0", "23.501 16.5.0"]
+        );
+        assert_eq!(
+            texts(&feed, "entry > updated"),
+            ["2020-06-01T10:00:00+00:00", "2020-06-12T10:00:00+00:00"]
+        );
+        let links = Selector::parse("entry > link").unwrap();
+        let hrefs: Vec<_> = feed
+            .select(&links)
+            .filter_map(|e| e.value().attr("href"))
+            .collect();
+        assert_eq!(
+            hrefs,
+            [
+                "https://example.com/23501-g40.zip",
+                "https://example.com/23501-g50.zip?a=1&b=2"
+            ]
+        );
+        assert_eq!(
+            texts(&feed, "feed > updated"),
+            ["2020-06-12T10:00:00+00:00"]
+        );
+    }
+
+    #[test]
+    fn several_specs() {
+        let specs = [
+            parse_spec_number("23.501").unwrap(),
+            parse_spec_number("24.501").unwrap(),
+        ];
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let feed = Html::parse_fragment(&atom_feed(&specs, &[], now));
+        assert_eq!(
+            texts(&feed, "feed > title"),
+            ["3GPP 23.501, 24.501 versions"]
+        );
+        assert_eq!(
+            texts(&feed, "feed > id"),
+            ["https://www.3gpp.org/ftp/Specs/archive/#23.501,24.501"]
+        );
+    }
+
+    #[test]
+    fn empty_feed() {
+        let spec = parse_spec_number("23.501").unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let xml = atom_feed(&[spec], &[], now);
+        assert!(xml.trim_end().ends_with("</feed>"));
+
+        let feed = Html::parse_fragment(&xml);
+        assert!(texts(&feed, "entry").is_empty());
+        assert_eq!(
+            texts(&feed, "feed > updated"),
+            ["2024-01-01T00:00:00+00:00"]
+        );
+        assert_eq!(
+            texts(&feed, "feed > id"),
+            ["https://www.3gpp.org/ftp/Specs/archive/23_series/23.501"]
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! Minimal passive-mode FTP client and listing parser, for mirrors that still serve the archive
+//! over `ftp://` instead of HTTP.
+
+use crate::{Error, ListQuery, SpecItem, listed_item, sort_canonical};
+use chrono::{DateTime, Datelike, Utc};
+use reqwest::Url;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// How long to wait for the server on the control and data connections.
+const FTP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Logged-in control connection to an FTP server.
+struct Session {
+    control: BufReader<TcpStream>,
+    url: String,
+}
+
+impl Session {
+    /// Connect to the server of `url` and log in, anonymously unless the URL has credentials.
+    fn connect(url: &Url) -> Result<Self, String> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("invalid FTP URL '{}': no host", url))?;
+        let port = url.port().unwrap_or(21);
+        let stream = TcpStream::connect((host, port))
+            .map_err(|e| format!("failed to connect to '{}': {}", url, e))?;
+        stream
+            .set_read_timeout(Some(FTP_TIMEOUT))
+            .map_err(|e| format!("failed to connect to '{}': {}", url, e))?;
+
+        let mut session = Session {
+            control: BufReader::new(stream),
+            url: url.to_string(),
+        };
+        session.expect(None, &[220])?;
+        let user = match url.username() {
+            "" => "anonymous",
+            user => user,
+        };
+        if session.expect(Some(&format!("USER {}", user)), &[230, 331])? == 331 {
+            let password = url.password().unwrap_or("anonymous@");
+            session.expect(Some(&format!("PASS {}", password)), &[230])?;
+        }
+        session.expect(Some("TYPE I"), &[200])?;
+        Ok(session)
+    }
+
+    /// Read one (possibly multi-line) reply and return its code and text.
+    fn reply(&mut self) -> Result<(u16, String), String> {
+        let mut text = String::new();
+        let mut first = String::new();
+        loop {
+            let mut line = String::new();
+            match self.control.read_line(&mut line) {
+                Ok(0) => {
+                    return Err(format!(
+                        "FTP server for '{}' closed the connection",
+                        self.url
+                    ));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    return Err(format!(
+                        "failed to read FTP reply for '{}': {}",
+                        self.url, e
+                    ));
+                }
+            }
+            text.push_str(&line);
+            if first.is_empty() {
+                first = line.clone();
+            }
+            // A multi-line reply starts with `123-` and ends with a line starting with `123 `.
+            let last = match first.as_bytes().get(3) {
+                Some(b'-') => line.len() > 3 && line[..3] == first[..3] && &line[3..4] == " ",
+                _ => true,
+            };
+            if last {
+                break;
+            }
+        }
+        let code = first
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| format!("invalid FTP reply for '{}': {}", self.url, first.trim()))?;
+        Ok((code, text.trim_end().to_string()))
+    }
+
+    /// Send `command` (if any), read the reply and fail unless its code is one of `codes`.
+    fn expect(&mut self, command: Option<&str>, codes: &[u16]) -> Result<u16, String> {
+        if let Some(command) = command {
+            self.control
+                .get_mut()
+                .write_all(format!("{}\r\n", command).as_bytes())
+                .map_err(|e| format!("failed to send FTP command for '{}': {}", self.url, e))?;
+        }
+        let (code, text) = self.reply()?;
+        match codes.contains(&code) {
+            true => Ok(code),
+            false => Err(format!("FTP request for '{}' failed: {}", self.url, text)),
+        }
+    }
+
+    /// Run `command` over a passive-mode data connection and return the transferred bytes.
+    fn transfer(&mut self, command: &str) -> Result<Vec<u8>, String> {
+        self.control
+            .get_mut()
+            .write_all(b"PASV\r\n")
+            .map_err(|e| format!("failed to send FTP command for '{}': {}", self.url, e))?;
+        let (code, text) = self.reply()?;
+        if code != 227 {
+            return Err(format!("FTP request for '{}' failed: {}", self.url, text));
+        }
+        let port = parse_pasv(&text)
+            .ok_or_else(|| format!("invalid FTP passive reply for '{}': {}", self.url, text))?;
+        // Connect to the control connection's address rather than the announced one, which is
+        // often wrong behind NAT.
+        let host = self
+            .control
+            .get_ref()
+            .peer_addr()
+            .map_err(|e| format!("failed to connect to '{}': {}", self.url, e))?
+            .ip();
+        let mut data = TcpStream::connect((host, port)).map_err(|e| {
+            format!(
+                "failed to open FTP data connection for '{}': {}",
+                self.url, e
+            )
+        })?;
+        data.set_read_timeout(Some(FTP_TIMEOUT)).map_err(|e| {
+            format!(
+                "failed to open FTP data connection for '{}': {}",
+                self.url, e
+            )
+        })?;
+
+        self.expect(Some(command), &[125, 150])?;
+        let mut body = Vec::new();
+        data.read_to_end(&mut body)
+            .map_err(|e| format!("failed to read FTP data for '{}': {}", self.url, e))?;
+        drop(data);
+        self.expect(None, &[226, 250])?;
+        Ok(body)
+    }
+
+    fn quit(mut self) {
+        let _ = self.expect(Some("QUIT"), &[221]);
+    }
+}
+
+/// Data port announced in a `227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)` reply.
+fn parse_pasv(text: &str) -> Option<u16> {
+    let start = text.find('(')?;
+    let end = text[start..].find(')')? + start;
+    let numbers: Vec<u16> = text[start + 1..end]
+        .split(',')
+        .map(|n| n.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [_, _, _, _, p1, p2] if p1 < 256 && p2 < 256 => Some(p1 * 256 + p2),
+        _ => None,
+    }
+}
+
+/// Fetch the `LIST` output for the directory at `url`.
+pub(crate) fn fetch_listing(url: &Url) -> Result<String, String> {
+    let mut session = Session::connect(url)?;
+    let body = session.transfer(&format!("LIST {}", url.path()))?;
+    session.quit();
+    String::from_utf8(body).map_err(|e| format!("invalid FTP listing for '{}': {}", url, e))
+}
+
+/// Download the file at `url`.
+pub(crate) fn retrieve(url: &str) -> Result<Vec<u8>, String> {
+    let url = Url::parse(url).map_err(|e| format!("invalid FTP URL '{}': {}", url, e))?;
+    let mut session = Session::connect(&url)?;
+    let body = session
+        .transfer(&format!("RETR {}", url.path()))
+        .map_err(|e| format!("failed to download '{}': {}", url, e))?;
+    session.quit();
+    Ok(body)
+}
+
+/// Parse the date of a Unix-style listing line: `Mar 27 14:30` for files modified in the last
+/// six months (the year is the one that puts the date closest before `now`) or `Mar 27  2020`.
+fn parse_ftp_date(
+    month: &str,
+    day: &str,
+    time_or_year: &str,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let parse = |year: i32, time: &str| {
+        let text = format!("{} {} {} {}", year, month, day, time);
+        chrono::NaiveDateTime::parse_from_str(&text, "%Y %b %d %H:%M")
+            .ok()
+            .map(|dt| DateTime::from_naive_utc_and_offset(dt, Utc))
+    };
+    match time_or_year.contains(':') {
+        false => parse(time_or_year.parse().ok()?, "00:00"),
+        true => {
+            let date = parse(now.year(), time_or_year)?;
+            match date > now + chrono::Days::new(1) {
+                true => parse(now.year() - 1, time_or_year),
+                false => Some(date),
+            }
+        }
+    }
+}
+
+/// Parse the Unix-style (`ls -l`) `LIST` output of the directory at `url` into spec items,
+/// applying the filters in `query`, and collect the names of its subdirectories with a trailing
+/// `/`. `now` resolves dates listed without a year.
+pub(crate) fn parse_listing(
+    body: &str,
+    url: &Url,
+    query: &ListQuery,
+    now: DateTime<Utc>,
+) -> Result<(Vec<SpecItem>, Vec<String>), Error> {
+    let prefix = format!("{}/", url.as_str().trim_end_matches('/'));
+    let dir_url = Url::parse(&prefix)
+        .map_err(|e| format!("failed to parse directory URL '{}': {}", prefix, e))?;
+
+    let mut specs = Vec::new();
+    let mut directories = Vec::new();
+    let mut rows = 0;
+    for line in body.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 9 {
+            continue;
+        }
+        let name = fields[8..].join(" ");
+        if name == "." || name == ".." {
+            continue;
+        }
+        rows += 1;
+
+        match fields[0].chars().next() {
+            Some('d') => {
+                directories.push(format!("{}/", name));
+                continue;
+            }
+            Some('-') => {}
+            _ => continue,
+        }
+        let Some(date) = parse_ftp_date(fields[5], fields[6], fields[7], now) else {
+            continue;
+        };
+        let Ok(file_url) = dir_url.join(&name) else {
+            continue;
+        };
+        let size = fields[4].parse().ok();
+        let date = query.listing_date(date);
+        if let Some(item) = listed_item(query, &name, file_url.to_string(), date, size, None) {
+            specs.push(item);
+        }
+    }
+
+    if rows == 0 {
+        return Err(Error::EmptyDirectory(query.spec_number.clone()));
+    }
+
+    sort_canonical(&mut specs);
+    Ok((specs, directories))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ftp_date, parse_listing, retrieve};
+    use crate::test_support::{MockServer, Response};
+    use crate::{Error, FixedClock, ListQuery, SharedClock, list, parse_spec_number};
+    use chrono::{TimeZone, Utc};
+    use reqwest::Url;
+    use reqwest::blocking::Client;
+
+    const LISTING: &str = "\
+total 12
+drwxr-xr-x   2 ftp      ftp          4096 Jan 10  2021 .
+drwxr-xr-x  40 ftp      ftp          4096 Jan 10  2021 ..
+-rw-r--r--   1 ftp      ftp         81101 Dec  1  2017 23501-f00.zip
+-rw-r--r--   1 ftp      ftp       1234567 Mar 27 14:30 23501-g40.zip
+drwxr-xr-x   2 ftp      ftp          4096 Jun 12  2020 Versions
+lrwxrwxrwx   1 ftp      ftp            13 Jun 12  2020 latest.zip -> 23501-g40.zip
+-rw-r--r--   1 ftp      ftp           512 Jun 12  2020 readme.txt
+";
+
+    fn query() -> ListQuery {
+        ListQuery::new(parse_spec_number("23.501").unwrap())
+    }
+
+    #[test]
+    fn ftp_dates() {
+        let now = Utc.with_ymd_and_hms(2020, 6, 30, 0, 0, 0).unwrap();
+        assert_eq!(
+            parse_ftp_date("Mar", "27", "14:30", now),
+            Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).single()
+        );
+        assert_eq!(
+            parse_ftp_date("Dec", "1", "09:15", now),
+            Utc.with_ymd_and_hms(2019, 12, 1, 9, 15, 0).single()
+        );
+        assert_eq!(
+            parse_ftp_date("Dec", "1", "2017", now),
+            Utc.with_ymd_and_hms(2017, 12, 1, 0, 0, 0).single()
+        );
+        assert_eq!(parse_ftp_date("Foo", "1", "2017", now), None);
+    }
+
+    #[test]
+    fn ftp_listing_items() {
+        let url = Url::parse("ftp://mirror.example.com/Specs/archive/23_series/23.501").unwrap();
+        let now = Utc.with_ymd_and_hms(2020, 6, 30, 0, 0, 0).unwrap();
+        let (items, directories) = parse_listing(LISTING, &url, &query(), now).unwrap();
+
+        assert_eq!(directories, ["Versions/"]);
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[1].url,
+            "ftp://mirror.example.com/Specs/archive/23_series/23.501/23501-f00.zip"
+        );
+        assert_eq!(items[1].size, Some(81_101));
+        assert_eq!(
+            items[1].date,
+            Utc.with_ymd_and_hms(2017, 12, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(items[2].version.unwrap().to_string(), "16.4.0");
+        assert_eq!(
+            items[2].date,
+            Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap()
+        );
+        assert_eq!(items[0].version, None);
+        assert!(items[0].url.ends_with("/readme.txt"));
+
+        assert_eq!(
+            parse_listing("total 0\n", &url, &query(), now),
+            Err(Error::EmptyDirectory(query().spec_number))
+        );
+    }
+
+    #[test]
+    fn list_and_retrieve_over_ftp() {
+        let server = MockServer::start_ftp(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("LIST", "/archive/23_series/23.501/") => Response::ok(LISTING),
+            ("RETR", "/archive/23_series/23.501/23501-g40.zip") => {
+                Response::ok(b"PK\x03\x04spec".to_vec())
+            }
+            _ => Response::not_found(),
+        });
+        // The year of dates listed without one comes from the clock of the query.
+        let query = ListQuery {
+            base_url: server.url("/archive/"),
+            clock: SharedClock::new(FixedClock(
+                Utc.with_ymd_and_hms(2021, 2, 1, 0, 0, 0).unwrap(),
+            )),
+            ..query()
+        };
+
+        let items = list(&Client::new(), &query).unwrap();
+        assert_eq!(items.len(), 3);
+        let latest = &items[2];
+        assert_eq!(
+            latest.date,
+            Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap()
+        );
+        assert_eq!(
+            latest.url,
+            server.url("/archive/23_series/23.501/23501-g40.zip")
+        );
+
+        assert_eq!(retrieve(&latest.url).unwrap(), b"PK\x03\x04spec");
+        let err = retrieve(&server.url("/archive/missing.zip")).unwrap_err();
+        assert!(err.contains("550"), "{}", err);
+    }
+}
@@ -0,0 +1,404 @@
+//! Minimal DEFLATE decoder (RFC 1951) with gzip (RFC 1952) and zlib (RFC 1950) framing, used to
+//! decode listings served with `Content-Encoding: gzip` or `deflate`.
+
+/// Base lengths of length codes 257..=285, and their numbers of extra bits.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances of distance codes 0..=29, and their numbers of extra bits.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which the code lengths of the code length alphabet are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const MAX_BITS: usize = 15;
+
+/// Reader of the bits of `data`, least significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Bits {
+            data,
+            pos: 0,
+            bit: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.pos)
+                .ok_or("unexpected end of compressed data")?;
+            value |= u32::from(byte >> self.bit & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Skip to the next byte boundary.
+    fn align(&mut self) {
+        if self.bit > 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    /// Number of codes of each length.
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Build the code for symbols with the given code `lengths` (0 for unused symbols).
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[usize::from(len)] += 1;
+        }
+        counts[0] = 0;
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err("invalid Huffman code: oversubscribed".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; usize::from(offsets[MAX_BITS + 1])];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len > 0 {
+                let offset = &mut offsets[usize::from(len)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code in compressed data".to_string())
+    }
+}
+
+/// Fixed literal/length and distance codes of block type 1.
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Read the literal/length and distance codes of a block of type 2.
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.read(5)? as usize + 257;
+    let distances = bits.read(5)? as usize + 1;
+    let code_lengths = bits.read(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[i] = bits.read(3)? as u8;
+    }
+    let lengths_code = Huffman::new(&lengths)?;
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let symbol = lengths_code.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or("repeated code length without a first")?;
+                (previous, 3 + bits.read(2)?)
+            }
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err("too many code lengths in compressed data".to_string());
+    }
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..])?,
+    ))
+}
+
+/// Decode the symbols of one block of type 1 or 2 into `out`, up to its end-of-block code.
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literal: &Huffman,
+    distance: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = usize::from(literal.decode(bits)?);
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                let base = *LENGTH_BASE.get(i).ok_or("invalid length code")?;
+                let len = usize::from(base) + bits.read(LENGTH_EXTRA[i].into())? as usize;
+                let d = usize::from(distance.decode(bits)?);
+                let base = *DIST_BASE.get(d).ok_or("invalid distance code")?;
+                let dist = usize::from(base) + bits.read(DIST_EXTRA[d].into())? as usize;
+                if dist > out.len() {
+                    return Err("distance too far back in compressed data".to_string());
+                }
+                let start = out.len() - dist;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// Decode raw DEFLATE `data`, returning the output and the number of input bytes consumed.
+fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut bits = Bits::new(data);
+    let mut out = Vec::new();
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let header = data
+                    .get(bits.pos..bits.pos + 4)
+                    .ok_or("unexpected end of compressed data")?;
+                let len = usize::from(u16::from_le_bytes([header[0], header[1]]));
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if nlen != !(len as u16) {
+                    return Err("invalid stored block length".to_string());
+                }
+                let start = bits.pos + 4;
+                let block = data
+                    .get(start..start + len)
+                    .ok_or("unexpected end of compressed data")?;
+                out.extend_from_slice(block);
+                bits.pos = start + len;
+            }
+            1 => {
+                let (literal, distance) = fixed_codes()?;
+                inflate_block(&mut bits, &mut out, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut out, &literal, &distance)?;
+            }
+            _ => return Err("invalid block type in compressed data".to_string()),
+        }
+        if last {
+            bits.align();
+            return Ok((out, bits.pos));
+        }
+    }
+}
+
+/// CRC-32 (IEEE) checksum of `data`, as used by gzip.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Adler-32 checksum of `data`, as used by zlib.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Decode a gzip member, checking its CRC-32 and size.
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+
+    if data.len() < 18 || data[..3] != [0x1f, 0x8b, 8] {
+        return Err("not gzip data".to_string());
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or("truncated gzip header")?;
+        pos += 2 + usize::from(u16::from_le_bytes([len[0], len[1]]));
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or("truncated gzip header")?;
+            pos += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    let (out, used) = inflate(data.get(pos..).ok_or("truncated gzip header")?)?;
+    let trailer = data
+        .get(pos + used..pos + used + 8)
+        .ok_or("truncated gzip trailer")?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err("gzip checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+/// Decode zlib-wrapped data, checking its Adler-32 checksum. Some servers send raw DEFLATE data
+/// for `Content-Encoding: deflate`, which is decoded as such.
+fn unzlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    let is_zlib = data.len() >= 2
+        && data[0] & 0x0f == 8
+        && (u16::from(data[0]) << 8 | u16::from(data[1])) % 31 == 0;
+    if !is_zlib {
+        return inflate(data).map(|(out, _)| out);
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("zlib data with a preset dictionary is not supported".to_string());
+    }
+    let (out, used) = inflate(&data[2..])?;
+    let trailer = data
+        .get(2 + used..2 + used + 4)
+        .ok_or("truncated zlib trailer")?;
+    if u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != adler32(&out) {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+/// Encodings `decode` supports, for the `Accept-Encoding` header of listing requests.
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// Decode `body` sent with the `Content-Encoding` header value `encoding`. Only `gzip` (or
+/// `x-gzip`), `deflate` and `identity` are supported.
+pub(crate) fn decode(encoding: &str, body: Vec<u8>) -> Result<Vec<u8>, String> {
+    let mut body = body;
+    // Encodings are listed in the order they were applied.
+    for encoding in encoding.rsplit(',').map(|e| e.trim().to_ascii_lowercase()) {
+        body = match encoding.as_str() {
+            "" | "identity" => body,
+            "gzip" | "x-gzip" => gunzip(&body)?,
+            "deflate" => unzlib(&body)?,
+            other => return Err(format!("unsupported content encoding '{}'", other)),
+        };
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{adler32, crc32, decode};
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn decode_encodings() {
+        // gzip of "hello hello hello\n" (fixed Huffman codes with a back reference).
+        let gzip = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9,
+            0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00, 0x3b, 0x7c, 0x8a, 0xdf, 0x12, 0x00, 0x00,
+            0x00,
+        ];
+        assert_eq!(
+            decode("gzip", gzip.to_vec()).unwrap(),
+            b"hello hello hello\n"
+        );
+        assert!(decode("gzip", gzip[..20].to_vec()).is_err());
+        let mut corrupt = gzip.to_vec();
+        corrupt[22] ^= 1;
+        assert!(decode("gzip", corrupt).is_err());
+
+        // zlib of "abc", and the same raw DEFLATE data without the zlib wrapper.
+        let zlib = [
+            0x78, 0x9c, 0x4b, 0x4c, 0x4a, 0x06, 0x00, 0x02, 0x4d, 0x01, 0x27,
+        ];
+        assert_eq!(decode("deflate", zlib.to_vec()).unwrap(), b"abc");
+        assert_eq!(decode("deflate", zlib[2..7].to_vec()).unwrap(), b"abc");
+
+        // A stored (uncompressed) block.
+        let stored = [0x01, 0x03, 0x00, 0xfc, 0xff, b'x', b'y', b'z'];
+        assert_eq!(decode("deflate", stored.to_vec()).unwrap(), b"xyz");
+
+        assert_eq!(decode("identity", b"plain".to_vec()).unwrap(), b"plain");
+        let err = decode("br", b"\x0b".to_vec()).unwrap_err();
+        assert!(err.contains("unsupported content encoding 'br'"), "{}", err);
+    }
+
+    #[test]
+    fn decode_dynamic_huffman() {
+        let html = include_bytes!("../resources/23501.htm");
+        let gzip = include_bytes!("../resources/23501.htm.gz");
+        assert_eq!(decode("gzip", gzip.to_vec()).unwrap(), html);
+    }
+}
@@ -1,17 +1,135 @@
 use chrono::{DateTime, Utc};
 use regex::Regex;
-use scraper::{Html, Selector};
+use reqwest::blocking::Client;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+mod cache;
+mod checkpoint;
+mod client;
+mod clock;
+mod cookies;
+mod diff;
+mod download;
+mod error;
+mod feed;
+mod ftp;
+mod inflate;
+mod manifest;
+mod parallel;
+mod parser;
+mod probe;
+mod sha256;
+#[cfg(test)]
+mod test_support;
+mod timezone;
+mod yaml;
+
+pub use cache::{DEFAULT_CACHE_TTL, ListingCache};
+pub use checkpoint::Checkpoint;
+pub use client::{ClientOptions, build_client, parse_header, parse_proxy, redact_header};
+pub use clock::{Clock, FixedClock, SharedClock, SystemClock};
+pub use cookies::CookieJar;
+pub use diff::{Diff, Watcher, diff, read_listing};
+pub use download::{
+    BatchSummary, Download, DownloadOptions, DownloadOutcome, DownloadStatus, Metadata,
+    SidecarCheck, download_url_to_path, download_url_to_writer, fetch_metadata, format_bytes,
+    part_path, preserve_mtime,
+};
+pub use error::{ConnectError, Error};
+pub use feed::atom_feed;
+pub use manifest::{Manifest, ManifestEntry};
+pub use parallel::{download_many, list_many, parallel_map};
+pub use parser::{DirectoryIndexParser, ListingParser, MirrorKind, ThreeGppFtpParser};
+pub use probe::{Probe, probe, probe_url};
+pub use sha256::{Sha256, sha256_hex};
+pub use timezone::SourceTz;
+pub use yaml::to_yaml;
 
 /// Base URL for 3GPP spec archive.
 pub const BASE_URL: &str = "https://www.3gpp.org/ftp/Specs/archive/";
 
 /// Struct holding parsed spec number parts.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct SpecNumber {
     pub series: String,
     pub number: String,
 }
 
+/// Titles of the 3GPP specification series, keyed by the two-digit series number.
+const SERIES_DESCRIPTIONS: &[(&str, &str)] = &[
+    ("21", "Requirements"),
+    ("22", "Service aspects (\"stage 1\")"),
+    ("23", "Technical realization (\"stage 2\")"),
+    (
+        "24",
+        "Signalling protocols (\"stage 3\") - user equipment to network",
+    ),
+    ("25", "Radio aspects"),
+    ("26", "CODECs"),
+    ("27", "Data"),
+    (
+        "28",
+        "Signalling protocols (\"stage 3\") - (RSS-CN) and OAM&P and Charging",
+    ),
+    (
+        "29",
+        "Signalling protocols (\"stage 3\") - intra-fixed-network",
+    ),
+    ("30", "Programme management"),
+    (
+        "31",
+        "Subscriber Identity Module (SIM / USIM), IC Cards. Test specs.",
+    ),
+    ("32", "OAM&P and Charging"),
+    ("33", "Security aspects"),
+    ("34", "UE and (U)SIM test specifications"),
+    ("35", "Security algorithms"),
+    (
+        "36",
+        "LTE (Evolved UTRA), LTE-Advanced, LTE-Advanced Pro radio technology",
+    ),
+    ("37", "Multiple radio access technology aspects"),
+    ("38", "Radio technology beyond LTE"),
+    ("41", "Requirements (GSM only)"),
+    ("42", "Service aspects (\"stage 1\") (GSM only)"),
+    ("43", "Technical realization (\"stage 2\") (GSM only)"),
+    (
+        "44",
+        "Signalling protocols (\"stage 3\") - user equipment to network (GSM only)",
+    ),
+    ("45", "Radio aspects (GSM only)"),
+    ("46", "CODECs (GSM only)"),
+    ("47", "Data (GSM only)"),
+    (
+        "48",
+        "Signalling protocols (\"stage 3\") - radio system to core network (GSM only)",
+    ),
+    (
+        "49",
+        "Signalling protocols (\"stage 3\") - intra-fixed-network (GSM only)",
+    ),
+    ("50", "Programme management (GSM only)"),
+    (
+        "51",
+        "Subscriber Identity Module (SIM / USIM), IC Cards. Test specs. (GSM only)",
+    ),
+    ("52", "OAM&P and Charging (GSM only)"),
+    ("55", "Security algorithms (GSM only)"),
+];
+
+impl SpecNumber {
+    /// Human-readable title of this spec's series, or `None` if the series is not a known
+    /// 3GPP series.
+    pub fn series_description(&self) -> Option<&'static str> {
+        SERIES_DESCRIPTIONS
+            .iter()
+            .find(|(series, _)| *series == self.series)
+            .map(|(_, description)| *description)
+    }
+}
+
 impl std::fmt::Display for SpecNumber {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}.{}", self.series, self.number)
@@ -30,6 +148,13 @@ impl std::str::FromStr for SpecNumber {
 /// Accepts strings that start with two digits, optionally a dot, then at least one alphanumeric.
 /// Returns `Ok(SpecNumber)` on success, or `Err(String)` with a human-friendly message on failure.
 pub fn parse_spec_number(spec: &str) -> Result<SpecNumber, String> {
+    // Checked first for a clearer message, as the spec number becomes part of the listing path.
+    if spec.contains(['/', '\\']) || spec.contains("..") {
+        return Err(format!(
+            "invalid spec_number '{}': must not contain path separators or '..'",
+            spec
+        ));
+    }
     let re = Regex::new(r"^\d{2}\.?[A-Za-z0-9]+$")
         .map_err(|e| format!("internal regex error: {}", e))?;
     if !re.is_match(spec) {
@@ -51,9 +176,92 @@ pub fn parse_spec_number(spec: &str) -> Result<SpecNumber, String> {
     })
 }
 
+/// Like `parse_spec_number`, but also rejects series that are not known 3GPP series
+/// (see `SpecNumber::series_description`), listing the valid ones in the error.
+pub fn parse_spec_number_strict(spec: &str) -> Result<SpecNumber, String> {
+    let spec_number = parse_spec_number(spec)?;
+    if spec_number.series_description().is_none() {
+        let valid: Vec<&str> = SERIES_DESCRIPTIONS
+            .iter()
+            .map(|(series, _)| *series)
+            .collect();
+        return Err(format!(
+            "invalid spec_number '{}': unknown series '{}', expected one of {}",
+            spec,
+            spec_number.series,
+            valid.join(", ")
+        ));
+    }
+    Ok(spec_number)
+}
+
+/// Spec number optionally followed by a release, as in "23.501 Rel-16" or "23.501-16".
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SpecRef {
+    pub spec_number: SpecNumber,
+    pub release: Option<u32>,
+}
+
+impl std::str::FromStr for SpecRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_spec_ref(s)
+    }
+}
+
+impl From<SpecRef> for ListQuery {
+    /// Query for the referenced spec, filtered to its release if one was given.
+    fn from(spec_ref: SpecRef) -> Self {
+        ListQuery {
+            release: spec_ref.release,
+            ..ListQuery::new(spec_ref.spec_number)
+        }
+    }
+}
+
+/// Parse a spec reference: a plain spec number (see `parse_spec_number`), or a spec number
+/// followed by a release separated by whitespace or `-`, with an optional case-insensitive
+/// `Rel`/`Release` prefix. For example `23.501 Rel-16`, `23501 rel16` and `23.501-16`.
+pub fn parse_spec_ref(spec: &str) -> Result<SpecRef, String> {
+    let spec = spec.trim();
+    let re = Regex::new(r"(?i)^(\d{2}\.?[A-Za-z0-9]+)(?:\s*-\s*|\s+)(?:rel(?:ease)?[-\s]?)?(\d+)$")
+        .map_err(|e| format!("internal regex error: {}", e))?;
+    let Some(caps) = re.captures(spec) else {
+        return Ok(SpecRef {
+            spec_number: parse_spec_number(spec)?,
+            release: None,
+        });
+    };
+
+    let release = caps[2]
+        .parse()
+        .map_err(|e| format!("invalid release in '{}': {}", spec, e))?;
+    Ok(SpecRef {
+        spec_number: parse_spec_number(&caps[1])?,
+        release: Some(release),
+    })
+}
+
+/// Parse a release given as a number or by name: `16`, `Rel-16`, `Rel 16`, `Rel16`,
+/// `Release 16` or `R16`, case-insensitively.
+pub fn parse_release(release: &str) -> Result<u32, String> {
+    let re = Regex::new(r"(?i)^(?:rel(?:ease)?[-\s]?|r)?(\d+)$")
+        .map_err(|e| format!("internal regex error: {}", e))?;
+    let caps = re.captures(release.trim()).ok_or_else(|| {
+        format!(
+            "invalid release '{}': expected a number or a name like Rel-16, Release 16 or R16",
+            release
+        )
+    })?;
+    caps[1]
+        .parse()
+        .map_err(|e| format!("invalid release '{}': {}", release, e))
+}
+
 /// Month of year with explicit numeric values 1..=12.
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Month {
     January = 1,
     February = 2,
@@ -69,21 +277,82 @@ pub enum Month {
     December = 12,
 }
 
-/// Simple filter holding a year and month.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct DateFilter {
+/// Month of a given year, parsed from `YYYY-MM`. Adding or subtracting a number of months
+/// wraps around year boundaries, e.g. 2023-12 + 1 is 2024-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct YearMonth {
     pub year: u32,
     pub month: Month,
 }
 
+/// Year and month filter for `--date`, selecting the quarter starting at that month.
+pub type DateFilter = YearMonth;
+
 /// Version with nonnegative integer components.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Version {
     pub major: u32,
     pub minor: u32,
     pub editorial: u32,
 }
 
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.editorial)
+    }
+}
+
+impl Version {
+    /// The 3-character code used in spec filenames, one lowercase base-36 digit per component,
+    /// e.g. `g40` for 16.4.0. Fails if a component is 36 or more.
+    pub fn to_short_code(&self) -> Result<String, String> {
+        [self.major, self.minor, self.editorial]
+            .iter()
+            .map(|&c| {
+                char::from_digit(c, 36)
+                    .ok_or_else(|| format!("version {} has no short code: {} > 35", self, c))
+            })
+            .collect()
+    }
+
+    /// The 6-character code used in spec filenames, two zero-padded decimal digits per
+    /// component, e.g. `160400` for 16.4.0. Fails if a component is 100 or more.
+    pub fn to_long_code(&self) -> Result<String, String> {
+        let components = [self.major, self.minor, self.editorial];
+        if let Some(c) = components.iter().find(|&&c| c > 99) {
+            return Err(format!("version {} has no long code: {} > 99", self, c));
+        }
+        Ok(components.iter().map(|c| format!("{:02}", c)).collect())
+    }
+
+    /// The version packed into one integer, `major << 32 | minor << 16 | editorial`: 32 bits
+    /// for the major version and 16 bits each for minor and editorial. Packed versions compare
+    /// like the versions themselves. Fails if minor or editorial is 65536 or more.
+    pub fn to_packed(&self) -> Result<u64, String> {
+        if let Some(c) = [self.minor, self.editorial].iter().find(|&&c| c > 0xffff) {
+            return Err(format!("version {} cannot be packed: {} > 65535", self, c));
+        }
+        Ok(u64::from(self.major) << 32 | u64::from(self.minor) << 16 | u64::from(self.editorial))
+    }
+
+    /// Unpack a version packed with [`Version::to_packed`].
+    pub fn from_packed(packed: u64) -> Self {
+        Self {
+            major: (packed >> 32) as u32,
+            minor: (packed >> 16 & 0xffff) as u32,
+            editorial: (packed & 0xffff) as u32,
+        }
+    }
+
+    /// Whether this is a pre-release version: one whose major component is below 3. By 3GPP
+    /// convention, versions 0.x.y and 1.x.y are working drafts and 2.x.y is a draft presented for
+    /// approval; approved specs start at the release number (e.g. 15.0.0). The minor and
+    /// editorial components play no part.
+    pub fn is_prerelease(&self) -> bool {
+        self.major < 3
+    }
+}
+
 impl std::cmp::PartialOrd for Version {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -102,26 +371,201 @@ impl std::cmp::Ord for Version {
     }
 }
 
+/// Version selector with optional trailing components, parsed from `16`, `16.4` or `16.4.0`.
+/// A missing component is a wildcard: `16` matches every 16.x.y and `16.4` every 16.4.x, while
+/// `16.4.0` matches only 16.4.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialVersion {
+    pub major: u32,
+    pub minor: Option<u32>,
+    /// Only set together with `minor`.
+    pub editorial: Option<u32>,
+}
+
+impl PartialVersion {
+    /// Whether every component given in this selector equals that of `version`.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.major == version.major
+            && self.minor.is_none_or(|minor| minor == version.minor)
+            && self
+                .editorial
+                .is_none_or(|editorial| editorial == version.editorial)
+    }
+}
+
+impl std::fmt::Display for PartialVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.major)?;
+        if let Some(minor) = self.minor {
+            write!(f, ".{}", minor)?;
+        }
+        if let Some(editorial) = self.editorial {
+            write!(f, ".{}", editorial)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for PartialVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid version '{}': must be MAJOR, MAJOR.MINOR or MAJOR.MINOR.EDITORIAL",
+                s
+            )
+        };
+        let components: Vec<u32> = s
+            .split('.')
+            .map(|c| c.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        match components[..] {
+            [major] => Ok(PartialVersion {
+                major,
+                minor: None,
+                editorial: None,
+            }),
+            [major, minor] => Ok(PartialVersion {
+                major,
+                minor: Some(minor),
+                editorial: None,
+            }),
+            [major, minor, editorial] => Ok(PartialVersion {
+                major,
+                minor: Some(minor),
+                editorial: Some(editorial),
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl From<Version> for PartialVersion {
+    fn from(version: Version) -> Self {
+        PartialVersion {
+            major: version.major,
+            minor: Some(version.minor),
+            editorial: Some(version.editorial),
+        }
+    }
+}
+
 /// Single spec item including version, date and URL.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct SpecItem {
-    pub version: Version,
+    /// Spec number of the directory the item was listed from.
+    pub spec: SpecNumber,
+    /// Version parsed from the filename, or `None` for files whose name carries no version code,
+    /// such as meeting documents.
+    pub version: Option<Version>,
+    /// Release named by a `Rel-NN` token in the filename (see `parse_release_token`), which
+    /// takes precedence over the major version for `ListQuery::release`.
+    pub release: Option<u32>,
     pub date: DateTime<Utc>,
     pub url: String,
+    /// Name of the file as shown in the listing, e.g. `23501-g40.zip`. Empty in listings saved
+    /// before it was recorded; see `SpecItem::filename` for a fallback.
+    #[serde(default)]
+    pub filename: String,
+    /// Whether the item looks like a draft rather than an approved version (see `is_draft`).
+    pub draft: bool,
+    /// Size in bytes advertised by the listing, if it has a parseable size column.
+    pub size: Option<u64>,
 }
 
-impl std::fmt::Display for SpecItem {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{:2}.{:2}.{:2} @ {} ({})",
-            self.version.major,
-            self.version.minor,
-            self.version.editorial,
-            self.date.to_rfc3339(),
+impl SpecItem {
+    /// Release the item belongs to: the one named in its filename, otherwise its major version.
+    pub fn effective_release(&self) -> Option<u32> {
+        self.release.or(self.version.map(|v| v.major))
+    }
+
+    /// Name of the file as shown in the listing, or the last segment of its URL if the listing
+    /// did not record one.
+    pub fn filename(&self) -> &str {
+        match self.filename.is_empty() {
+            true => self.url.rsplit('/').next().unwrap_or_default(),
+            false => &self.filename,
+        }
+    }
+
+    /// Time elapsed between the listing date of the item and `now`, negative if it is dated
+    /// after `now`.
+    pub fn age(&self, now: DateTime<Utc>) -> chrono::Duration {
+        now - self.date
+    }
+
+    /// The item as with `Display`, but with its date written in `format`.
+    pub fn to_string_with(&self, format: &DateFormat) -> String {
+        self.line(&format.format(&self.date))
+    }
+
+    /// The item as with `Display`, with `date` as its date.
+    fn line(&self, date: &str) -> String {
+        format!(
+            "{} {} @ {} ({})",
+            self.spec,
+            self.version_label(),
+            date,
             self.url
         )
     }
+
+    /// The version as written by `Display`, each component padded to two columns, e.g.
+    /// `16. 4. 0`, or `unversioned`.
+    pub fn version_label(&self) -> String {
+        match &self.version {
+            Some(v) => format!("{:2}.{:2}.{:2}", v.major, v.minor, v.editorial),
+            None => "unversioned".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SpecItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.line(&self.date.to_rfc3339()))
+    }
+}
+
+/// `strftime`-style format of dates in output, e.g. `%Y-%m-%d`, validated when parsed. See
+/// `chrono::format::strftime` for the specifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateFormat(String);
+
+impl DateFormat {
+    /// Date without the time of day, e.g. `2020-03-27`.
+    pub fn date_only() -> Self {
+        DateFormat("%Y-%m-%d".to_string())
+    }
+
+    /// `date` written in this format.
+    pub fn format(&self, date: &DateTime<Utc>) -> String {
+        date.format(&self.0).to_string()
+    }
+}
+
+impl std::fmt::Display for DateFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use chrono::format::{Item, StrftimeItems};
+        if s.is_empty() {
+            return Err("invalid date format '': expected specifiers like %Y-%m-%d".to_string());
+        }
+        match StrftimeItems::new(s).any(|item| matches!(item, Item::Error)) {
+            true => Err(format!(
+                "invalid date format '{}': unknown or incomplete % specifier",
+                s
+            )),
+            false => Ok(DateFormat(s.to_string())),
+        }
+    }
 }
 
 impl std::convert::TryFrom<u8> for Month {
@@ -146,10 +590,71 @@ impl std::convert::TryFrom<u8> for Month {
     }
 }
 
-impl std::str::FromStr for DateFilter {
+impl YearMonth {
+    /// Months since the start of year 0, or `None` if that overflows.
+    fn index(self) -> Option<u32> {
+        self.year
+            .checked_mul(12)?
+            .checked_add(self.month as u32 - 1)
+    }
+
+    fn from_index(index: u32) -> Self {
+        YearMonth {
+            year: index / 12,
+            month: Month::try_from((index % 12 + 1) as u8).expect("month in 1..=12"),
+        }
+    }
+
+    /// The month `months` after this one, or `None` if it is out of range.
+    pub fn checked_add(self, months: u32) -> Option<YearMonth> {
+        self.index()?.checked_add(months).map(YearMonth::from_index)
+    }
+
+    /// The month `months` before this one, or `None` if it is before year 0.
+    pub fn checked_sub(self, months: u32) -> Option<YearMonth> {
+        self.index()?.checked_sub(months).map(YearMonth::from_index)
+    }
+
+    /// First instant of the month, at 00:00 UTC.
+    pub fn start(self) -> Option<DateTime<Utc>> {
+        chrono::NaiveDate::from_ymd_opt(i32::try_from(self.year).ok()?, self.month as u32, 1)
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|naive_dt| DateTime::<Utc>::from_naive_utc_and_offset(naive_dt, Utc))
+    }
+}
+
+/// Panics if the result is out of range, like the integer operators; see
+/// `YearMonth::checked_add`.
+impl std::ops::Add<u32> for YearMonth {
+    type Output = YearMonth;
+
+    fn add(self, months: u32) -> YearMonth {
+        self.checked_add(months)
+            .expect("attempt to add months with overflow")
+    }
+}
+
+/// Panics if the result is before year 0, like the integer operators; see
+/// `YearMonth::checked_sub`.
+impl std::ops::Sub<u32> for YearMonth {
+    type Output = YearMonth;
+
+    fn sub(self, months: u32) -> YearMonth {
+        self.checked_sub(months)
+            .expect("attempt to subtract months with overflow")
+    }
+}
+
+impl std::fmt::Display for YearMonth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month as u8)
+    }
+}
+
+impl std::str::FromStr for YearMonth {
     type Err = String;
 
-    /// Parse a date string in YYYY-MM format into `DateFilter`.
+    /// Parse a date string in YYYY-MM format into `YearMonth`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let re =
             Regex::new(r"^(\d{4})-(\d{2})$").map_err(|e| format!("internal regex error: {}", e))?;
@@ -169,12 +674,99 @@ impl std::str::FromStr for DateFilter {
             .parse()
             .map_err(|e| format!("invalid month: {}", e))?;
         let month = Month::try_from(month_num)?;
-        Ok(DateFilter { year, month })
+        Ok(YearMonth { year, month })
+    }
+}
+
+/// Unit of a `RelativeDuration`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DurationUnit {
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+/// Longest `RelativeDuration` accepted, in years.
+const MAX_DURATION_YEARS: u32 = 10_000;
+
+/// Calendar-relative duration such as `30d`, `2w`, `6m` or `1y`, used by `--since`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelativeDuration {
+    pub amount: u32,
+    pub unit: DurationUnit,
+}
+
+impl RelativeDuration {
+    /// The instant this duration before `now`, or `None` if it is out of range.
+    /// Months and years are calendar months, clamped to the end of shorter months.
+    pub fn before(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.unit {
+            DurationUnit::Days => now.checked_sub_days(chrono::Days::new(self.amount.into())),
+            DurationUnit::Weeks => {
+                now.checked_sub_days(chrono::Days::new(u64::from(self.amount) * 7))
+            }
+            DurationUnit::Months => now.checked_sub_months(chrono::Months::new(self.amount)),
+            DurationUnit::Years => {
+                now.checked_sub_months(chrono::Months::new(self.amount.checked_mul(12)?))
+            }
+        }
+    }
+
+    /// The instant this duration before the current time of `clock`.
+    pub fn cutoff(&self, clock: &dyn Clock) -> Option<DateTime<Utc>> {
+        self.before(clock.now())
+    }
+}
+
+impl std::str::FromStr for RelativeDuration {
+    type Err = String;
+
+    /// Parse a duration as a nonnegative integer followed by `d`, `w`, `m` or `y`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let re =
+            Regex::new(r"^(\d+)([dwmy])$").map_err(|e| format!("internal regex error: {}", e))?;
+        let caps = re.captures(s).ok_or_else(|| {
+            format!(
+                "invalid duration '{}': must be a number followed by d, w, m or y (e.g. 30d, 6m, 1y)",
+                s
+            )
+        })?;
+        let amount = caps[1]
+            .parse()
+            .map_err(|e| format!("invalid duration amount: {}", e))?;
+        let unit = match &caps[2] {
+            "d" => DurationUnit::Days,
+            "w" => DurationUnit::Weeks,
+            "m" => DurationUnit::Months,
+            _ => DurationUnit::Years,
+        };
+        // A duration reaching further back than any date could be would otherwise silently
+        // disable the filter, as `before` has no instant to return for it.
+        let limit = match unit {
+            DurationUnit::Days => MAX_DURATION_YEARS * 366,
+            DurationUnit::Weeks => MAX_DURATION_YEARS * 53,
+            DurationUnit::Months => MAX_DURATION_YEARS * 12,
+            DurationUnit::Years => MAX_DURATION_YEARS,
+        };
+        if amount > limit {
+            return Err(format!(
+                "invalid duration '{}': out of range (at most {} years)",
+                s, MAX_DURATION_YEARS
+            ));
+        }
+        Ok(RelativeDuration { amount, unit })
     }
 }
 
 fn parse_version(filename: &str) -> Option<Version> {
     let stem = std::path::Path::new(filename).file_stem()?.to_str()?;
+    // A release token is not part of the version code, e.g. `23501-g40_Rel-16`.
+    let stem = match release_token_regex().captures(stem).and_then(|c| c.get(1)) {
+        Some(token) => format!("{}{}", &stem[..token.start()], &stem[token.end()..]),
+        None => stem.to_string(),
+    };
+    let stem = stem.trim_end_matches(['-', '_', ' ']);
     let parts: Vec<&str> = stem.split('-').collect();
     let ver_str = parts.last()?.trim_end_matches(".zip");
     match ver_str.len() {
@@ -203,155 +795,1359 @@ fn parse_version(filename: &str) -> Option<Version> {
     }
 }
 
-/// List specs matching provided filters.
-///
-/// This is a simple placeholder implementation that returns an empty list.
-pub fn list(
-    spec_number: SpecNumber,
-    release: Option<u32>,
-    date_filter: Option<DateFilter>,
-) -> Result<Vec<SpecItem>, String> {
-    let base =
-        reqwest::Url::parse(BASE_URL).map_err(|e| format!("failed to parse BASE_URL: {}", e))?;
-    let path = format!("{}_series/{}", spec_number.series, spec_number);
-    let url = base
-        .join(&path)
-        .map_err(|e| format!("failed to join path to BASE_URL: {}", e))?;
+/// Pattern of a textual release token such as `Rel-16` in a filename (case-insensitive), not
+/// preceded by a letter nor followed by a digit. Compiled once, as it is matched for every row.
+fn release_token_regex() -> &'static Regex {
+    static RELEASE_TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)(?:^|[^a-z])(rel-(\d+))(?:\D|$)").expect("valid release token regex")
+    });
+    &RELEASE_TOKEN
+}
 
-    if !url.as_str().starts_with(BASE_URL) {
-        return Err(format!(
-            "security check failed: URL '{}' does not start with BASE_URL",
-            url
-        ));
-    }
+/// Parse the release named by a `Rel-NN` token in `filename`, e.g. 16 for `23501-Rel-16-g40.zip`.
+pub fn parse_release_token(filename: &str) -> Option<u32> {
+    release_token_regex().captures(filename)?[2].parse().ok()
+}
 
-    let response = reqwest::blocking::get(url.clone())
-        .map_err(|e| format!("failed to fetch URL '{}': {}", url, e))?;
+/// Heuristically decide whether a listed file is a draft (non-final) version.
+///
+/// This is a best-effort guess, not an authoritative status. A file is considered a draft when
+/// either of the following holds:
+/// - its `filename` contains the marker `draft` (case-insensitive), or
+/// - its `version`, if it has one, is a pre-release (see `Version::is_prerelease`).
+pub fn is_draft(filename: &str, version: Option<&Version>) -> bool {
+    version.is_some_and(Version::is_prerelease) || filename.to_lowercase().contains("draft")
+}
 
-    if !response.status().is_success() {
-        return Err(format!(
-            "failed to fetch URL '{}': status code {}",
-            url,
-            response.status()
-        ));
-    }
+/// Marker (case-insensitive) in the filename of a spec's change-request archive, e.g.
+/// `23501_CRs.zip`. Such archives carry no version code, so they are listed unversioned.
+pub const CHANGE_REQUESTS_MARKER: &str = "_crs";
 
-    let body = response
-        .text()
-        .map_err(|e| format!("failed to read response body: {}", e))?;
+/// Whether `filename` names a change-request archive (see `CHANGE_REQUESTS_MARKER`).
+pub fn is_change_requests(filename: &str) -> bool {
+    filename.to_lowercase().contains(CHANGE_REQUESTS_MARKER)
+}
+
+/// The most recently dated change-request archive of `spec` among `items`, for `--with-crs`.
+pub fn change_requests<'a>(items: &'a [SpecItem], spec: &SpecNumber) -> Option<&'a SpecItem> {
+    items
+        .iter()
+        .filter(|item| {
+            item.spec == *spec && item.version.is_none() && is_change_requests(item.filename())
+        })
+        .max_by_key(|item| item.date)
+}
 
-    let document = Html::parse_document(&body);
-    let (name_idx, date_idx) = find_header_indexes(&document)?;
+/// The item of `spec` with the highest editorial version of `major`.`minor`, e.g. 16.4.2 out of
+/// 16.4.0, 16.4.2 and 16.5.0, or `None` if `items` has no such version. Of two items with the same
+/// version, the newer one is returned.
+pub fn latest_editorial<'a>(
+    items: &'a [SpecItem],
+    spec: &SpecNumber,
+    major: u32,
+    minor: u32,
+) -> Option<&'a SpecItem> {
+    items
+        .iter()
+        .filter(|item| item.spec == *spec)
+        .filter_map(|item| item.version.map(|version| (version, item)))
+        .filter(|(version, _)| version.major == major && version.minor == minor)
+        .max_by_key(|(version, item)| (*version, item.date))
+        .map(|(_, item)| item)
+}
 
-    let row_selector =
-        Selector::parse("tbody > tr").map_err(|e| format!("selector error: {:?}", e))?;
-    let cell_selector = Selector::parse("td").map_err(|e| format!("selector error: {:?}", e))?;
-    let link_selector =
-        Selector::parse("a[href]").map_err(|e| format!("selector error: {:?}", e))?;
+/// Order a listing page can be requested in, with its `sortby` query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingSort {
+    Name,
+    NameRev,
+    Date,
+    /// Newest first.
+    DateRev,
+    Size,
+    SizeRev,
+}
 
-    let mut specs = Vec::new();
+impl ListingSort {
+    pub const ALL: [ListingSort; 6] = [
+        ListingSort::Name,
+        ListingSort::NameRev,
+        ListingSort::Date,
+        ListingSort::DateRev,
+        ListingSort::Size,
+        ListingSort::SizeRev,
+    ];
 
-    for row in document.select(&row_selector) {
-        let cells: Vec<_> = row.select(&cell_selector).collect();
-        if cells.len() <= std::cmp::max(name_idx, date_idx) {
-            continue;
+    /// Value of the `sortby` query parameter, as linked from the listing's column headers.
+    pub fn param(self) -> &'static str {
+        match self {
+            ListingSort::Name => "name",
+            ListingSort::NameRev => "namerev",
+            ListingSort::Date => "date",
+            ListingSort::DateRev => "daterev",
+            ListingSort::Size => "size",
+            ListingSort::SizeRev => "sizerev",
         }
+    }
 
-        let name_cell = cells[name_idx];
-        let date_cell = cells[date_idx];
-
-        let anchor = match name_cell.select(&link_selector).next() {
-            Some(a) => a,
-            None => continue,
-        };
+    /// `url` with the `sortby` parameter for this order appended. Only the query string changes,
+    /// so the URL stays within the base it was checked against.
+    pub fn apply(self, url: &reqwest::Url) -> reqwest::Url {
+        let mut url = url.clone();
+        url.query_pairs_mut().append_pair("sortby", self.param());
+        url
+    }
+}
 
-        let url = anchor.value().attr("href").unwrap_or("").to_string();
-        let filename = anchor.text().collect::<String>();
+impl std::fmt::Display for ListingSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.param())
+    }
+}
 
-        let date_str = date_cell.text().collect::<String>();
-        let date_str = date_str.trim();
+impl std::str::FromStr for ListingSort {
+    type Err = String;
 
-        let date = match chrono::NaiveDateTime::parse_from_str(date_str, "%Y/%m/%d %-H:%M") {
-            Ok(dt) => DateTime::from_naive_utc_and_offset(dt, Utc),
-            Err(_) => continue,
-        };
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ListingSort::ALL
+            .into_iter()
+            .find(|sort| sort.param() == s)
+            .ok_or_else(|| {
+                format!(
+                    "invalid sort order '{}': expected one of name, namerev, date, daterev, size, sizerev",
+                    s
+                )
+            })
+    }
+}
 
-        let version = match parse_version(&filename) {
-            Some(v) => v,
-            None => continue,
-        };
+/// Query describing which spec directory to list and which filters to apply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListQuery {
+    pub spec_number: SpecNumber,
+    /// Archive root the spec directory is resolved against; `BASE_URL` unless a mirror is used.
+    pub base_url: String,
+    /// Path of the spec directory below `base_url`.
+    pub path_template: PathTemplate,
+    /// Keep only items of this release: the one named in their filename, otherwise their major
+    /// version.
+    pub release: Option<u32>,
+    /// Keep only items dated within three months from the start of this month.
+    pub date_filter: Option<YearMonth>,
+    /// Keep only items dated at or after this instant.
+    pub since: Option<DateTime<Utc>>,
+    /// Keep items detected as drafts by `is_draft`.
+    pub include_drafts: bool,
+    /// Keep only unversioned items and items with at least this editorial version.
+    pub min_editorial: Option<u32>,
+    /// Keep only items whose version matches this selector (see `PartialVersion::matches`).
+    pub version: Option<PartialVersion>,
+    /// How many levels of subdirectory rows to descend into; 0 lists only the spec directory.
+    pub max_depth: u32,
+    /// Keep only one item per version (see `dedupe_versions`).
+    pub dedupe: bool,
+    /// Fail with `Error::Timeout` instead of fetching, or while fetching, after this instant.
+    pub deadline: Option<std::time::Instant>,
+    /// Find the listing columns with `guess_header_indexes` rather than `find_header_indexes`,
+    /// for mirrors with unusual markup.
+    pub lenient_html: bool,
+    /// Send the cookies of this jar with listing requests, and store the cookies they set.
+    pub cookies: Option<CookieJar>,
+    /// Timezone the listing's dates are written in; UTC if not set.
+    pub source_tz: Option<SourceTz>,
+    /// Ask the server to sort listing pages in this order. `list` still returns items in
+    /// canonical order, but `list_iter` yields them as the server ordered them.
+    pub sort: Option<ListingSort>,
+    /// Layout of the listing pages, selecting the parser that reads them.
+    pub mirror_kind: MirrorKind,
+    /// Source of the current time, e.g. for the year of FTP listing dates written without one.
+    pub clock: SharedClock,
+    /// Use the listing pages cached here while they are fresh, and cache the ones fetched.
+    pub cache: Option<ListingCache>,
+}
 
-        if let Some(rel) = release {
-            if version.major != rel {
-                continue;
-            }
+impl ListQuery {
+    /// Create a query for `spec_number` with no filters applied.
+    pub fn new(spec_number: SpecNumber) -> Self {
+        ListQuery {
+            spec_number,
+            base_url: BASE_URL.to_string(),
+            path_template: PathTemplate::default(),
+            release: None,
+            date_filter: None,
+            since: None,
+            include_drafts: true,
+            min_editorial: None,
+            version: None,
+            max_depth: 0,
+            dedupe: false,
+            deadline: None,
+            lenient_html: false,
+            cookies: None,
+            source_tz: None,
+            sort: None,
+            mirror_kind: MirrorKind::default(),
+            clock: SharedClock::default(),
+            cache: None,
         }
+    }
 
-        // If a `date_filter` is provided, derive `filter_start` and `filter_end`.
-        // `filter_start` is the first day of that year/month at 00:00 UTC.
-        // `filter_end` is three months after `filter_start` (exclusive upper bound).
-        let (filter_start, filter_end): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) = if let Some(df) = date_filter {
-            // `from_ymd_opt` and `and_hms_opt` return Option to avoid panics on invalid dates.
-            let start = chrono::NaiveDate::from_ymd_opt(df.year as i32, df.month as u8 as u32, 1)
-                .and_then(|d| d.and_hms_opt(0, 0, 0))
-                .map(|naive_dt| DateTime::<Utc>::from_naive_utc_and_offset(naive_dt, Utc));
-            let end = start.clone().and_then(|s| s.checked_add_months(chrono::Months::new(3)));
-            (start, end)
-        } else {
-            (None, None)
-        };
+    /// Rows of the listing page `body` served from `url`, read with the parser of `mirror_kind`.
+    fn rows(&self, body: &str, url: &reqwest::Url) -> Result<Vec<ListingRow>, Error> {
+        self.mirror_kind
+            .parser(self.lenient_html)
+            .parse(body, url, &self.spec_number)
+    }
 
-        // If a `date_filter` was provided, exclude rows outside [filter_start, filter_end).
-        if let Some(start) = filter_start {
-            if date < start {
-                continue;
-            }
-            if let Some(end) = filter_end {
-                if date >= end {
-                    continue;
-                }
-            }
+    /// The instant of a listing date that was parsed as UTC, taking it as a local time in
+    /// `source_tz` instead.
+    pub fn listing_date(&self, date: DateTime<Utc>) -> DateTime<Utc> {
+        match &self.source_tz {
+            Some(tz) => tz.to_utc(date.naive_utc()),
+            None => date,
         }
+    }
 
-        specs.push(SpecItem { version, date, url });
+    /// URL of the directory listing this query fetches (see `listing_url`).
+    pub fn listing_url(&self) -> Result<reqwest::Url, Error> {
+        let base = reqwest::Url::parse(&self.base_url)
+            .map_err(|e| format!("failed to parse base URL '{}': {}", self.base_url, e))?;
+        listing_url_with(&base, &self.spec_number, &self.path_template)
     }
+}
 
-    // Sort by `version` in descending order: compare `major`, then `minor`, then `editorial`.
-    specs.sort_by(|a, b| b.version.cmp(&a.version));
+/// Candidate formats for the listing "date" column, tried in order.
+/// The first entry is the format currently served by 3GPP, so it is checked first.
+const LISTING_DATE_FORMATS: &[&str] = &[
+    "%Y/%m/%d %-H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+];
 
-    Ok(specs)
+/// Parse the text of a listing "date" cell as a UTC timestamp.
+/// Tries each of `LISTING_DATE_FORMATS` in order and returns the first successful parse,
+/// or `None` if no format matches.
+pub fn parse_listing_date(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    LISTING_DATE_FORMATS.iter().find_map(|fmt| {
+        let dt = chrono::NaiveDateTime::parse_from_str(s, fmt).ok()?;
+        log::debug!("parsed listing date '{}' with format '{}'", s, fmt);
+        Some(DateTime::from_naive_utc_and_offset(dt, Utc))
+    })
 }
 
-/// Find the column indexes for "name" and "date" in the table header.
-/// Returns `Ok((name_index, date_index))` on success.
-pub fn find_header_indexes(document: &Html) -> Result<(usize, usize), String> {
-    let selector = Selector::parse("thead > tr > th")
-        .map_err(|e| format!("internal selector error: {:?}", e))?;
+/// Sort `items` into the canonical order returned by `list`: ascending by `version`
+/// (`major`, then `minor`, then `editorial`, with unversioned items first), then by `date`, then
+/// by `url`.
+/// The order is independent of how the server sorted the listing table.
+pub fn sort_canonical(items: &mut [SpecItem]) {
+    items.sort_by(|a, b| {
+        a.version
+            .cmp(&b.version)
+            .then(a.date.cmp(&b.date))
+            .then_with(|| a.url.cmp(&b.url))
+    });
+}
 
-    let mut name_idx = None;
-    let mut date_idx = None;
+/// List specs matching the filters in `query`, in canonical order (see `sort_canonical`):
+/// the highest version is last.
+///
+/// Returns `Error::EmptyDirectory` if the directory listing has no rows at all, and `Ok` with an
+/// empty vector if rows exist but the filters excluded all of them. The exception is
+/// `query.release` excluding every item that has a release, in which case
+/// `Error::ReleaseNotFound` names the releases that are listed.
+pub fn list(client: &Client, query: &ListQuery) -> Result<Vec<SpecItem>, Error> {
+    let url = query.listing_url()?;
+    // The release filter is applied here rather than per row, to collect the listed releases.
+    let unfiltered = ListQuery {
+        release: None,
+        ..query.clone()
+    };
+    let items = list_directory(client, &url, &unfiltered, query.max_depth)?;
+    select_listed(items, query)
+}
 
-    for (i, element) in document.select(&selector).enumerate() {
-        let text = element.text().collect::<String>().to_lowercase();
-        if name_idx.is_none() && text.contains("name") {
-            name_idx = Some(i);
+/// List the items of an already fetched listing page `body`, e.g. one saved from a browser,
+/// applying the filters of `query` as `list` does. `listing_url` is the address the page was
+/// served from and is only used to resolve relative hrefs; nothing is fetched, so subdirectory
+/// rows are not descended into and `query.base_url` is ignored.
+pub fn list_from_body(
+    body: &str,
+    listing_url: &reqwest::Url,
+    query: &ListQuery,
+) -> Result<Vec<SpecItem>, Error> {
+    let unfiltered = ListQuery {
+        release: None,
+        ..query.clone()
+    };
+    let (mut items, _) = parse_rows(body, listing_url, &unfiltered)?;
+    for item in &mut items {
+        item.url = resolve_href(listing_url, &item.url);
+    }
+    select_listed(items, query)
+}
+
+/// Apply the release filter and deduplication of `query` to the `items` listed for it, as the
+/// last step of `list`.
+fn select_listed(mut items: Vec<SpecItem>, query: &ListQuery) -> Result<Vec<SpecItem>, Error> {
+    if let Some(release) = query.release {
+        let mut available: Vec<u32> = items.iter().filter_map(|i| i.effective_release()).collect();
+        available.sort_unstable();
+        available.dedup();
+        items.retain(|item| item.effective_release() == Some(release));
+        if items.is_empty() && !available.is_empty() {
+            return Err(Error::ReleaseNotFound {
+                spec: query.spec_number.clone(),
+                release,
+                available,
+            });
         }
-        if date_idx.is_none() && text.contains("date") {
-            date_idx = Some(i);
+    }
+    if query.dedupe {
+        for dropped in dedupe_versions(&mut items) {
+            if let Some(version) = dropped.version {
+                log::warn!(
+                    "dropped duplicate of {} version {}: {}",
+                    dropped.spec,
+                    version,
+                    dropped.url
+                );
+            }
         }
     }
+    Ok(items)
+}
 
-    match (name_idx, date_idx) {
-        (Some(n), Some(d)) => Ok((n, d)),
-        _ => Err("failed to find 'name' and 'date' columns".to_string()),
+/// Find the item of `version` in the spec directory of `query` without listing it when possible.
+///
+/// The file is probed directly with a `HEAD` request at its conventional name, the spec number
+/// without the dot followed by the version code (`23501-g40.zip`, or `23501-160400.zip` for a
+/// version without a short code). If no probe answers with a `Last-Modified` date, e.g. because
+/// the file is named differently or the archive is FTP, the directory is listed as with `list`
+/// and the newest item of `version` is returned. The filters in `query` apply either way, and
+/// `Ok(None)` means the version is not found or filtered out.
+pub fn get_version(
+    client: &Client,
+    query: &ListQuery,
+    version: &Version,
+) -> Result<Option<SpecItem>, Error> {
+    let url = query.listing_url()?;
+    if url.scheme() != "ftp" {
+        let codes = [version.to_short_code(), version.to_long_code()];
+        for code in codes.into_iter().flatten() {
+            let filename = format!(
+                "{}{}-{}.zip",
+                query.spec_number.series, query.spec_number.number, code
+            );
+            let file_url = url
+                .join(&filename)
+                .map_err(|e| format!("failed to join '{}' to '{}': {}", filename, url, e))?
+                .to_string();
+            match download::fetch_metadata(client, &file_url) {
+                Ok(metadata) if (200..300).contains(&metadata.status) => {
+                    if let Some(date) = metadata.last_modified {
+                        let size = metadata.content_length;
+                        return Ok(listed_item(query, &filename, file_url, date, size, None));
+                    }
+                }
+                Ok(metadata) => log::debug!("probe of '{}': status {}", file_url, metadata.status),
+                Err(e) => log::debug!("probe of '{}' failed: {}", file_url, e),
+            }
+        }
+        log::info!(
+            "no direct hit for {} {}, listing '{}'",
+            query.spec_number,
+            version,
+            url
+        );
     }
+    let items = list(client, query)?;
+    Ok(items
+        .into_iter()
+        .rev()
+        .find(|item| item.version == Some(*version)))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::{SpecNumber, find_header_indexes, parse_spec_number};
+/// Lazily list the items of the spec directory for `query`, without descending into
+/// subdirectories or sorting. Nothing is fetched until the first item is requested. The listing
+/// is then fetched whole and the cells of all its rows are extracted at once, as the HTML
+/// document must be parsed whole; turning a row into an item (parsing its version, date and
+/// size, and filtering) is only done when the item is requested, so e.g. `take(n)` skips that
+/// work for the remaining rows. An FTP listing is parsed into items at once. Items come in the
+/// order of the listing, which on the 3GPP archive (sorted by filename) is canonical.
+///
+/// A failure to fetch or parse the listing is yielded as the only item, as is a query that sets
+/// `max_depth` or `dedupe`, which need the whole listing; use `list` for those.
+pub fn list_iter<'a>(
+    client: &'a Client,
+    query: &'a ListQuery,
+) -> impl Iterator<Item = Result<SpecItem, Error>> + 'a {
+    std::iter::once(()).flat_map(move |()| -> Box<dyn Iterator<Item = _> + 'a> {
+        if query.max_depth > 0 || query.dedupe {
+            return Box::new(std::iter::once(Err(Error::Other(
+                "list_iter does not descend into subdirectories or dedupe versions; use list"
+                    .to_string(),
+            ))));
+        }
+        let url = match query.listing_url() {
+            Ok(url) => url,
+            Err(e) => return Box::new(std::iter::once(Err(e))),
+        };
+        if url.scheme() == "ftp" {
+            let items = client::time_left(query.deadline, url.as_str())
+                .and_then(|_| {
+                    cached_listing(query, &url, || {
+                        ftp::fetch_listing(&url).map_err(Error::from)
+                    })
+                })
+                .and_then(|body| ftp::parse_listing(&body, &url, query, query.clock.now()));
+            return match items {
+                Ok((items, _)) => Box::new(items.into_iter().map(Ok)),
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            };
+        }
+        match fetch_listing(client, &url, query).and_then(|body| query.rows(&body, &url)) {
+            Ok(rows) => Box::new(
+                rows.into_iter()
+                    .filter(|row| !row.is_directory())
+                    .filter_map(move |row| row.item(query))
+                    .map(Ok),
+            ),
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    })
+}
+
+/// Keep one item per version, dropping the others, which are returned.
+///
+/// Of the items with the same version, the one with the newest `date` is kept. On equal dates,
+/// the one whose URL has the fewest path segments wins, so a file directly in the spec directory
+/// is preferred over a copy in a subdirectory, and then the smallest URL. Unversioned items are
+/// all kept. `items` stays in canonical order.
+pub fn dedupe_versions(items: &mut Vec<SpecItem>) -> Vec<SpecItem> {
+    dedupe_by(items, |item| item.version)
+}
+
+/// Keep one item per spec and version of the merged `items` of several specs, dropping the
+/// others, which are returned, for `--dedup-across-specs`. The same file may be listed under
+/// several directories, e.g. aliases of the spec or mirrors. Spec numbers are compared
+/// case-insensitively, and the kept item is chosen as by `dedupe_versions`.
+pub fn dedupe_across_specs(items: &mut Vec<SpecItem>) -> Vec<SpecItem> {
+    dedupe_by(items, |item| {
+        let spec = &item.spec;
+        let version = item.version?;
+        Some((
+            spec.series.clone(),
+            spec.number.to_ascii_lowercase(),
+            version,
+        ))
+    })
+}
+
+/// Keep one item per `key`, as described for `dedupe_versions`. Items without a key are all kept.
+fn dedupe_by<K: PartialEq>(
+    items: &mut Vec<SpecItem>,
+    key: impl Fn(&SpecItem) -> Option<K>,
+) -> Vec<SpecItem> {
+    let depth = |item: &SpecItem| item.url.split('/').filter(|s| !s.is_empty()).count();
+    let mut kept: Vec<(Option<K>, SpecItem)> = Vec::with_capacity(items.len());
+    let mut dropped = Vec::new();
+    for item in items.drain(..) {
+        let item_key = key(&item);
+        let same = |k: &&mut (Option<K>, SpecItem)| item_key.is_some() && k.0 == item_key;
+        match kept.iter_mut().find(same) {
+            None => kept.push((item_key, item)),
+            Some((_, existing)) => {
+                let better = item
+                    .date
+                    .cmp(&existing.date)
+                    .then_with(|| depth(existing).cmp(&depth(&item)))
+                    .then_with(|| existing.url.cmp(&item.url))
+                    .is_gt();
+                match better {
+                    true => dropped.push(std::mem::replace(existing, item)),
+                    false => dropped.push(item),
+                }
+            }
+        }
+    }
+    *items = kept.into_iter().map(|(_, item)| item).collect();
+    dropped
+}
+
+/// Layout of the 3GPP archive: `{series}_series/{series}.{number}`.
+pub const DEFAULT_PATH_TEMPLATE: &str = "{series}_series/{series}.{number}";
+
+/// Path of a spec directory below the archive root, with `{series}` and `{number}` placeholders
+/// for the parts of the spec number, for mirrors that lay out the archive differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate(String);
+
+impl PathTemplate {
+    /// The path of `spec`'s directory, e.g. `23_series/23.501` with the default template.
+    pub fn render(&self, spec: &SpecNumber) -> String {
+        self.0
+            .replace("{series}", &spec.series)
+            .replace("{number}", &spec.number)
+    }
+}
+
+impl Default for PathTemplate {
+    fn default() -> Self {
+        PathTemplate(DEFAULT_PATH_TEMPLATE.to_string())
+    }
+}
+
+impl std::fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for PathTemplate {
+    type Err = String;
+
+    /// Parse a template, checking that every `{...}` placeholder is `{series}` or `{number}`.
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed placeholder in path template '{}'", template))?;
+            let name = &rest[start + 1..start + end];
+            if name != "series" && name != "number" {
+                return Err(format!(
+                    "unknown placeholder '{{{}}}' in path template '{}': must be {{series}} or {{number}}",
+                    name, template
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        Ok(PathTemplate(template.to_string()))
+    }
+}
+
+/// URL of the directory listing for `spec` below the archive root `base`, e.g.
+/// `{base}23_series/23.501`. Fails the security check if the result is not below `base`.
+pub fn listing_url(base: &reqwest::Url, spec: &SpecNumber) -> Result<reqwest::Url, Error> {
+    listing_url_with(base, spec, &PathTemplate::default())
+}
+
+/// `listing_url` with the spec directory at `template` below `base`.
+pub fn listing_url_with(
+    base: &reqwest::Url,
+    spec: &SpecNumber,
+    template: &PathTemplate,
+) -> Result<reqwest::Url, Error> {
+    // Without a trailing slash, `join` would replace the last segment of the base.
+    let mut base = base.clone();
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    let path = template.render(spec);
+    let mut url = base
+        .join(&path)
+        .map_err(|e| format!("failed to join path to base URL: {}", e))?;
+
+    if !url.as_str().starts_with(base.as_str()) {
+        return Err(Error::SecurityCheck {
+            attempted_url: url.to_string(),
+            base: base.to_string(),
+        });
+    }
+    // The directory URL ends with a slash, so that hrefs and filenames joined to it resolve
+    // below the directory rather than next to it.
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+/// The listing page at `url` from `query.cache` if it is fresh there, otherwise from `fetch`, in
+/// which case it is added to the cache.
+fn cached_listing(
+    query: &ListQuery,
+    url: &reqwest::Url,
+    fetch: impl FnOnce() -> Result<String, Error>,
+) -> Result<String, Error> {
+    let Some(cache) = &query.cache else {
+        return fetch();
+    };
+    let now = query.clock.now();
+    if let Some(body) = cache.get(url, now) {
+        log::debug!("using cached listing of '{}'", url);
+        return Ok(body);
+    }
+    let body = fetch()?;
+    if let Err(e) = cache.put(url, &body, now) {
+        log::warn!("{}", e);
+    }
+    Ok(body)
+}
+
+/// Fetch the listing page at `url`, or take it from `query.cache`, and return its body.
+/// The page is requested in the order of `query.sort`, if set.
+fn fetch_listing(client: &Client, url: &reqwest::Url, query: &ListQuery) -> Result<String, Error> {
+    let sorted = query.sort.map(|sort| sort.apply(url));
+    let url = sorted.as_ref().unwrap_or(url);
+    cached_listing(query, url, || fetch_listing_page(client, url, query))
+}
+
+/// Fetch the listing page at `url` from the server and return its body.
+fn fetch_listing_page(
+    client: &Client,
+    url: &reqwest::Url,
+    query: &ListQuery,
+) -> Result<String, Error> {
+    let deadline = query.deadline;
+    let request = client
+        .get(url.clone())
+        .header(reqwest::header::ACCEPT_ENCODING, inflate::ACCEPT_ENCODING);
+    let mut request = cookies::with_cookies(request, query.cookies.as_ref(), url);
+    if let Some(left) = client::time_left(deadline, url.as_str())? {
+        request = request.timeout(left);
+    }
+    let timeout = || Error::Timeout {
+        url: url.to_string(),
+    };
+    let response = request
+        .send()
+        .map_err(|e| match client::deadline_passed(deadline) {
+            true => timeout(),
+            false if e.is_connect() => Error::Connect {
+                url: url.to_string(),
+                source: ConnectError::new(e),
+            },
+            false => Error::Request {
+                url: url.to_string(),
+                message: client::error_chain(&e),
+            },
+        })?;
+
+    if let Some(jar) = &query.cookies {
+        jar.store(response.url(), response.headers());
+    }
+    if !response.status().is_success() {
+        return Err(Error::Status {
+            url: url.to_string(),
+            status: response.status().as_u16(),
+        });
+    }
+
+    let encoding = response
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let body = response
+        .bytes()
+        .map_err(|e| match client::deadline_passed(deadline) {
+            true => timeout(),
+            false => Error::from(format!("failed to read response body: {}", e)),
+        })?
+        .to_vec();
+    let body = match encoding {
+        Some(encoding) => inflate::decode(&encoding, body)
+            .map_err(|e| format!("failed to decode listing '{}': {}", url, e))?,
+        None => body,
+    };
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Name of the subdirectory some spec directories keep their version files in.
+const VERSIONS_DIR: &str = "Versions";
+
+/// List the directory at `url`, descending into subdirectory rows up to `depth` levels.
+/// Only subdirectories below `url` are followed, so parent and sibling links are ignored.
+///
+/// A directory without any matching items but with a `Versions/` subdirectory is assumed to
+/// follow that convention, and `Versions/` is listed in its place even when `depth` is 0.
+fn list_directory(
+    client: &Client,
+    url: &reqwest::Url,
+    query: &ListQuery,
+    depth: u32,
+) -> Result<Vec<SpecItem>, Error> {
+    let (mut specs, directories) = match url.scheme() {
+        "ftp" => {
+            client::time_left(query.deadline, url.as_str())?;
+            let body = cached_listing(query, url, || ftp::fetch_listing(url).map_err(Error::from))?;
+            ftp::parse_listing(&body, url, query, query.clock.now())?
+        }
+        _ => parse_rows(&fetch_listing(client, url, query)?, url, query)?,
+    };
+    if depth == 0 && specs.is_empty() {
+        let versions = directories.iter().find_map(|href| {
+            let nested = subdirectory_url(url, href)?;
+            let name = nested.path_segments()?.rev().find(|s| !s.is_empty())?;
+            name.eq_ignore_ascii_case(VERSIONS_DIR).then_some(nested)
+        });
+        if let Some(versions) = versions {
+            log::info!("no items in '{}', listing '{}' instead", url, versions);
+            return match list_directory(client, &versions, query, 0) {
+                Err(Error::EmptyDirectory(_)) => Ok(specs),
+                result => result,
+            };
+        }
+    }
+    if depth == 0 || directories.is_empty() {
+        return Ok(specs);
+    }
+
+    for href in directories {
+        let Some(nested) = subdirectory_url(url, &href) else {
+            log::debug!("not descending into '{}' outside of '{}'", href, url);
+            continue;
+        };
+        match list_directory(client, &nested, query, depth - 1) {
+            Ok(items) => specs.extend(items),
+            Err(Error::EmptyDirectory(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    sort_canonical(&mut specs);
+    Ok(specs)
+}
+
+/// Resolve `href` against the listing at `url`, which names a directory with or without a
+/// trailing `/`. An href that cannot be resolved is returned as is.
+fn resolve_href(url: &reqwest::Url, href: &str) -> String {
+    let directory = format!("{}/", url.as_str().trim_end_matches('/'));
+    reqwest::Url::parse(&directory)
+        .and_then(|directory| directory.join(href))
+        .map_or_else(|_| href.to_string(), String::from)
+}
+
+/// Resolve the directory row `href` against the listing at `url`, or `None` if it does not point
+/// strictly below `url`.
+fn subdirectory_url(url: &reqwest::Url, href: &str) -> Option<reqwest::Url> {
+    let prefix = format!("{}/", url.as_str().trim_end_matches('/'));
+    let nested = reqwest::Url::parse(&prefix).ok()?.join(href).ok()?;
+    (nested.as_str().starts_with(&prefix) && nested.as_str() != prefix).then_some(nested)
+}
+
+/// Name of the file `href` links to: its last path segment, percent-decoded, without any
+/// fragment. `None` if the segment is empty, e.g. for a directory href ending with `/`, or if the
+/// href has a query, in which case the path names a script that serves the file.
+pub fn href_filename(href: &str) -> Option<String> {
+    let path = href.split('#').next().unwrap_or_default();
+    if path.contains('?') {
+        return None;
+    }
+    let segment = path.rsplit('/').next().unwrap_or_default();
+    (!segment.is_empty()).then(|| decode_segment(segment))
+}
+
+/// Percent-decode the URL path segment `segment`, e.g. `23501%20g40.zip` to `23501 g40.zip`, for
+/// use as a filename. The segment is kept as written if it would decode to a path separator or
+/// to `.` or `..`, so that it never names a file outside of the directory it is saved in.
+pub fn decode_segment(segment: &str) -> String {
+    let decoded = percent_decode(segment);
+    match decoded.contains(['/', '\\']) || decoded == "." || decoded == ".." {
+        true => segment.to_string(),
+        false => decoded,
+    }
+}
+
+/// Decode the `%XX` escapes in `s`. An escape that is not followed by two hex digits is kept as
+/// written, and so is all of `s` if the decoded bytes are not UTF-8.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| s.to_string())
+}
+
+/// Anchor and cells of one row of a listing page, as extracted by a `ListingParser`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingRow {
+    pub href: String,
+    /// Name of the linked file: the last segment of the href, or the text of the anchor if the
+    /// href has none. Listings may display a shortened name, so the href is preferred.
+    pub name: String,
+    /// Text of the date, parsed with `parse_listing_date`.
+    pub date: String,
+    pub size: Option<u64>,
+    /// Release in the release column of the row, if the listing has one (see
+    /// `HeaderIndexes::release`) and its text is a release (see `parse_release`).
+    pub release: Option<u32>,
+}
+
+impl ListingRow {
+    /// Whether the row links to a directory, i.e. its href ends with `/`.
+    fn is_directory(&self) -> bool {
+        self.href.ends_with('/')
+    }
+
+    /// The item for this file row if its date can be parsed and it passes the filters in `query`.
+    fn item(self, query: &ListQuery) -> Option<SpecItem> {
+        let date = query.listing_date(parse_listing_date(&self.date)?);
+        listed_item(query, &self.name, self.href, date, self.size, self.release)
+    }
+
+    /// Whether `item` would return an item for this row, and if not, why.
+    fn explain(self, query: &ListQuery) -> Explanation {
+        let date = parse_listing_date(&self.date).map(|date| query.listing_date(date));
+        let result = match date {
+            Some(date) => evaluate(
+                query,
+                &self.name,
+                self.href.clone(),
+                date,
+                self.size,
+                self.release,
+            ),
+            None => Err(DropReason::UnparseableDate),
+        };
+        Explanation {
+            version: parse_version(&self.name),
+            filename: self.name,
+            url: self.href,
+            date,
+            kept: result.is_ok(),
+            reason: result.err(),
+        }
+    }
+}
+
+/// Compiled selector for `css`, a constant that is known to be valid.
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).unwrap_or_else(|e| panic!("invalid selector '{}': {:?}", css, e))
+}
+
+// Selectors of listing pages, compiled once rather than on every listing.
+static TBODY_ROWS: LazyLock<Selector> = LazyLock::new(|| selector("tbody > tr"));
+static TABLE_ROWS: LazyLock<Selector> = LazyLock::new(|| selector("table tr"));
+static THEAD_CELLS: LazyLock<Selector> = LazyLock::new(|| selector("thead > tr > th"));
+static HEADER_CELLS: LazyLock<Selector> = LazyLock::new(|| selector("th"));
+static DATA_CELLS: LazyLock<Selector> = LazyLock::new(|| selector("td"));
+static CELLS: LazyLock<Selector> = LazyLock::new(|| selector("th, td"));
+static LINKS: LazyLock<Selector> = LazyLock::new(|| selector("a[href]"));
+
+/// Extract the rows with an anchor from a listing page `body`, in document order.
+/// Returns `Error::EmptyDirectory` for `spec` if the page has no data rows at all. With `lenient`,
+/// the columns are found with `guess_header_indexes`.
+fn listing_rows(body: &str, spec: &SpecNumber, lenient: bool) -> Result<Vec<ListingRow>, Error> {
+    let document = Html::parse_document(body);
+    let HeaderIndexes {
+        name: name_idx,
+        date: date_idx,
+        size: size_idx,
+        release: release_idx,
+    } = match lenient {
+        true => guess_header_indexes(&document)?,
+        false => find_header_indexes(&document)?,
+    };
+
+    // Raw HTML may put rows directly in `<table>` without a `<tbody>`. The HTML parser usually
+    // inserts one, but if no `tbody` rows are found take every table row. Either way header rows,
+    // which may land in the `<tbody>` too, are left out.
+    let mut data_rows: Vec<_> = document
+        .select(&TBODY_ROWS)
+        .filter(|row| !is_header_row(row))
+        .collect();
+    if data_rows.is_empty() {
+        data_rows = document
+            .select(&TABLE_ROWS)
+            .filter(|row| !is_header_row(row))
+            .collect();
+    }
+    if data_rows.is_empty() {
+        return Err(Error::EmptyDirectory(spec.clone()));
+    }
+
+    let last_idx = name_idx
+        .max(date_idx)
+        .max(size_idx.unwrap_or(0))
+        .max(release_idx.unwrap_or(0));
+    let mut rows = Vec::with_capacity(data_rows.len());
+    for row in data_rows {
+        // Only the name, date and size cells are needed, so the others are skipped over. Row
+        // header cells (`<th>`) count as columns, so indexes line up with the header.
+        let (mut name_cell, mut date_cell, mut size_cell, mut release_cell) =
+            (None, None, None, None);
+        for (i, cell) in row.select(&CELLS).take(last_idx + 1).enumerate() {
+            if i == name_idx {
+                name_cell = Some(cell);
+            }
+            if i == date_idx {
+                date_cell = Some(cell);
+            }
+            if Some(i) == size_idx {
+                size_cell = Some(cell);
+            }
+            if Some(i) == release_idx {
+                release_cell = Some(cell);
+            }
+        }
+        let (Some(name_cell), Some(date_cell)) = (name_cell, date_cell) else {
+            continue;
+        };
+
+        let Some(anchor) = name_cell.select(&LINKS).next() else {
+            continue;
+        };
+        let href = anchor.value().attr("href").unwrap_or("").to_string();
+        rows.push(ListingRow {
+            name: href_filename(&href).unwrap_or_else(|| anchor.text().collect()),
+            href,
+            date: date_cell.text().collect(),
+            size: size_cell.and_then(|cell| parse_size(&cell.text().collect::<String>())),
+            release: release_cell
+                .and_then(|cell| parse_release(&cell.text().collect::<String>()).ok()),
+        });
+    }
+    Ok(rows)
+}
+
+/// Whether the table `row` is a header row, i.e. holds `<th>` cells only. Data rows may start
+/// with a `<th>` row header but always have a `<td>` cell.
+fn is_header_row(row: &ElementRef) -> bool {
+    row.select(&DATA_CELLS).next().is_none()
+}
+
+/// Parse a listing page `body` into spec items, applying the filters in `query`, and collect the
+/// hrefs of its directory rows. Rows whose date cannot be parsed are skipped.
+/// A row is a directory when its href ends with `/`; such rows never produce items.
+fn parse_rows(
+    body: &str,
+    url: &reqwest::Url,
+    query: &ListQuery,
+) -> Result<(Vec<SpecItem>, Vec<String>), Error> {
+    let (directories, files): (Vec<_>, Vec<_>) = query
+        .rows(body, url)?
+        .into_iter()
+        .partition(ListingRow::is_directory);
+    let mut specs: Vec<_> = files
+        .into_iter()
+        .filter_map(|row| row.item(query))
+        .collect();
+    sort_canonical(&mut specs);
+
+    Ok((specs, directories.into_iter().map(|row| row.href).collect()))
+}
+
+/// Why a listed file was left out of a listing, as reported by `explain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DropReason {
+    /// The date cell could not be parsed (see `parse_listing_date`).
+    UnparseableDate,
+    /// Not of `ListQuery::release`.
+    Release,
+    /// Not matching `ListQuery::version`, or unversioned.
+    Version,
+    /// Editorial version below `ListQuery::min_editorial`.
+    Editorial,
+    /// A draft, and `ListQuery::include_drafts` is not set.
+    Draft,
+    /// Outside the three months of `ListQuery::date_filter`.
+    DateFilter,
+    /// Dated before `ListQuery::since`.
+    Since,
+}
+
+impl DropReason {
+    /// Kebab-case name of the reason, as in the JSON output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DropReason::UnparseableDate => "unparseable-date",
+            DropReason::Release => "release",
+            DropReason::Version => "version",
+            DropReason::Editorial => "editorial",
+            DropReason::Draft => "draft",
+            DropReason::DateFilter => "date-filter",
+            DropReason::Since => "since",
+        }
+    }
+}
+
+impl std::fmt::Display for DropReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Outcome of the filters of a `ListQuery` for one file row of a listing, for `--explain`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Explanation {
+    /// Filename as displayed in the listing.
+    pub filename: String,
+    pub url: String,
+    pub version: Option<Version>,
+    /// Date of the row, `None` if it could not be parsed.
+    pub date: Option<DateTime<Utc>>,
+    pub kept: bool,
+    /// The first filter that dropped the row, if it was dropped.
+    pub reason: Option<DropReason>,
+}
+
+/// Explain, for each file row of the spec directory listing of `query` in document order, whether
+/// the filters of `query` keep it and if not, which one drops it. Subdirectories are not
+/// descended into, deduplication is not applied, and FTP listings are not supported.
+pub fn explain(client: &Client, query: &ListQuery) -> Result<Vec<Explanation>, Error> {
+    let url = query.listing_url()?;
+    if url.scheme() == "ftp" {
+        return Err(Error::Other(format!(
+            "cannot explain '{}': FTP listings are not supported",
+            url
+        )));
+    }
+    explain_rows(&fetch_listing(client, &url, query)?, &url, query)
+}
+
+/// Explanations for the file rows of the listing page `body`, served from `url`.
+fn explain_rows(
+    body: &str,
+    url: &reqwest::Url,
+    query: &ListQuery,
+) -> Result<Vec<Explanation>, Error> {
+    Ok(query
+        .rows(body, url)?
+        .into_iter()
+        .filter(|row| !row.is_directory())
+        .map(|row| row.explain(query))
+        .collect())
+}
+
+/// Build the item for a listed file named `filename`, or `None` if the filters in `query`
+/// exclude it.
+fn listed_item(
+    query: &ListQuery,
+    filename: &str,
+    url: String,
+    date: DateTime<Utc>,
+    size: Option<u64>,
+    release: Option<u32>,
+) -> Option<SpecItem> {
+    evaluate(query, filename, url, date, size, release).ok()
+}
+
+/// Build the item for a listed file named `filename`, or return the first filter in `query`
+/// that excludes it. A `release` read from the listing takes precedence over one named in the
+/// filename.
+fn evaluate(
+    query: &ListQuery,
+    filename: &str,
+    url: String,
+    date: DateTime<Utc>,
+    size: Option<u64>,
+    release: Option<u32>,
+) -> Result<SpecItem, DropReason> {
+    let version = parse_version(filename);
+    let release = release.or_else(|| parse_release_token(filename));
+
+    // Unversioned items belong to no release, unless their filename names one. Keep in line
+    // with `SpecItem::effective_release`.
+    if let Some(rel) = query.release
+        && release.or(version.map(|v| v.major)) != Some(rel)
+    {
+        return Err(DropReason::Release);
+    }
+
+    if let Some(selector) = query.version
+        && !version.is_some_and(|version| selector.matches(&version))
+    {
+        return Err(DropReason::Version);
+    }
+
+    if let (Some(min), Some(version)) = (query.min_editorial, version)
+        && version.editorial < min
+    {
+        return Err(DropReason::Editorial);
+    }
+
+    let draft = is_draft(filename, version.as_ref());
+    if draft && !query.include_drafts {
+        return Err(DropReason::Draft);
+    }
+
+    // If a `date_filter` is provided, derive `filter_start` and `filter_end`.
+    // `filter_start` is the first day of that year/month at 00:00 UTC.
+    // `filter_end` is three months after `filter_start` (exclusive upper bound).
+    let (filter_start, filter_end): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) =
+        if let Some(df) = query.date_filter {
+            (df.start(), df.checked_add(3).and_then(YearMonth::start))
+        } else {
+            (None, None)
+        };
+
+    // If a `date_filter` was provided, exclude rows outside [filter_start, filter_end).
+    if let Some(start) = filter_start {
+        if date < start {
+            return Err(DropReason::DateFilter);
+        }
+        if let Some(end) = filter_end
+            && date >= end
+        {
+            return Err(DropReason::DateFilter);
+        }
+    }
+
+    if let Some(since) = query.since
+        && date < since
+    {
+        return Err(DropReason::Since);
+    }
+
+    Ok(SpecItem {
+        spec: query.spec_number.clone(),
+        version,
+        release,
+        date,
+        url,
+        filename: filename.to_string(),
+        draft,
+        size,
+    })
+}
+
+/// Column indexes of the listing table, as found by `find_header_indexes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderIndexes {
+    pub name: usize,
+    pub date: usize,
+    /// Index of the "size" column, if the table has one.
+    pub size: Option<usize>,
+    /// Index of the "release" (or "rel") column, if the table has one, as some enriched
+    /// listings do.
+    pub release: Option<usize>,
+}
+
+/// Find the column indexes for "name", "date" and (optionally) "size" in the table header.
+/// Fails if the "name" or "date" column is missing.
+pub fn find_header_indexes(document: &Html) -> Result<HeaderIndexes, String> {
+    header_indexes(document.select(&THEAD_CELLS))
+}
+
+/// Lenient variant of `find_header_indexes`, for `ListQuery::lenient_html`. Header cells are
+/// also looked for in rows outside a `<thead>`. A table without recognizable headers has its
+/// columns guessed from the first row with a link: the linked cell is the name, the first cell
+/// holding a listing date is the date, and the first other cell holding a size is the size.
+pub fn guess_header_indexes(document: &Html) -> Result<HeaderIndexes, String> {
+    if let Ok(indexes) = find_header_indexes(document) {
+        return Ok(indexes);
+    }
+    for row in document.select(&TABLE_ROWS).filter(is_header_row) {
+        if let Ok(indexes) = header_indexes(row.select(&HEADER_CELLS)) {
+            return Ok(indexes);
+        }
+    }
+    for row in document.select(&TABLE_ROWS) {
+        let cells: Vec<_> = row.select(&CELLS).collect();
+        let text = |cell: &ElementRef| cell.text().collect::<String>();
+        let Some(name) = cells
+            .iter()
+            .position(|cell| cell.select(&LINKS).next().is_some())
+        else {
+            continue;
+        };
+        let Some(date) = cells
+            .iter()
+            .position(|cell| parse_listing_date(&text(cell)).is_some())
+        else {
+            continue;
+        };
+        let size = (0..cells.len())
+            .find(|&i| i != name && i != date && parse_size(&text(&cells[i])).is_some());
+        log::info!(
+            "listing has no 'name' and 'date' headers, guessed columns {} and {}",
+            name,
+            date
+        );
+        return Ok(HeaderIndexes {
+            name,
+            date,
+            size,
+            release: None,
+        });
+    }
+    Err("failed to find 'name' and 'date' columns, by header or by position".to_string())
+}
+
+/// Column indexes for the header cells `cells` whose text contains "name", "date", "size" and
+/// "release", or has the word "rel".
+fn header_indexes<'a>(
+    cells: impl Iterator<Item = ElementRef<'a>>,
+) -> Result<HeaderIndexes, String> {
+    let mut name_idx = None;
+    let mut date_idx = None;
+    let mut size_idx = None;
+    let mut release_idx = None;
+
+    for (i, element) in cells.enumerate() {
+        let text = element.text().collect::<String>().to_lowercase();
+        if name_idx.is_none() && text.contains("name") {
+            name_idx = Some(i);
+        }
+        if date_idx.is_none() && text.contains("date") {
+            date_idx = Some(i);
+        }
+        if size_idx.is_none() && text.contains("size") {
+            size_idx = Some(i);
+        }
+        let rel = |word: &str| word.trim_end_matches(['.', ':']) == "rel";
+        if release_idx.is_none() && (text.contains("release") || text.split_whitespace().any(rel)) {
+            release_idx = Some(i);
+        }
+    }
+
+    match (name_idx, date_idx) {
+        (Some(name), Some(date)) => Ok(HeaderIndexes {
+            name,
+            date,
+            size: size_idx,
+            release: release_idx,
+        }),
+        _ => Err("failed to find 'name' and 'date' columns".to_string()),
+    }
+}
+
+/// Parse the text of a listing "size" cell into bytes.
+///
+/// Accepts a number with an optional unit: `B`/`bytes`, `K`/`KB`/`KiB`, `M`/`MB`/`MiB`,
+/// `G`/`GB`/`GiB` (case-insensitive, all 1024-based). Commas are read as thousands separators
+/// when every group after one has exactly three digits (`1,234,567`), and as a decimal
+/// separator otherwise (`1,2 MB`). Returns `None` if the cell cannot be parsed.
+pub fn parse_size(s: &str) -> Option<u64> {
+    static SIZE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?i)^([0-9][0-9.,]*)\s*(b|bytes?|k|kb|kib|m|mb|mib|g|gb|gib)?$")
+            .expect("valid size regex")
+    });
+    let caps = SIZE.captures(s.trim())?;
+    let number = &caps[1];
+
+    let groups: Vec<&str> = number.split(',').collect();
+    let grouped = groups.len() > 1
+        && groups[1..]
+            .iter()
+            .all(|g| g.len() == 3 && g.chars().all(|c| c.is_ascii_digit()));
+    let number = match grouped {
+        true => number.replace(',', ""),
+        false => number.replace(',', "."),
+    };
+    let value: f64 = number.parse().ok()?;
+
+    let multiplier: u64 = match caps.get(2).map(|u| u.as_str().to_lowercase()) {
+        None => 1,
+        Some(unit) => match unit.chars().next()? {
+            'k' => 1 << 10,
+            'm' => 1 << 20,
+            'g' => 1 << 30,
+            _ => 1,
+        },
+    };
+    Some((value * multiplier as f64).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DateFormat, DropReason, DurationUnit, Error, Explanation, FixedClock, HeaderIndexes,
+        ListQuery, ListingSort, Month, PartialVersion, PathTemplate, RelativeDuration, SpecItem,
+        SpecNumber, Version, YearMonth, change_requests, decode_segment, dedupe_across_specs,
+        dedupe_versions, explain_rows, find_header_indexes, get_version, guess_header_indexes,
+        href_filename, is_change_requests, is_draft, latest_editorial, list, list_directory,
+        list_from_body, list_iter, listing_rows, listing_url, listing_url_with, parse_listing_date,
+        parse_release, parse_release_token, parse_rows, parse_size, parse_spec_number,
+        parse_spec_number_strict, parse_spec_ref, parse_version,
+    };
+    use crate::test_support::{MockServer, Response};
+    use chrono::{TimeZone, Utc};
+    use reqwest::blocking::Client;
     use scraper::Html;
 
+    /// Row extraction as it was before selectors were cached and cells were visited by index:
+    /// every cell of a row is collected. Kept to check the two give the same rows.
+    fn reference_rows(body: &str, lenient: bool) -> Vec<(String, String, String, Option<u64>)> {
+        use scraper::Selector;
+        let document = Html::parse_document(body);
+        let indexes = match lenient {
+            true => guess_header_indexes(&document).unwrap(),
+            false => find_header_indexes(&document).unwrap(),
+        };
+        let cell_selector = Selector::parse("td").unwrap();
+        let link_selector = Selector::parse("a[href]").unwrap();
+        let mut rows = Vec::new();
+        for row in document.select(&Selector::parse("tbody > tr").unwrap()) {
+            let cells: Vec<_> = row.select(&cell_selector).collect();
+            if cells.len() <= std::cmp::max(indexes.name, indexes.date) {
+                continue;
+            }
+            let Some(anchor) = cells[indexes.name].select(&link_selector).next() else {
+                continue;
+            };
+            let href = anchor.value().attr("href").unwrap_or("").to_string();
+            rows.push((
+                href_filename(&href).unwrap_or_else(|| anchor.text().collect()),
+                href,
+                cells[indexes.date].text().collect(),
+                indexes
+                    .size
+                    .and_then(|i| cells.get(i))
+                    .and_then(|cell| parse_size(&cell.text().collect::<String>())),
+            ));
+        }
+        rows
+    }
+
+    /// Parse a listing page, discarding its directory rows.
+    fn parse_listing(body: &str, query: &ListQuery) -> Result<Vec<SpecItem>, Error> {
+        parse_rows(body, &query.listing_url().unwrap(), query).map(|(specs, _)| specs)
+    }
+
+    /// Query for 23.501 with no filters applied.
+    fn query() -> ListQuery {
+        ListQuery::new(parse_spec_number("23.501").unwrap())
+    }
+
+    /// Build a minimal listing page with a name/date header and the given `(filename, date)` rows.
+    fn listing_html(rows: &[(&str, &str)]) -> String {
+        let hrefs: Vec<_> = rows
+            .iter()
+            .map(|(name, _)| format!("https://example.com/{}", name))
+            .collect();
+        let rows: Vec<_> = rows
+            .iter()
+            .zip(&hrefs)
+            .map(|((name, date), href)| (href.as_str(), *name, *date))
+            .collect();
+        listing_html_with_hrefs(&rows)
+    }
+
+    /// Build a minimal listing page with the given `(href, text, date)` rows.
+    fn listing_html_with_hrefs(rows: &[(&str, &str, &str)]) -> String {
+        let mut html =
+            String::from("<table><thead><tr><th>name</th><th>date</th></tr></thead><tbody>");
+        for (href, text, date) in rows {
+            html.push_str(&format!(
+                "<tr><td><a href=\"{}\">{}</a></td><td>{}</td></tr>",
+                href, text, date
+            ));
+        }
+        html.push_str("</tbody></table>");
+        html
+    }
+
     #[test]
     fn valid_examples() {
         assert_eq!(
@@ -400,6 +2196,60 @@ mod tests {
         assert!(parse_spec_number("").is_err());
     }
 
+    #[test]
+    fn lenient_html_parsing() {
+        // Header cells outside a `<thead>`, then no headers at all.
+        let th_rows = "<table><tr><th>Size</th><th>Name</th><th>Date</th></tr>\
+             <tr><td>1 MB</td><td><a href=\"https://example.com/23501-g40.zip\">23501-g40.zip</a></td>\
+             <td>2020/03/27 14:30</td></tr></table>";
+        let headless = "<table>\
+             <tr><td><a href=\"https://example.com/23501-g40.zip\">23501-g40.zip</a></td>\
+             <td>2 KB</td><td>2020/03/27 14:30</td></tr>\
+             <tr><td><a href=\"https://example.com/23501-g50.zip\">23501-g50.zip</a></td>\
+             <td>3 KB</td><td>2020/06/12 10:02</td></tr></table>";
+        let strict = query();
+        let lenient = ListQuery {
+            lenient_html: true,
+            ..query()
+        };
+
+        for html in [th_rows, headless] {
+            let err = parse_listing(html, &strict).unwrap_err();
+            assert_eq!(
+                err,
+                Error::Other("failed to find 'name' and 'date' columns".to_string())
+            );
+        }
+        let items = parse_listing(th_rows, &lenient).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].size, Some(1024 * 1024));
+
+        let doc = Html::parse_document(headless);
+        assert_eq!(
+            guess_header_indexes(&doc),
+            Ok(HeaderIndexes {
+                name: 0,
+                date: 2,
+                size: Some(1),
+                release: None
+            })
+        );
+        let items = parse_listing(headless, &lenient).unwrap();
+        let versions: Vec<_> = items
+            .iter()
+            .map(|i| i.version.unwrap().to_string())
+            .collect();
+        assert_eq!(versions, ["16.4.0", "16.5.0"]);
+        assert_eq!(items[1].size, Some(3 * 1024));
+
+        // Well-formed listings parse the same either way.
+        let html = listing_html(&[("23501-g40.zip", "2020/03/27 14:30")]);
+        assert_eq!(
+            parse_listing(&html, &strict),
+            parse_listing(&html, &lenient)
+        );
+    }
+
     #[test]
     fn test_find_header_indexes() {
         let html = r#"
@@ -418,6 +2268,1759 @@ mod tests {
                 </thead>
         "#;
         let doc = Html::parse_document(html);
-        assert_eq!(find_header_indexes(&doc), Ok((2, 3)));
+        assert_eq!(
+            find_header_indexes(&doc),
+            Ok(HeaderIndexes {
+                name: 2,
+                date: 3,
+                size: Some(4),
+                release: None
+            })
+        );
+    }
+
+    #[test]
+    fn release_column() {
+        let html = "<table><thead><tr><th>Name</th><th>Date</th><th>Size</th><th>Rel</th>\
+                    </tr></thead><tbody>\
+                    <tr><td><a href=\"/a/23501-g40.zip\">23501-g40.zip</a></td>\
+                    <td>2020/03/27 14:30</td><td>1k</td><td>Rel-16</td></tr>\
+                    <tr><td><a href=\"/a/23501-h10_Rel-16.zip\">23501-h10_Rel-16.zip</a></td>\
+                    <td>2021/03/27 14:30</td><td>1k</td><td>17</td></tr>\
+                    <tr><td><a href=\"/a/23501-i00.zip\">23501-i00.zip</a></td>\
+                    <td>2022/03/27 14:30</td><td>1k</td><td>TBD</td></tr>\
+                    </tbody></table>";
+        assert_eq!(
+            find_header_indexes(&Html::parse_document(html)),
+            Ok(HeaderIndexes {
+                name: 0,
+                date: 1,
+                size: Some(2),
+                release: Some(3)
+            })
+        );
+        let items = parse_listing(html, &query()).unwrap();
+        let releases: Vec<_> = items.iter().map(|i| i.release).collect();
+        // The column wins over the `Rel-16` token of the filename, and unreadable cells are
+        // left to the filename.
+        assert_eq!(releases, [Some(16), Some(17), None]);
+
+        let rel17 = ListQuery {
+            release: Some(17),
+            ..query()
+        };
+        let items = parse_listing(html, &rel17).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].filename, "23501-h10_Rel-16.zip");
+
+        let long = html.replace("<th>Rel</th>", "<th>3GPP Release</th>");
+        let indexes = find_header_indexes(&Html::parse_document(&long)).unwrap();
+        assert_eq!(indexes.release, Some(3));
+    }
+
+    #[test]
+    fn listing_date_formats() {
+        let expected = Utc.with_ymd_and_hms(2023, 5, 1, 14, 30, 0).unwrap();
+        assert_eq!(parse_listing_date("2023/05/01 14:30"), Some(expected));
+        assert_eq!(parse_listing_date("  2023/05/01 14:30\n  "), Some(expected));
+        assert_eq!(parse_listing_date("2023-05-01 14:30"), Some(expected));
+        assert_eq!(parse_listing_date("2023/05/01 14:30:00"), Some(expected));
+        assert_eq!(parse_listing_date("2023-05-01T14:30:00"), Some(expected));
+        assert_eq!(
+            parse_listing_date("2017/01/25 9:15"),
+            Some(Utc.with_ymd_and_hms(2017, 1, 25, 9, 15, 0).unwrap())
+        );
+        assert_eq!(parse_listing_date("01.05.2023"), None);
+        assert_eq!(parse_listing_date(""), None);
+    }
+
+    #[test]
+    fn unparseable_date_skips_row() {
+        let html = listing_html(&[
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-g50.zip", "2020-06-12 10:02"),
+            ("23501-g60.zip", "sometime in 2020"),
+        ]);
+        let items = parse_listing(&html, &query()).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].version.unwrap().minor, 4);
+        assert_eq!(items[1].version.unwrap().minor, 5);
+    }
+
+    #[test]
+    fn draft_heuristic() {
+        let v = |major, minor, editorial| Version {
+            major,
+            minor,
+            editorial,
+        };
+        assert!(is_draft("23501-010.zip", Some(&v(0, 1, 0))));
+        assert!(is_draft("23501-100.zip", Some(&v(1, 0, 0))));
+        assert!(is_draft("23501-200.zip", Some(&v(2, 0, 0))));
+        assert!(is_draft("draft_23501-g00.zip", Some(&v(16, 0, 0))));
+        assert!(is_draft("23501-g00_DRAFT.zip", Some(&v(16, 0, 0))));
+        assert!(!is_draft("23501-f00.zip", Some(&v(15, 0, 0))));
+        assert!(!is_draft("23501-300.zip", Some(&v(3, 0, 0))));
+        // Unversioned files only by their name.
+        assert!(is_draft("23501_CRs_draft.zip", None));
+        assert!(!is_draft("23501_CRs.zip", None));
+
+        assert!(v(0, 1, 0).is_prerelease());
+        assert!(v(1, 9, 9).is_prerelease());
+        assert!(v(2, 0, 0).is_prerelease());
+        assert!(!v(3, 0, 0).is_prerelease());
+        assert!(!v(16, 0, 0).is_prerelease());
+        assert!(!v(16, 4, 1).is_prerelease());
+    }
+
+    #[test]
+    fn draft_filter() {
+        let html = listing_html(&[
+            ("23501-100.zip", "2017/06/01 10:00"),
+            ("draft_23501-g10.zip", "2019/06/01 10:00"),
+            ("23501-g00.zip", "2019/03/01 10:00"),
+        ]);
+
+        let items = parse_listing(&html, &query()).unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items.iter().map(|i| i.draft).collect::<Vec<_>>(),
+            vec![true, false, true]
+        );
+
+        let query = ListQuery {
+            include_drafts: false,
+            ..query()
+        };
+        let items = parse_listing(&html, &query).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].url, "https://example.com/23501-g00.zip");
+        assert!(!items[0].draft);
+    }
+
+    #[test]
+    fn spec_number_set_from_query() {
+        let html = listing_html(&[("23501-g00.zip", "2019/03/01 10:00")]);
+        let query = ListQuery::new(parse_spec_number("23501").unwrap());
+        let items = parse_listing(&html, &query).unwrap();
+        assert_eq!(items[0].spec, query.spec_number);
+        assert!(items[0].to_string().starts_with("23.501 16. 0. 0 @ "));
+    }
+
+    #[test]
+    fn spec_ref_with_release() {
+        let spec_number = parse_spec_number("23.501").unwrap();
+        for input in [
+            "23.501 Rel-16",
+            "23501 rel16",
+            "23.501-16",
+            "23.501 Release 16",
+        ] {
+            let spec_ref = parse_spec_ref(input).unwrap();
+            assert_eq!(spec_ref.spec_number, spec_number, "{}", input);
+            assert_eq!(spec_ref.release, Some(16), "{}", input);
+        }
+
+        let plain = parse_spec_ref("23.501").unwrap();
+        assert_eq!(plain.spec_number, spec_number);
+        assert_eq!(plain.release, None);
+
+        assert!(parse_spec_ref("23.501 Rel-").is_err());
+        assert!(parse_spec_ref("2.501 Rel-16").is_err());
+    }
+
+    #[test]
+    fn release_names() {
+        for input in [
+            "16",
+            "Rel-16",
+            "rel-16",
+            "Rel 16",
+            "REL16",
+            "Release 16",
+            "release-16",
+            "R16",
+            "r16",
+            " 16 ",
+        ] {
+            assert_eq!(parse_release(input), Ok(16), "{}", input);
+        }
+        for input in [
+            "",
+            "Rel-",
+            "Release",
+            "R",
+            "16.4",
+            "Rel-16a",
+            "Rl-16",
+            "-16",
+            "99999999999",
+        ] {
+            let err = parse_release(input).unwrap_err();
+            assert!(
+                err.starts_with(&format!("invalid release '{}'", input)),
+                "{}",
+                err
+            );
+        }
+
+        let html = listing_html(&[
+            ("23501-f00.zip", "2017/12/01 10:00"),
+            ("23501-g00.zip", "2019/03/01 10:00"),
+        ]);
+        for input in ["15", "Release 15", "R15"] {
+            let query = ListQuery {
+                release: Some(parse_release(input).unwrap()),
+                ..query()
+            };
+            let items = parse_listing(&html, &query).unwrap();
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].version.unwrap().major, 15);
+        }
+    }
+
+    #[test]
+    fn spec_ref_release_filters() {
+        let html = listing_html(&[
+            ("23501-f00.zip", "2017/12/01 10:00"),
+            ("23501-g00.zip", "2019/03/01 10:00"),
+        ]);
+        let query = ListQuery::from(parse_spec_ref("23.501 Rel-16").unwrap());
+        let items = parse_listing(&html, &query).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].version.unwrap().major, 16);
+    }
+
+    #[test]
+    fn explain_filter_outcomes() {
+        let body = listing_html(&[
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-f90.zip", "2019/12/20 10:00"),
+            ("23501-g41.zip", "yesterday"),
+            ("23501-100.zip", "2020/04/01 10:00"),
+            ("23501-g50.zip", "2019/06/01 10:00"),
+            ("23501_CRs.zip", "2020/04/01 10:00"),
+        ]);
+        let filtered = ListQuery {
+            release: Some(16),
+            include_drafts: false,
+            since: Some(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap()),
+            ..query()
+        };
+        let explained = explain_rows(&body, &filtered.listing_url().unwrap(), &filtered).unwrap();
+        let outcomes: Vec<_> = explained
+            .iter()
+            .map(|e| (e.filename.as_str(), e.kept, e.reason))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("23501-g40.zip", true, None),
+                ("23501-f90.zip", false, Some(DropReason::Release)),
+                ("23501-g41.zip", false, Some(DropReason::UnparseableDate)),
+                ("23501-100.zip", false, Some(DropReason::Release)),
+                ("23501-g50.zip", false, Some(DropReason::Since)),
+                ("23501_CRs.zip", false, Some(DropReason::Release)),
+            ]
+        );
+        assert_eq!(explained[2].date, None);
+        assert_eq!(explained[2].version.unwrap().to_string(), "16.4.1");
+
+        let drafts = ListQuery {
+            include_drafts: false,
+            ..query()
+        };
+        let explained = explain_rows(&body, &drafts.listing_url().unwrap(), &drafts).unwrap();
+        assert_eq!(explained[3].reason, Some(DropReason::Draft));
+        assert_eq!(
+            serde_json::to_value(&explained[3]).unwrap(),
+            serde_json::json!({
+                "filename": "23501-100.zip",
+                "url": "https://example.com/23501-100.zip",
+                "version": {"major": 1, "minor": 0, "editorial": 0},
+                "date": "2020-04-01T10:00:00Z",
+                "kept": false,
+                "reason": "draft",
+            })
+        );
+        let kept: Vec<Explanation> = explained.into_iter().filter(|e| e.kept).collect();
+        assert_eq!(kept.len(), 4);
+    }
+
+    #[test]
+    fn partial_version_wildcards() {
+        let v = |major, minor, editorial| Version {
+            major,
+            minor,
+            editorial,
+        };
+        let minor: PartialVersion = "16.4".parse().unwrap();
+        assert!(minor.matches(&v(16, 4, 0)));
+        assert!(minor.matches(&v(16, 4, 5)));
+        assert!(!minor.matches(&v(16, 5, 0)));
+        assert!(!minor.matches(&v(17, 4, 0)));
+
+        let major: PartialVersion = "16".parse().unwrap();
+        assert!(major.matches(&v(16, 0, 0)));
+        assert!(major.matches(&v(16, 9, 3)));
+        assert!(!major.matches(&v(15, 9, 3)));
+
+        let exact: PartialVersion = "16.4.0".parse().unwrap();
+        assert_eq!(exact, PartialVersion::from(v(16, 4, 0)));
+        assert!(exact.matches(&v(16, 4, 0)));
+        assert!(!exact.matches(&v(16, 4, 1)));
+
+        assert_eq!(minor.to_string(), "16.4");
+        assert_eq!(exact.to_string(), "16.4.0");
+        for invalid in ["", "16.", "16.4.0.1", "v16", "16.x"] {
+            assert!(invalid.parse::<PartialVersion>().is_err(), "{}", invalid);
+        }
+
+        let body = listing_html(&[
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-g45.zip", "2020/06/01 10:00"),
+            ("23501-g50.zip", "2020/07/09 10:00"),
+            ("23501-h00.zip", "2021/12/23 09:00"),
+            ("23501_CRs.zip", "2021/12/23 09:00"),
+        ]);
+        let versions = |selector: &str| {
+            let query = ListQuery {
+                version: Some(selector.parse().unwrap()),
+                ..query()
+            };
+            parse_listing(&body, &query)
+                .unwrap()
+                .iter()
+                .map(|i| i.version.unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(versions("16.4"), ["16.4.0", "16.4.5"]);
+        assert_eq!(versions("16"), ["16.4.0", "16.4.5", "16.5.0"]);
+        assert_eq!(versions("17.0.0"), ["17.0.0"]);
+    }
+
+    #[test]
+    fn latest_editorial_of_minor_version() {
+        let body = listing_html(&[
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-g42.zip", "2020/06/01 10:00"),
+            ("23501-g41.zip", "2020/04/20 10:00"),
+            ("23501-g50.zip", "2020/07/09 10:00"),
+            ("23501-f93.zip", "2020/03/20 10:00"),
+        ]);
+        let items = parse_listing(&body, &query()).unwrap();
+        let spec = parse_spec_number("23.501").unwrap();
+        let latest = latest_editorial(&items, &spec, 16, 4).unwrap();
+        assert_eq!(latest.version.unwrap().to_string(), "16.4.2");
+        assert_eq!(
+            latest_editorial(&items, &spec, 16, 5)
+                .unwrap()
+                .version
+                .unwrap()
+                .to_string(),
+            "16.5.0"
+        );
+        assert_eq!(latest_editorial(&items, &spec, 16, 6), None);
+        let other = parse_spec_number("24.501").unwrap();
+        assert_eq!(latest_editorial(&items, &other, 16, 4), None);
+
+        let versions = |min_editorial| {
+            let query = ListQuery {
+                min_editorial,
+                ..query()
+            };
+            parse_listing(&body, &query)
+                .unwrap()
+                .iter()
+                .map(|i| i.version.unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(versions(Some(2)), ["15.9.3", "16.4.2"]);
+        assert_eq!(versions(Some(0)).len(), 5);
+    }
+
+    #[test]
+    fn server_side_sort() {
+        let requested = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let paths = requested.clone();
+        let server = MockServer::start(move |req| {
+            paths.lock().unwrap().push(req.path.clone());
+            match req.path.split_once('?') {
+                Some(("/23_series/23.501/", _)) => Response::ok(listing_html(&[
+                    ("23501-g40.zip", "2020/03/27 14:30"),
+                    ("23501-f90.zip", "2020/03/20 10:00"),
+                ])),
+                _ => Response::not_found(),
+            }
+        });
+        let client = Client::new();
+        for (sort, param) in ListingSort::ALL
+            .into_iter()
+            .zip(["name", "namerev", "date", "daterev", "size", "sizerev"])
+        {
+            assert_eq!(param.parse::<ListingSort>().unwrap(), sort);
+            let query = ListQuery {
+                base_url: server.url("/"),
+                sort: Some(sort),
+                ..ListQuery::new(parse_spec_number("23.501").unwrap())
+            };
+            let url = query.listing_url().unwrap();
+            let sorted = sort.apply(&url);
+            assert!(sorted.as_str().starts_with(url.as_str()));
+            assert_eq!(sorted.query(), Some(format!("sortby={}", param).as_str()));
+
+            assert_eq!(list(&client, &query).unwrap().len(), 2);
+            assert_eq!(
+                requested.lock().unwrap().pop().unwrap(),
+                format!("/23_series/23.501/?sortby={}", param)
+            );
+        }
+        let err = "newest".parse::<ListingSort>().unwrap_err();
+        assert!(err.starts_with("invalid sort order 'newest'"), "{}", err);
+    }
+
+    #[test]
+    fn release_not_found_lists_available() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(listing_html(&[
+                ("23501-f90.zip", "2020/03/20 10:00"),
+                ("23501-g40.zip", "2020/03/27 14:30"),
+                ("23501-Rel-17-h00.zip", "2021/12/23 09:00"),
+                ("23501-h10.zip", "2022/03/23 09:00"),
+                ("S2-2001234.zip", "2020/02/24 09:00"),
+            ])),
+            _ => Response::not_found(),
+        });
+        let client = Client::new();
+        let query = |release| ListQuery {
+            base_url: server.url("/"),
+            release: Some(release),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+
+        let err = list(&client, &query(99)).unwrap_err();
+        assert_eq!(
+            err,
+            Error::ReleaseNotFound {
+                spec: parse_spec_number("23.501").unwrap(),
+                release: 99,
+                available: vec![15, 16, 17],
+            }
+        );
+        assert_eq!(err.kind(), "release-not-found");
+        assert_eq!(
+            err.to_string(),
+            "no items of 23.501 for release 99; available: 15, 16, 17"
+        );
+
+        let versions: Vec<_> = list(&client, &query(17))
+            .unwrap()
+            .iter()
+            .map(|i| i.version.unwrap().to_string())
+            .collect();
+        assert_eq!(versions, ["17.0.0", "17.1.0"]);
+    }
+
+    #[test]
+    fn release_token_in_filename() {
+        assert_eq!(parse_release_token("23501-Rel-16-g40.zip"), Some(16));
+        assert_eq!(parse_release_token("23501_rel-17.zip"), Some(17));
+        assert_eq!(parse_release_token("23501-g40.zip"), None);
+        assert_eq!(parse_release_token("Prel-16.zip"), None);
+        assert_eq!(
+            parse_version("23501-Rel-16-g40.zip"),
+            parse_version("23501-g40.zip")
+        );
+        assert_eq!(
+            parse_version("23501-g40_Rel-16.zip"),
+            parse_version("23501-g40.zip")
+        );
+
+        let html = listing_html(&[
+            ("23501-Rel-15-g40.zip", "2020/03/27 14:30"),
+            ("23501-g50.zip", "2020/06/12 10:02"),
+            ("23501_CRs_Rel-16.zip", "2020/06/15 08:00"),
+        ]);
+        let items = parse_listing(&html, &query()).unwrap();
+        let releases: Vec<_> = items.iter().map(|i| i.release).collect();
+        assert_eq!(releases, [Some(16), Some(15), None]);
+
+        let query = ListQuery {
+            release: Some(16),
+            ..query()
+        };
+        let urls: Vec<_> = parse_listing(&html, &query)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.url)
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/23501_CRs_Rel-16.zip",
+                "https://example.com/23501-g50.zip"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_directory() {
+        let html = listing_html(&[]);
+        assert_eq!(
+            parse_listing(&html, &query()),
+            Err(Error::EmptyDirectory(query().spec_number))
+        );
+
+        let html = listing_html(&[("23501-f00.zip", "2017/12/01 10:00")]);
+        let query = ListQuery {
+            release: Some(16),
+            ..query()
+        };
+        assert_eq!(parse_listing(&html, &query), Ok(vec![]));
+    }
+
+    #[test]
+    fn recursive_listing() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23.501" => Response::ok(listing_html_with_hrefs(&[
+                ("../", "Parent Directory", "2020/01/01 00:00"),
+                ("/23.501/nested/", "nested", "2020/03/27 14:30"),
+                ("/elsewhere/", "elsewhere", "2020/03/27 14:30"),
+                ("/23.501/23501-f00.zip", "23501-f00.zip", "2017/12/01 10:00"),
+            ])),
+            "/23.501/nested/" => Response::ok(listing_html_with_hrefs(&[(
+                "/23.501/nested/23501-g40.zip",
+                "23501-g40.zip",
+                "2020/03/27 14:30",
+            )])),
+            _ => Response::not_found(),
+        });
+        let url = reqwest::Url::parse(&server.url("/23.501")).unwrap();
+
+        let items = list_directory(&Client::new(), &url, &query(), 0).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].version.unwrap().major, 15);
+
+        let items = list_directory(&Client::new(), &url, &query(), 1).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].version.unwrap().major, 15);
+        assert_eq!(items[1].url, "/23.501/nested/23501-g40.zip");
+        assert_eq!(items[1].version.unwrap().major, 16);
+    }
+
+    #[test]
+    fn relative_duration_parse() {
+        let d = |amount, unit| Ok(RelativeDuration { amount, unit });
+        assert_eq!("30d".parse(), d(30, DurationUnit::Days));
+        assert_eq!("2w".parse(), d(2, DurationUnit::Weeks));
+        assert_eq!("6m".parse(), d(6, DurationUnit::Months));
+        assert_eq!("1y".parse(), d(1, DurationUnit::Years));
+        assert!("".parse::<RelativeDuration>().is_err());
+        assert!("30".parse::<RelativeDuration>().is_err());
+        assert!("d".parse::<RelativeDuration>().is_err());
+        assert!("-3d".parse::<RelativeDuration>().is_err());
+        assert!("3h".parse::<RelativeDuration>().is_err());
+        assert_eq!(
+            "10001y".parse::<RelativeDuration>(),
+            Err("invalid duration '10001y': out of range (at most 10000 years)".to_string())
+        );
+        assert!("4000000000m".parse::<RelativeDuration>().is_err());
+        assert!("99999999999d".parse::<RelativeDuration>().is_err());
+        assert_eq!("10000y".parse(), d(10_000, DurationUnit::Years));
+    }
+
+    #[test]
+    fn relative_duration_before() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap();
+        let before = |s: &str| s.parse::<RelativeDuration>().unwrap().before(now);
+        assert_eq!(
+            before("30d"),
+            Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).single()
+        );
+        assert_eq!(
+            before("1w"),
+            Utc.with_ymd_and_hms(2024, 3, 24, 12, 0, 0).single()
+        );
+        assert_eq!(
+            before("1m"),
+            Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).single()
+        );
+        assert_eq!(
+            before("1y"),
+            Utc.with_ymd_and_hms(2023, 3, 31, 12, 0, 0).single()
+        );
+    }
+
+    #[test]
+    fn year_month_arithmetic() {
+        let ym = |s: &str| s.parse::<YearMonth>().unwrap();
+        assert_eq!(
+            ym("2023-12"),
+            YearMonth {
+                year: 2023,
+                month: Month::December
+            }
+        );
+        assert_eq!(ym("2023-12") + 1, ym("2024-01"));
+        assert_eq!(ym("2023-11") + 26, ym("2026-01"));
+        assert_eq!(ym("2024-01") - 1, ym("2023-12"));
+        assert_eq!(ym("2024-01") - 25, ym("2021-12"));
+        assert_eq!(ym("2026-02") - 27, ym("2023-11"));
+        assert_eq!(ym("2020-06") + 120 - 120, ym("2020-06"));
+        assert_eq!((ym("2019-11") + 3).to_string(), "2020-02");
+        assert!(std::panic::catch_unwind(|| ym("0000-02") - 2).is_err());
+
+        assert_eq!(ym("2023-12").checked_add(1), Some(ym("2024-01")));
+        assert_eq!(ym("2023-11").checked_add(26), Some(ym("2026-01")));
+        assert_eq!(ym("2024-01").checked_sub(1), Some(ym("2023-12")));
+        assert_eq!(ym("2026-02").checked_sub(27), Some(ym("2023-11")));
+        assert_eq!(ym("2020-06").checked_add(0), Some(ym("2020-06")));
+        assert_eq!(
+            ym("2020-06")
+                .checked_add(120)
+                .and_then(|m| m.checked_sub(120)),
+            Some(ym("2020-06"))
+        );
+        assert!(ym("2023-12") < ym("2024-01"));
+        assert_eq!(ym("2019-11").checked_add(3).unwrap().to_string(), "2020-02");
+        // Out of range instead of overflowing.
+        assert_eq!(ym("0000-02").checked_sub(2), None);
+        assert_eq!(ym("2020-06").checked_add(u32::MAX), None);
+        let far = YearMonth {
+            year: u32::MAX,
+            month: Month::January,
+        };
+        assert_eq!(far.checked_add(1), None);
+        assert_eq!(
+            ym("2023-12").start(),
+            Some(Utc.with_ymd_and_hms(2023, 12, 1, 0, 0, 0).unwrap())
+        );
+        assert!("2023-13".parse::<YearMonth>().is_err());
+        assert!("2023-1".parse::<YearMonth>().is_err());
+    }
+
+    #[test]
+    fn since_filter() {
+        let html = listing_html(&[
+            ("23501-g30.zip", "2019/12/01 10:00"),
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-g50.zip", "2020/06/12 10:02"),
+        ]);
+        let clock = FixedClock(Utc.with_ymd_and_hms(2020, 6, 30, 0, 0, 0).unwrap());
+        let query = ListQuery {
+            since: "120d".parse::<RelativeDuration>().unwrap().cutoff(&clock),
+            ..query()
+        };
+        let items = parse_listing(&html, &query).unwrap();
+        assert_eq!(
+            items
+                .iter()
+                .map(|i| i.version.unwrap().minor)
+                .collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+    }
+
+    #[test]
+    fn series_descriptions() {
+        let description = |s: &str| parse_spec_number(s).unwrap().series_description();
+        assert_eq!(
+            description("23.501"),
+            Some("Technical realization (\"stage 2\")")
+        );
+        assert_eq!(description("33.501"), Some("Security aspects"));
+        assert_eq!(description("38.331"), Some("Radio technology beyond LTE"));
+        assert_eq!(description("26.114"), Some("CODECs"));
+        assert_eq!(description("98.001"), None);
+        assert_eq!(description("00.001"), None);
+    }
+
+    #[test]
+    fn canonical_order_independent_of_rows() {
+        let rows = [
+            ("23501-g50.zip", "2020/06/12 10:02"),
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-f90.zip", "2020/03/27 14:00"),
+            ("23501-g40.zip", "2020/03/20 09:00"),
+            ("23501-100.zip", "2017/06/01 10:00"),
+        ];
+        let mut reversed = rows;
+        reversed.reverse();
+
+        let items = parse_listing(&listing_html(&rows), &query()).unwrap();
+        let versions: Vec<_> = items
+            .iter()
+            .map(|i| {
+                (
+                    i.version.unwrap().to_string(),
+                    i.date.format("%d").to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            versions,
+            [
+                ("1.0.0", "01"),
+                ("15.9.0", "27"),
+                ("16.4.0", "20"),
+                ("16.4.0", "27"),
+                ("16.5.0", "12")
+            ]
+            .map(|(v, d)| (v.to_string(), d.to_string()))
+        );
+        assert_eq!(
+            parse_listing(&listing_html(&reversed), &query()).unwrap(),
+            items
+        );
+    }
+
+    #[test]
+    fn size_parsing() {
+        assert_eq!(parse_size("1,234,567"), Some(1_234_567));
+        assert_eq!(parse_size("1,2 MB"), Some(1_258_291));
+        assert_eq!(parse_size("340 K"), Some(348_160));
+        assert_eq!(parse_size("  79,2 KB\n"), Some(81_101));
+        assert_eq!(parse_size("1.5 MiB"), Some(1_572_864));
+        assert_eq!(parse_size("1,234 kb"), Some(1_263_616));
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("512 bytes"), Some(512));
+        assert_eq!(parse_size("garbage"), None);
+        assert_eq!(parse_size("12 parsecs"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn size_from_listing_fixture() {
+        let html = include_str!("../resources/23501.htm");
+        let items = parse_listing(html, &query()).unwrap();
+        assert_eq!(
+            items[0].url,
+            "https://www.3gpp.org/ftp/Specs/archive/23_series/23.501/23501-000.zip"
+        );
+        assert_eq!(items[0].size, Some(81_101));
+        assert!(items.iter().all(|i| i.size.is_some()));
+    }
+
+    #[test]
+    fn rows_without_tbody() {
+        let html = "<table>\
+            <thead><tr><th>name</th><th>date</th></tr></thead>\
+            <tr><td><a href=\"https://example.com/23501-g40.zip\">23501-g40.zip</a></td>\
+                <td>2020/03/27 14:30</td></tr>\
+            <tr><td><a href=\"https://example.com/23501-g50.zip\">23501-g50.zip</a></td>\
+                <td>2020/06/12 10:02</td></tr>\
+            </table>";
+        let items = parse_listing(html, &query()).unwrap();
+        assert_eq!(
+            items.iter().map(|i| i.url.as_str()).collect::<Vec<_>>(),
+            [
+                "https://example.com/23501-g40.zip",
+                "https://example.com/23501-g50.zip"
+            ]
+        );
+
+        let header_only = "<table><thead><tr><th>name</th><th>date</th></tr></thead></table>";
+        assert_eq!(
+            parse_listing(header_only, &query()),
+            Err(Error::EmptyDirectory(query().spec_number))
+        );
+    }
+
+    #[test]
+    fn gzip_encoded_listing() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23_series/23.501/" => {
+                Response::ok(include_bytes!("../resources/23501.htm.gz").to_vec())
+                    .header("Content-Encoding", "gzip")
+                    .header("Content-Type", "text/html")
+            }
+            "/23_series/23.502/" => Response::ok("not gzip").header("Content-Encoding", "gzip"),
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..query()
+        };
+        let items = list(&Client::new(), &query).unwrap();
+        let expected = parse_listing(include_str!("../resources/23501.htm"), &query).unwrap();
+        assert!(!items.is_empty());
+        assert_eq!(items, expected);
+
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..ListQuery::new(parse_spec_number("23.502").unwrap())
+        };
+        let err = list(&Client::new(), &query).unwrap_err().to_string();
+        assert!(err.contains("failed to decode listing"), "{}", err);
+    }
+
+    #[test]
+    fn list_from_fetched_body() {
+        let body = r#"<table>
+            <thead><tr><th>Name</th><th>Date</th><th>Size</th></tr></thead>
+            <tbody>
+            <tr><td><a href="23501-f10.zip">23501-f10.zip</a></td><td>2018/03/27 14:30</td><td>1 KB</td></tr>
+            <tr><td><a href="/mirror/23501-g40.zip">23501-g40.zip</a></td><td>2020/03/27 14:30</td><td>2 KB</td></tr>
+            <tr><td><a href="https://example.com/23501-g41.zip">23501-g41.zip</a></td><td>2020/06/01 09:00</td><td>3 KB</td></tr>
+            <tr><td><a href="Archive/">Archive</a></td><td>2020/06/01 09:00</td><td>-</td></tr>
+            </tbody></table>"#;
+        let url = reqwest::Url::parse("https://mirror.example/specs/23_series/23.501").unwrap();
+
+        let items = list_from_body(body, &url, &query()).unwrap();
+        let urls: Vec<_> = items.iter().map(|item| item.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://mirror.example/specs/23_series/23.501/23501-f10.zip",
+                "https://mirror.example/mirror/23501-g40.zip",
+                "https://example.com/23501-g41.zip",
+            ]
+        );
+        assert_eq!(items[0].size, Some(1024));
+
+        let release_16 = ListQuery {
+            release: Some(16),
+            ..query()
+        };
+        let items = list_from_body(body, &url, &release_16).unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(matches!(
+            list_from_body(body, &url, &ListQuery { release: Some(17), ..query() }),
+            Err(Error::ReleaseNotFound { available, .. }) if available == [15, 16]
+        ));
+
+        let dedupe = ListQuery {
+            dedupe: true,
+            ..query()
+        };
+        let twice = body.replace("23501-g41", "23501-g40");
+        assert_eq!(list_from_body(&twice, &url, &dedupe).unwrap().len(), 2);
+        let header_only = format!("{}</table>", &body[..body.find("<tbody>").unwrap()]);
+        assert!(matches!(
+            list_from_body(&header_only, &url, &query()),
+            Err(Error::EmptyDirectory(_))
+        ));
+    }
+
+    #[test]
+    fn lazy_listing() {
+        let requests = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = requests.clone();
+        let server = MockServer::start(move |req| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            match req.path.as_str() {
+                "/23_series/23.501/" => Response::ok(listing_html_with_hrefs(&[
+                    ("Versions/", "Versions", "2020/01/01 00:00"),
+                    (
+                        "https://example.com/23501-g30.zip",
+                        "23501-g30.zip",
+                        "2019/12/22 10:00",
+                    ),
+                    (
+                        "https://example.com/23501-g40.zip",
+                        "23501-g40.zip",
+                        "2020/03/27 14:30",
+                    ),
+                    (
+                        "https://example.com/23501-g50.zip",
+                        "23501-g50.zip",
+                        "2020/06/12 10:02",
+                    ),
+                ])),
+                _ => Response::not_found(),
+            }
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..query()
+        };
+        let client = Client::new();
+
+        let items = list_iter(&client, &query);
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 0);
+        let first: Vec<_> = items.take(2).collect::<Result<_, _>>().unwrap();
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let all = list(&client, &query).unwrap();
+        assert_eq!(first, all[..2]);
+
+        let query = ListQuery {
+            base_url: server.url("/missing/"),
+            ..self::query()
+        };
+        let results: Vec<_> = list_iter(&client, &query).collect();
+        assert!(matches!(
+            results[..],
+            [Err(Error::Status { status: 404, .. })]
+        ));
+
+        // Queries needing the whole listing are rejected rather than silently listed flat.
+        for query in [
+            ListQuery {
+                max_depth: 1,
+                ..self::query()
+            },
+            ListQuery {
+                dedupe: true,
+                ..self::query()
+            },
+        ] {
+            let results: Vec<_> = list_iter(&client, &query).collect();
+            assert!(
+                matches!(results[..], [Err(Error::Other(_))]),
+                "{:?}",
+                results
+            );
+        }
+    }
+
+    #[test]
+    fn get_version_direct_hit() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listed = Arc::new(AtomicUsize::new(0));
+        let listings = listed.clone();
+        let server = MockServer::start(move |req| match (req.method.as_str(), req.path.as_str()) {
+            ("HEAD", "/23_series/23.501/23501-g40.zip") => {
+                Response::ok(vec![0; 2048]).header("Last-Modified", "Fri, 27 Mar 2020 14:30:00 GMT")
+            }
+            ("GET", "/23_series/23.501/") => {
+                listings.fetch_add(1, Ordering::SeqCst);
+                Response::new(500, "listing requested")
+            }
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let client = Client::new();
+        let version = parse_version("g40").unwrap();
+
+        let item = get_version(&client, &query, &version).unwrap().unwrap();
+        assert_eq!(item.url, server.url("/23_series/23.501/23501-g40.zip"));
+        assert_eq!(item.version, Some(version));
+        assert_eq!(
+            item.date,
+            Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap()
+        );
+        assert_eq!(item.size, Some(2048));
+        assert_eq!(listed.load(Ordering::SeqCst), 0);
+
+        let filtered = ListQuery {
+            release: Some(17),
+            ..query.clone()
+        };
+        assert_eq!(get_version(&client, &filtered, &version), Ok(None));
+        assert_eq!(listed.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn get_version_falls_back_to_listing() {
+        let server = MockServer::start(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("GET", "/23_series/23.501/") => Response::ok(listing_html_with_hrefs(&[
+                ("/files/23501-g40.zip", "23501-g40.zip", "2020/03/27 14:30"),
+                ("/files/23501-g50.zip", "23501-g50.zip", "2020/06/12 10:02"),
+            ])),
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let client = Client::new();
+
+        let version = parse_version("g50").unwrap();
+        let item = get_version(&client, &query, &version).unwrap().unwrap();
+        assert_eq!(item.url, "/files/23501-g50.zip");
+        assert_eq!(item.version, Some(version));
+
+        let missing = parse_version("g60").unwrap();
+        assert_eq!(get_version(&client, &query, &missing), Ok(None));
+    }
+
+    #[test]
+    fn change_request_archive() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(listing_html_with_hrefs(&[
+                ("/23.501/23501-g40.zip", "23501-g40.zip", "2020/03/27 14:30"),
+                ("/23.501/23501_CRs.zip", "23501_CRs.zip", "2020/03/30 08:00"),
+                (
+                    "/23.501/S2-2001234.zip",
+                    "S2-2001234.zip",
+                    "2020/04/01 09:00",
+                ),
+            ])),
+            "/23.501/23501-g40.zip" => Response::ok(b"PK\x03\x04spec".to_vec()),
+            "/23.501/23501_CRs.zip" => Response::ok(b"PK\x03\x04crs".to_vec()),
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let client = Client::new();
+        let items = list(&client, &query).unwrap();
+        assert_eq!(items.len(), 3);
+
+        let crs = change_requests(&items, &query.spec_number).unwrap();
+        assert_eq!(crs.url, "/23.501/23501_CRs.zip");
+        assert!(change_requests(&items, &parse_spec_number("23.502").unwrap()).is_none());
+        assert!(is_change_requests("23501_crs.zip"));
+        assert!(!is_change_requests("23501-g40.zip"));
+
+        let dir = crate::test_support::scratch_dir("change-request-archive");
+        let spec = items.iter().find(|i| i.version.is_some()).unwrap();
+        for item in [spec, crs] {
+            let name = item.url.rsplit('/').next().unwrap();
+            crate::download_url_to_path(
+                &client,
+                &server.url(&item.url),
+                &dir.join(name),
+                &crate::DownloadOptions::default(),
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            std::fs::read(dir.join("23501-g40.zip")).unwrap(),
+            b"PK\x03\x04spec"
+        );
+        assert_eq!(
+            std::fs::read(dir.join("23501_CRs.zip")).unwrap(),
+            b"PK\x03\x04crs"
+        );
+    }
+
+    #[test]
+    fn filename_from_href_basename() {
+        let html = listing_html_with_hrefs(&[
+            (
+                "https://example.com/23_series/23.501/23501-g40.zip",
+                "23501-g4…",
+                "2020/03/27 14:30",
+            ),
+            ("23501-h10.zip#latest", "Latest", "2021/03/27 14:30"),
+            ("/download?id=9", "23501-f90.zip", "2019/12/20 10:00"),
+        ]);
+        let items = parse_listing(&html, &query()).unwrap();
+        let names: Vec<_> = items.iter().map(|item| item.filename.as_str()).collect();
+        assert_eq!(names, ["23501-f90.zip", "23501-g40.zip", "23501-h10.zip"]);
+        let versions: Vec<_> = items.iter().map(|item| item.version).collect();
+        assert_eq!(
+            versions,
+            [
+                parse_version("23501-f90.zip"),
+                parse_version("23501-g40.zip"),
+                parse_version("23501-h10.zip"),
+            ]
+        );
+        assert!(versions.iter().all(Option::is_some));
+        assert_eq!(href_filename("/download?file=23501-g40.zip"), None);
+    }
+
+    #[test]
+    fn cached_selectors_give_same_rows() {
+        // A large listing whose columns are out of order, with directories, rows without an
+        // anchor, short rows and missing sizes.
+        let mut html = String::from(
+            "<table><thead><tr><th>size</th><th>date</th><th>extra</th><th>name</th></tr>\
+             </thead><tbody>",
+        );
+        for i in 0..2000 {
+            let row = match i % 5 {
+                0 => format!(
+                    "<tr><td>{}k</td><td>2020/03/{:02} 14:30</td><td>x</td>\
+                     <td><a href=\"23501-g{}0.zip\">23501-g{}0.zip</a></td></tr>",
+                    i,
+                    i % 28 + 1,
+                    i % 10,
+                    i % 10
+                ),
+                1 => format!(
+                    "<tr><td>-</td><td>2021/01/01</td><td></td>\
+                     <td><a href=\"/dir{}/\">dir{}</a></td></tr>",
+                    i, i
+                ),
+                2 => "<tr><td>1</td><td>2020/01/01</td><td></td><td>no anchor</td></tr>".into(),
+                3 => "<tr><td>1</td><td>2020/01/01</td></tr>".into(),
+                _ => format!(
+                    "<tr><td></td><td>2019/12/20</td><td></td>\
+                     <td><a href=\"/download?id={}\">23501-f{}0.zip</a></td></tr>",
+                    i,
+                    i % 10
+                ),
+            };
+            html.push_str(&row);
+        }
+        html.push_str("</tbody></table>");
+
+        for lenient in [false, true] {
+            let rows: Vec<_> = listing_rows(&html, &query().spec_number, lenient)
+                .unwrap()
+                .into_iter()
+                .map(|row| (row.name, row.href, row.date, row.size))
+                .collect();
+            assert_eq!(rows.len(), 1200);
+            assert_eq!(rows, reference_rows(&html, lenient));
+        }
+    }
+
+    #[test]
+    fn row_header_cells() {
+        // Each data row starts with a `<th>` row header, and the header row has a sorting link.
+        let html = "<table><thead><tr><th><a href=\"?C=N\">name</a></th><th>date</th>\
+                    <th>size</th></tr></thead><tbody>\
+                    <tr><th><a href=\"/a/23501-g40.zip\">23501-g40.zip</a></th>\
+                    <td>2020/03/27 14:30</td><td>1k</td></tr>\
+                    <tr><th><a href=\"/a/23501-h10.zip\">23501-h10.zip</a></th>\
+                    <td>2021/03/27 14:30</td><td>2k</td></tr>\
+                    </tbody></table>";
+        let expected = [
+            ("23501-g40.zip", "2020/03/27 14:30", Some(1024)),
+            ("23501-h10.zip", "2021/03/27 14:30", Some(2048)),
+        ];
+        // Without a `<thead>`, the header row lands in the `<tbody>` and must not be a data row.
+        let bare = html.replace("<thead>", "").replace("</thead><tbody>", "");
+        for (html, lenient) in [(html, false), (html, true), (bare.as_str(), true)] {
+            let rows: Vec<_> = listing_rows(html, &query().spec_number, lenient)
+                .unwrap()
+                .into_iter()
+                .map(|row| (row.name, row.date, row.size))
+                .collect();
+            let rows: Vec<_> = rows
+                .iter()
+                .map(|(name, date, size)| (name.as_str(), date.as_str(), *size))
+                .collect();
+            assert_eq!(rows, expected, "lenient: {}", lenient);
+        }
+
+        let items = parse_listing(html, &query()).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].filename, "23501-h10.zip");
+    }
+
+    #[test]
+    fn percent_encoded_hrefs() {
+        let html = listing_html_with_hrefs(&[
+            (
+                "https://example.com/23501-g40%20Rel-16.zip",
+                "23501-g40 Rel-16.zip",
+                "2020/03/27 14:30",
+            ),
+            ("23501-h10%2Ezip", "23501-h10.zip", "2021/03/27 14:30"),
+        ]);
+        let items = parse_listing(&html, &query()).unwrap();
+        assert_eq!(items[0].filename, "23501-g40 Rel-16.zip");
+        assert_eq!(items[0].version, parse_version("23501-g40.zip"));
+        assert_eq!(items[0].release, Some(16));
+        assert_eq!(items[0].url, "https://example.com/23501-g40%20Rel-16.zip");
+        assert_eq!(items[1].filename, "23501-h10.zip");
+        assert_eq!(items[1].version, parse_version("23501-h10.zip"));
+
+        // Malformed escapes are kept as written.
+        assert_eq!(decode_segment("23501%2-g40%zz.zip%"), "23501%2-g40%zz.zip%");
+        assert_eq!(decode_segment("23501%FF.zip"), "23501%FF.zip");
+        assert_eq!(decode_segment("%C3%A9t%C3%A9.zip"), "été.zip");
+        // Decoded separators would leave the output directory.
+        assert_eq!(decode_segment("..%2Fetc%2Fpasswd"), "..%2Fetc%2Fpasswd");
+        assert_eq!(decode_segment("a%5Cb"), "a%5Cb");
+        assert_eq!(decode_segment("%2E%2E"), "%2E%2E");
+    }
+
+    #[test]
+    fn filename_from_listing() {
+        let html = listing_html_with_hrefs(&[
+            ("/download?id=7", "23501-g40.zip", "2020/03/27 14:30"),
+            (
+                "https://example.com/23501-g50.zip",
+                "23501-g50.zip",
+                "2020/06/12 10:02",
+            ),
+        ]);
+        let items = parse_listing(&html, &query()).unwrap();
+        assert_eq!(items[0].filename, "23501-g40.zip");
+        assert_eq!(items[0].filename(), "23501-g40.zip");
+        assert_eq!(items[0].url, "/download?id=7");
+        assert_eq!(items[1].filename(), "23501-g50.zip");
+
+        let json = serde_json::to_value(&items[0]).unwrap();
+        assert_eq!(json["filename"], "23501-g40.zip");
+
+        // Listings saved without filenames fall back to the URL.
+        let mut json = serde_json::to_value(&items[1]).unwrap();
+        json.as_object_mut().unwrap().remove("filename");
+        let saved: SpecItem = serde_json::from_value(json).unwrap();
+        assert_eq!(saved.filename, "");
+        assert_eq!(saved.filename(), "23501-g50.zip");
+    }
+
+    #[test]
+    fn item_age() {
+        let html = listing_html(&[("23501-g40.zip", "2020/03/27 14:30")]);
+        let item = &parse_listing(&html, &query()).unwrap()[0];
+        let now = Utc.with_ymd_and_hms(2020, 3, 30, 16, 0, 0).unwrap();
+        assert_eq!(
+            item.age(now),
+            chrono::Duration::days(3) + chrono::Duration::minutes(90)
+        );
+        assert_eq!(item.age(item.date), chrono::Duration::zero());
+        let before = Utc.with_ymd_and_hms(2020, 3, 27, 14, 0, 0).unwrap();
+        assert_eq!(item.age(before), -chrono::Duration::minutes(30));
+    }
+
+    #[test]
+    fn unversioned_rows() {
+        let html = listing_html(&[
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("S2-2001234.zip", "2020/02/24 09:00"),
+            ("23501-g50.zip", "2020/06/12 10:02"),
+            ("draft_cover_letter.doc", "2020/06/12 10:02"),
+        ]);
+        let items = parse_listing(&html, &query()).unwrap();
+        let names: Vec<_> = items
+            .iter()
+            .map(|i| (i.version.map(|v| v.to_string()), i.draft))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (None, false),
+                (None, true),
+                (Some("16.4.0".to_string()), false),
+                (Some("16.5.0".to_string()), false)
+            ]
+        );
+        assert_eq!(items[0].url, "https://example.com/S2-2001234.zip");
+        assert!(
+            items[0]
+                .to_string()
+                .starts_with("23.501 unversioned @ 2020-02-24")
+        );
+
+        let query = ListQuery {
+            release: Some(16),
+            include_drafts: false,
+            ..query()
+        };
+        let items = parse_listing(&html, &query).unwrap();
+        assert!(items.iter().all(|i| i.version.is_some()));
+        assert_eq!(items.len(), 2);
+
+        let mut items = parse_listing(&html, &self::query()).unwrap();
+        assert!(dedupe_versions(&mut items).is_empty());
+    }
+
+    #[test]
+    fn list_from_mirror_base_url() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/mirror/23_series/23.501/" => {
+                Response::ok(listing_html(&[("23501-g40.zip", "2020/03/27 14:30")]))
+            }
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/mirror"),
+            ..query()
+        };
+        let items = list(&Client::new(), &query).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].version.unwrap().minor, 4);
+    }
+
+    #[test]
+    fn client_reused_across_listing_and_downloads() {
+        let server = MockServer::start_keep_alive(|req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(listing_html_with_hrefs(&[
+                ("/23501-g40.zip", "23501-g40.zip", "2020/03/27 14:30"),
+                ("/23501-g50.zip", "23501-g50.zip", "2020/06/12 10:02"),
+            ])),
+            "/23501-g40.zip" | "/23501-g50.zip" => Response::ok(b"PK\x03\x04spec".to_vec()),
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..query()
+        };
+        let client = Client::new();
+        let items = list(&client, &query).unwrap();
+        assert_eq!(items.len(), 2);
+        let dir = crate::test_support::scratch_dir("client-reuse");
+        for item in &items {
+            let url = server.url(&item.url);
+            let dest = dir.join(item.url.trim_start_matches('/'));
+            crate::download_url_to_path(&client, &url, &dest, &Default::default()).unwrap();
+        }
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[test]
+    fn version_codes() {
+        let v = |major, minor, editorial| Version {
+            major,
+            minor,
+            editorial,
+        };
+        assert_eq!(v(16, 4, 0).to_short_code(), Ok("g40".to_string()));
+        assert_eq!(v(16, 4, 0).to_long_code(), Ok("160400".to_string()));
+        assert_eq!(v(1, 0, 0).to_long_code(), Ok("010000".to_string()));
+
+        for version in [
+            v(0, 1, 0),
+            v(15, 9, 0),
+            v(16, 4, 0),
+            v(18, 10, 35),
+            v(35, 35, 35),
+        ] {
+            let short = version.to_short_code().unwrap();
+            assert_eq!(parse_version(&short), Some(version), "{}", short);
+            let long = version.to_long_code().unwrap();
+            assert_eq!(parse_version(&long), Some(version), "{}", long);
+            assert_eq!(parse_version(&format!("23501-{}.zip", long)), Some(version));
+        }
+        for version in [v(40, 0, 0), v(99, 50, 99)] {
+            let long = version.to_long_code().unwrap();
+            assert_eq!(parse_version(&long), Some(version), "{}", long);
+        }
+
+        let err = v(17, 36, 0).to_short_code().unwrap_err();
+        assert!(err.contains("36 > 35"), "{}", err);
+        let err = v(100, 0, 0).to_long_code().unwrap_err();
+        assert!(err.contains("100 > 99"), "{}", err);
+    }
+
+    #[test]
+    fn packed_versions() {
+        let v = |major, minor, editorial| Version {
+            major,
+            minor,
+            editorial,
+        };
+        let versions = [
+            v(0, 0, 0),
+            v(0, 1, 0),
+            v(1, 0, 0),
+            v(15, 9, 0),
+            v(16, 4, 0),
+            v(16, 4, 1),
+            v(16, 65535, 65535),
+            v(17, 0, 0),
+            v(u32::MAX, 65535, 65535),
+        ];
+        assert_eq!(v(16, 4, 1).to_packed(), Ok(0x10_0004_0001));
+        let packed: Vec<u64> = versions.iter().map(|v| v.to_packed().unwrap()).collect();
+        for (version, &p) in versions.iter().zip(&packed) {
+            assert_eq!(Version::from_packed(p), *version);
+        }
+        for (a, b) in versions.iter().zip(&versions[1..]) {
+            assert!(a < b);
+        }
+        assert!(packed.windows(2).all(|w| w[0] < w[1]));
+
+        let err = v(16, 65536, 0).to_packed().unwrap_err();
+        assert!(err.contains("65536 > 65535"), "{}", err);
+        assert!(v(16, 0, 70000).to_packed().is_err());
+    }
+
+    #[test]
+    fn listing_urls() {
+        let url = |base: &str, spec: &str| {
+            let base = reqwest::Url::parse(base).unwrap();
+            listing_url(&base, &parse_spec_number(spec).unwrap()).map(|u| u.to_string())
+        };
+        let official = "https://www.3gpp.org/ftp/Specs/archive/";
+        assert_eq!(
+            url(official, "23.501"),
+            Ok(format!("{}23_series/23.501/", official))
+        );
+        assert_eq!(
+            url(official, "38331"),
+            Ok(format!("{}38_series/38.331/", official))
+        );
+        assert_eq!(
+            url(official, "21.905"),
+            Ok(format!("{}21_series/21.905/", official))
+        );
+        assert_eq!(
+            url(official, "36.10A"),
+            Ok(format!("{}36_series/36.10A/", official))
+        );
+        assert_eq!(
+            url("https://mirror.example.com/archive", "23.501"),
+            Ok("https://mirror.example.com/archive/23_series/23.501/".to_string())
+        );
+        assert_eq!(
+            url("ftp://ftp.example.com/Specs/archive/", "29.500"),
+            Ok("ftp://ftp.example.com/Specs/archive/29_series/29.500/".to_string())
+        );
+    }
+
+    #[test]
+    fn listing_url_resolves_relative_hrefs() {
+        for base in [
+            "https://mirror.example.com/archive",
+            "https://mirror.example.com/archive/",
+        ] {
+            let query = ListQuery {
+                base_url: base.to_string(),
+                ..query()
+            };
+            let url = query.listing_url().unwrap();
+            assert_eq!(
+                url.as_str(),
+                "https://mirror.example.com/archive/23_series/23.501/"
+            );
+            assert_eq!(
+                url.join("23501-g40.zip").unwrap().as_str(),
+                "https://mirror.example.com/archive/23_series/23.501/23501-g40.zip"
+            );
+            assert_eq!(
+                url.join("Versions/").unwrap().as_str(),
+                "https://mirror.example.com/archive/23_series/23.501/Versions/"
+            );
+
+            let html =
+                listing_html_with_hrefs(&[("23501-g40.zip", "23501-g40.zip", "2020/03/27 14:30")]);
+            let items = list_from_body(&html, &url, &query).unwrap();
+            assert_eq!(
+                items[0].url,
+                "https://mirror.example.com/archive/23_series/23.501/23501-g40.zip"
+            );
+        }
+    }
+
+    #[test]
+    fn path_templates() {
+        let base = reqwest::Url::parse("https://mirror.example.com/archive/").unwrap();
+        let spec = parse_spec_number("23.501").unwrap();
+        let url = |template: &str| {
+            let template: PathTemplate = template.parse().unwrap();
+            listing_url_with(&base, &spec, &template).map(|u| u.to_string())
+        };
+        assert_eq!(PathTemplate::default().render(&spec), "23_series/23.501");
+        assert_eq!(
+            url("{series}_series/{series}.{number}"),
+            Ok("https://mirror.example.com/archive/23_series/23.501/".to_string())
+        );
+        assert_eq!(
+            url("{series}_Series/{series}{number}/"),
+            Ok("https://mirror.example.com/archive/23_Series/23501/".to_string())
+        );
+        assert_eq!(
+            url("specs/{number}"),
+            Ok("https://mirror.example.com/archive/specs/501/".to_string())
+        );
+        assert!(matches!(
+            url("../{series}/{number}"),
+            Err(Error::SecurityCheck { .. })
+        ));
+        assert!(matches!(url("/{series}"), Err(Error::SecurityCheck { .. })));
+
+        let err = "{series}/{spec}".parse::<PathTemplate>().unwrap_err();
+        assert_eq!(
+            err,
+            "unknown placeholder '{spec}' in path template '{series}/{spec}': must be {series} or {number}"
+        );
+        assert!("{series".parse::<PathTemplate>().is_err());
+    }
+
+    #[test]
+    fn path_traversal_rejected() {
+        let err = parse_spec_number("23.501/../../etc").unwrap_err();
+        assert_eq!(
+            err,
+            "invalid spec_number '23.501/../../etc': must not contain path separators or '..'"
+        );
+        assert!(parse_spec_number("23..501").is_err());
+        assert!(parse_spec_number("23\\501").is_err());
+
+        let base = reqwest::Url::parse("https://www.3gpp.org/ftp/Specs/archive/").unwrap();
+        let spec = SpecNumber {
+            series: "23".to_string(),
+            number: "501/../../../../etc".to_string(),
+        };
+        let err = listing_url(&base, &spec).unwrap_err();
+        assert!(matches!(err, Error::SecurityCheck { .. }), "{:?}", err);
+        assert_eq!(err.kind(), "security-check");
+        let msg = err.to_string();
+        assert!(msg.contains("'https://www.3gpp.org/ftp/etc'"), "{}", msg);
+        assert!(msg.contains("its path escapes the base path"), "{}", msg);
+        assert!(msg.contains("invalid path characters"), "{}", msg);
+
+        let err = Error::SecurityCheck {
+            attempted_url: "https://evil.example.com/23_series/23.501".to_string(),
+            base: base.to_string(),
+        };
+        assert!(err.to_string().contains("different scheme, host or port"));
+    }
+
+    #[test]
+    fn date_formats() {
+        let item = SpecItem {
+            spec: parse_spec_number("23.501").unwrap(),
+            version: Some(Version {
+                major: 16,
+                minor: 4,
+                editorial: 0,
+            }),
+            release: None,
+            date: Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap(),
+            url: "https://example.com/23501-g40.zip".to_string(),
+            filename: String::new(),
+            draft: false,
+            size: None,
+        };
+        assert_eq!(
+            item.to_string_with(&DateFormat::date_only()),
+            "23.501 16. 4. 0 @ 2020-03-27 (https://example.com/23501-g40.zip)"
+        );
+        let iso: DateFormat = "%Y-%m-%dT%H:%M:%SZ".parse().unwrap();
+        assert_eq!(
+            item.to_string_with(&iso),
+            "23.501 16. 4. 0 @ 2020-03-27T14:30:00Z (https://example.com/23501-g40.zip)"
+        );
+        assert_eq!(
+            item.to_string(),
+            "23.501 16. 4. 0 @ 2020-03-27T14:30:00+00:00 (https://example.com/23501-g40.zip)"
+        );
+        let unversioned = SpecItem {
+            version: None,
+            ..item
+        };
+        let dmy: DateFormat = "%d/%m/%Y %H:%M".parse().unwrap();
+        assert_eq!(
+            unversioned.to_string_with(&dmy),
+            "23.501 unversioned @ 27/03/2020 14:30 (https://example.com/23501-g40.zip)"
+        );
+
+        for invalid in ["%Y-%Q", "%Y-%", ""] {
+            let err = invalid.parse::<DateFormat>().unwrap_err();
+            assert!(err.starts_with("invalid date format"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn hash_sets_collapse_duplicates() {
+        use std::collections::{HashMap, HashSet};
+
+        let html = listing_html(&[
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-g41.zip", "2020/06/01 09:00"),
+        ]);
+        let items = parse_listing(&html, &query()).unwrap();
+        let twice: HashSet<SpecItem> = items.iter().chain(&items).cloned().collect();
+        assert_eq!(twice.len(), 2);
+
+        let versions: HashSet<Version> = items
+            .iter()
+            .chain(&items)
+            .filter_map(|item| item.version)
+            .collect();
+        assert_eq!(versions.len(), 2);
+        let by_version: HashMap<Version, SpecItem> = items
+            .into_iter()
+            .map(|item| (item.version.unwrap(), item))
+            .collect();
+        let g41 = Version {
+            major: 16,
+            minor: 4,
+            editorial: 1,
+        };
+        assert_eq!(by_version[&g41].filename, "23501-g41.zip");
+    }
+
+    #[test]
+    fn dedupe_same_version() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(listing_html_with_hrefs(&[
+                ("/23.501/23501-g40.zip", "23501-g40.zip", "2020/03/27 14:30"),
+                ("latest/", "latest", "2020/06/12 10:02"),
+                ("/23.501/23501-f90.zip", "23501-f90.zip", "2020/03/20 10:00"),
+            ])),
+            "/23_series/23.501/latest/" => Response::ok(listing_html_with_hrefs(&[(
+                "/23.501/latest/23501-g40.zip",
+                "23501-g40.zip",
+                "2020/03/27 14:30",
+            )])),
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            max_depth: 1,
+            ..query()
+        };
+        let items = list(&Client::new(), &query).unwrap();
+        assert_eq!(items.len(), 3);
+
+        let query = ListQuery {
+            dedupe: true,
+            ..query
+        };
+        let items = list(&Client::new(), &query).unwrap();
+        assert_eq!(
+            items.iter().map(|i| i.url.as_str()).collect::<Vec<_>>(),
+            ["/23.501/23501-f90.zip", "/23.501/23501-g40.zip"]
+        );
+    }
+
+    #[test]
+    fn dedupe_keeps_newest() {
+        let html = listing_html(&[
+            ("23501-g40.zip", "2020/03/27 14:30"),
+            ("23501-g40.zip", "2020/04/01 09:00"),
+        ]);
+        let mut items = parse_listing(&html, &query()).unwrap();
+        let dropped = dedupe_versions(&mut items);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].date.format("%m-%d").to_string(), "04-01");
+        assert_eq!(dropped[0].date.format("%m-%d").to_string(), "03-27");
+    }
+
+    #[test]
+    fn dedupe_merged_sources() {
+        // The same versions of 23.501 served by two mirrors, and a version of 23.502.
+        let listing = |spec: &'static str, files: &'static [(&'static str, &'static str)]| {
+            MockServer::start(
+                move |req| match req.path == format!("/23_series/{}/", spec) {
+                    true => Response::ok(listing_html(files)),
+                    false => Response::not_found(),
+                },
+            )
+        };
+        let first = listing(
+            "23.501",
+            &[
+                ("23501-g40.zip", "2020/03/27 14:30"),
+                ("23501-g50.zip", "2020/06/12 10:02"),
+            ],
+        );
+        let second = listing("23.501", &[("23501-g40.zip", "2020/03/27 14:30")]);
+        let other = listing("23.502", &[("23502-g40.zip", "2020/03/27 14:30")]);
+        let mut items = Vec::new();
+        for (server, spec) in [(&first, "23.501"), (&second, "23501"), (&other, "23.502")] {
+            let query = ListQuery {
+                base_url: server.url("/"),
+                ..ListQuery::new(parse_spec_number(spec).unwrap())
+            };
+            items.extend(list(&Client::new(), &query).unwrap());
+        }
+        assert_eq!(items.len(), 4);
+
+        let dropped = dedupe_across_specs(&mut items);
+        let kept: Vec<_> = items
+            .iter()
+            .map(|item| format!("{} {}", item.spec, item.filename))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                "23.501 23501-g40.zip",
+                "23.501 23501-g50.zip",
+                "23.502 23502-g40.zip"
+            ]
+        );
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].filename, "23501-g40.zip");
+
+        // Unlike `dedupe_versions`, the same version of different specs is kept.
+        let mut versions = items.clone();
+        assert_eq!(dedupe_versions(&mut versions).len(), 1);
+    }
+
+    #[test]
+    fn versions_subdirectory() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(listing_html_with_hrefs(&[
+                ("../", "Parent Directory", "2020/01/01 00:00"),
+                ("Versions/", "Versions", "2020/06/12 10:02"),
+            ])),
+            "/23_series/23.501/Versions/" => Response::ok(listing_html(&[
+                ("23501-g40.zip", "2020/03/27 14:30"),
+                ("23501-g50.zip", "2020/06/12 10:02"),
+            ])),
+            "/23_series/23.502/" => Response::ok(listing_html_with_hrefs(&[(
+                "other/",
+                "other",
+                "2020/06/12 10:02",
+            )])),
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..query()
+        };
+        let items = list(&Client::new(), &query).unwrap();
+        assert_eq!(
+            items
+                .iter()
+                .map(|i| i.version.unwrap().to_string())
+                .collect::<Vec<_>>(),
+            ["16.4.0", "16.5.0"]
+        );
+
+        let query = ListQuery::new(parse_spec_number("23.502").unwrap());
+        let query = ListQuery {
+            base_url: server.url("/"),
+            ..query
+        };
+        assert_eq!(list(&Client::new(), &query), Ok(vec![]));
+    }
+
+    #[test]
+    fn strict_series_validation() {
+        assert_eq!(
+            parse_spec_number_strict("38.331"),
+            Ok(SpecNumber {
+                series: "38".to_string(),
+                number: "331".to_string()
+            })
+        );
+
+        let err = parse_spec_number_strict("98.001").unwrap_err();
+        assert!(err.contains("unknown series '98'"), "{}", err);
+        assert!(err.contains("21, 22, 23"), "{}", err);
+        assert!(parse_spec_number_strict("9.001").is_err());
+
+        assert!(parse_spec_number("98.001").is_ok());
+    }
+
+    #[test]
+    fn unreachable_base_url() {
+        // A port nothing listens on: bound, then released.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let query = ListQuery {
+            base_url: format!("http://127.0.0.1:{}/", port),
+            ..query()
+        };
+        let err = list(&Client::new(), &query).unwrap_err();
+        assert!(matches!(err, Error::Connect { .. }), "{:?}", err);
+        assert_eq!(err.kind(), "connect");
+        let url = format!("http://127.0.0.1:{}/23_series/23.501/", port);
+        assert_eq!(err.url(), Some(url.as_str()));
+        let message = err.to_string();
+        assert!(
+            message.starts_with(&format!(
+                "cannot connect to '127.0.0.1' to fetch '{}': ",
+                url
+            )),
+            "{}",
+            message
+        );
+        assert!(
+            message.ends_with("check the network connection, the proxy settings and the base URL"),
+            "{}",
+            message
+        );
+        let source = std::error::Error::source(&err).expect("connection error as source");
+        assert!(
+            source
+                .downcast_ref::<reqwest::Error>()
+                .unwrap()
+                .is_connect()
+        );
+        assert_eq!(err.clone(), err);
     }
 }
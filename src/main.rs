@@ -1,84 +1,2732 @@
-use clap::Parser;
-use get_3gpp_spec::{SpecNumber, DateFilter};
-use std::fs::File;
-use std::io::copy;
+use clap::{CommandFactory, Parser, ValueEnum};
+use get_3gpp_spec::{
+    BatchSummary, Checkpoint, ClientOptions, Clock, CookieJar, DEFAULT_CACHE_TTL, DateFormat,
+    DownloadOptions, Error, Explanation, ListQuery, ListingCache, ListingSort, Manifest,
+    ManifestEntry, MirrorKind, PartialVersion, PathTemplate, RelativeDuration, SharedClock,
+    SidecarCheck, SourceTz, SpecItem, SpecRef, YearMonth,
+};
+use std::io::{BufRead, IsTerminal};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-fn download_url_to_path(url: &str, dest: &Path) -> Result<PathBuf, String> {
-    let resp = reqwest::blocking::get(url)
-        .map_err(|e| format!("request failed for '{}': {}", url, e))?;
+mod batch;
+mod completions;
+mod config;
 
-    if !resp.status().is_success() {
-        return Err(format!("failed to download '{}': status {}", url, resp.status()));
+/// Exit status when `--fail-on-empty` is given and no spec matches the filters.
+const EXIT_EMPTY: u8 = 3;
+
+/// Exit status when the `--timeout-total` deadline passes before the run completes.
+const EXIT_TIMEOUT: u8 = 4;
+
+/// Environment variable naming the timezone of listing dates when `--source-tz` is not given.
+const SOURCE_TZ_VAR: &str = "GET_3GPP_SOURCE_TZ";
+
+/// Environment variable naming the directory to save downloads in when `--output-dir` is not
+/// given.
+const OUTPUT_DIR_VAR: &str = "GET_3GPP_OUTPUT_DIR";
+
+/// Output format of `--list`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Atom,
+    Json,
+    Yaml,
+}
+
+/// Logger printing this crate's log records to stderr, e.g. warnings about dropped duplicates.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("get_3gpp_spec")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{}: {}",
+                record.level().as_str().to_lowercase(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Which of the matching items to download, for `--select`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Selection {
+    /// The highest version of each spec
+    Latest,
+    /// The lowest version of each spec
+    Oldest,
+    /// Every matching item
+    All,
+}
+
+/// Order of the listing printed with `--list`, `--format json` or `yaml`, for `--sort`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    /// By spec, then ascending version
+    Version,
+    /// By ascending advertised size, items of unknown size first
+    Size,
+}
+
+/// Grouping of the listing printed with `--list` or `--format json`, for `--group-by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GroupBy {
+    /// By release, the newest first (see `SpecItem::effective_release`)
+    Release,
+}
+
+/// How failures are printed to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+/// When to color terminal output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output to a stream that is a terminal if `is_terminal`. `auto` colors
+    /// terminals only, and not at all if the `NO_COLOR` environment variable (`no_color`) is set
+    /// to a non-empty value.
+    fn enabled(self, is_terminal: bool, no_color: Option<std::ffi::OsString>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && no_color.is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+/// ANSI escape sequences for colored output.
+const GREEN_BOLD: &str = "\x1b[1;32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// How long ago something of age `age` happened, in the largest whole unit, e.g. "3 days ago",
+/// for `--show-age`.
+fn humanize_age(age: chrono::Duration) -> String {
+    let (count, unit) = match age {
+        age if age < chrono::Duration::zero() => return "in the future".to_string(),
+        age if age.num_minutes() < 1 => return "just now".to_string(),
+        age if age.num_hours() < 1 => (age.num_minutes(), "minute"),
+        age if age.num_days() < 1 => (age.num_hours(), "hour"),
+        age if age.num_days() < 60 => (age.num_days(), "day"),
+        age if age.num_days() < 730 => (age.num_days() / 30, "month"),
+        age => (age.num_days() / 365, "year"),
+    };
+    match count {
+        1 => format!("1 {} ago", unit),
+        _ => format!("{} {}s ago", count, unit),
+    }
+}
+
+/// Line of `--list` text output for `item` with its date in `dates`, followed by its age at
+/// `now` if given, and its version highlighted if `newest` and `color`. A pre-release version
+/// (see `Version::is_prerelease`) is marked with a trailing `*`.
+fn list_line(
+    item: &SpecItem,
+    dates: &DateFormat,
+    now: Option<chrono::DateTime<chrono::Utc>>,
+    newest: bool,
+    color: bool,
+) -> String {
+    let mut version = item.version_label();
+    if item.version.is_some_and(|v| v.is_prerelease()) {
+        version.push('*');
     }
+    if newest && color {
+        version = format!("{}{}{}", GREEN_BOLD, version, RESET);
+    }
+    let mut date = dates.format(&item.date);
+    if let Some(now) = now {
+        date = format!("{}, {}", date, humanize_age(item.age(now)));
+    }
+    format!("{} {} @ {} ({})", item.spec, version, date, item.url)
+}
 
-    let content = resp
-        .bytes()
-        .map_err(|e| format!("failed to read response body for '{}': {}", url, e))?;
+/// `items` as a JSON value for `--format json` and `yaml`, with their dates written in `dates`
+/// instead of RFC 3339 if given.
+fn dated_listing(
+    items: &[SpecItem],
+    dates: Option<&DateFormat>,
+) -> Result<serde_json::Value, serde_json::Error> {
+    let mut value = serde_json::to_value(items)?;
+    if let (Some(dates), Some(values)) = (dates, value.as_array_mut()) {
+        for (item, value) in items.iter().zip(values) {
+            value["date"] = dates.format(&item.date).into();
+        }
+    }
+    Ok(value)
+}
+
+/// `items` grouped by release for `--group-by release`: the releases from newest to oldest,
+/// then the items that belong to none, each group sorted by version.
+fn by_release(items: &[SpecItem]) -> Vec<(Option<u32>, Vec<&SpecItem>)> {
+    let mut groups: Vec<(Option<u32>, Vec<&SpecItem>)> = Vec::new();
+    for item in items {
+        let release = item.effective_release();
+        match groups.iter_mut().find(|(r, _)| *r == release) {
+            Some((_, group)) => group.push(item),
+            None => groups.push((release, vec![item])),
+        }
+    }
+    // `None` sorts below every release, so it comes last.
+    groups.sort_by(|(a, _), (b, _)| b.cmp(a));
+    for (_, group) in &mut groups {
+        group.sort_by_key(|item| item.version);
+    }
+    groups
+}
 
-    let mut file = File::create(dest)
-        .map_err(|e| format!("failed to create file '{}': {}", dest.display(), e))?;
+/// Name of the `--group-by release` group of `release`: its number, or `unversioned` for items
+/// that belong to no release.
+fn release_group_name(release: Option<u32>) -> String {
+    release.map_or_else(|| "unversioned".to_string(), |release| release.to_string())
+}
+
+/// `--format json` listing grouped by release: an object keyed by release, serialized in the
+/// order of `by_release` rather than sorted by key as a `serde_json::Value` object would be.
+struct ReleaseGroups(Vec<(String, serde_json::Value)>);
+
+impl ReleaseGroups {
+    fn new(items: &[SpecItem], dates: Option<&DateFormat>) -> Result<Self, serde_json::Error> {
+        by_release(items)
+            .into_iter()
+            .map(|(release, group)| {
+                let group: Vec<SpecItem> = group.into_iter().cloned().collect();
+                Ok((release_group_name(release), dated_listing(&group, dates)?))
+            })
+            .collect::<Result<_, _>>()
+            .map(ReleaseGroups)
+    }
+}
+
+impl serde::Serialize for ReleaseGroups {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_map(self.0.iter().map(|(release, items)| (release, items)))
+    }
+}
+
+/// Text listing grouped with `by_release`, each item written with `line` below the heading of
+/// its release, and groups separated by a blank line.
+fn grouped_lines(items: &[SpecItem], line: impl Fn(&SpecItem) -> String) -> String {
+    let groups: Vec<String> = by_release(items)
+        .into_iter()
+        .map(|(release, group)| {
+            let heading = match release {
+                Some(release) => format!("Release {}:\n", release),
+                None => "Unversioned:\n".to_string(),
+            };
+            group
+                .into_iter()
+                .fold(heading, |text, item| text + &line(item) + "\n")
+        })
+        .collect();
+    groups.join("\n")
+}
+
+/// Failure printed to stderr by `report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Report {
+    /// Machine-readable category, e.g. `"status"` (see `Error::kind`).
+    kind: &'static str,
+    message: String,
+    /// Spec the failure is about, when several specs were requested.
+    spec: Option<String>,
+    url: Option<String>,
+    status: Option<u16>,
+}
+
+impl Report {
+    fn new(kind: &'static str, message: impl ToString) -> Self {
+        Report {
+            kind,
+            message: message.to_string(),
+            spec: None,
+            url: None,
+            status: None,
+        }
+    }
 
-    copy(&mut content.as_ref(), &mut file)
-        .map_err(|e| format!("failed to write to '{}': {}", dest.display(), e))?;
+    fn from_error(e: &Error) -> Self {
+        Report {
+            url: e.url().map(|u| u.to_string()),
+            status: e.status(),
+            ..Report::new(e.kind(), e)
+        }
+    }
 
-    Ok(dest.to_path_buf())
+    /// Render as a line of text, or as a single-line JSON object with `kind`, `message` and, if
+    /// known, `spec`, `url` and `status`.
+    fn render(&self, format: ErrorFormat) -> String {
+        match format {
+            ErrorFormat::Text => match &self.spec {
+                Some(spec) => format!("{}: {}", spec, self.message),
+                None => self.message.clone(),
+            },
+            ErrorFormat::Json => {
+                let mut object = serde_json::Map::new();
+                object.insert("kind".into(), self.kind.into());
+                object.insert("message".into(), self.message.clone().into());
+                if let Some(spec) = &self.spec {
+                    object.insert("spec".into(), spec.clone().into());
+                }
+                if let Some(url) = &self.url {
+                    object.insert("url".into(), url.clone().into());
+                }
+                if let Some(status) = self.status {
+                    object.insert("status".into(), status.into());
+                }
+                serde_json::Value::Object(object).to_string()
+            }
+        }
+    }
 }
 
 /// Simple CLI for fetching 3GPP spec info
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// 3GPP spec number (positional)
-    spec_number: SpecNumber,
+    /// 3GPP spec numbers (positional), optionally with a release, e.g. "23.501 Rel-16", or aliases from the config file; `-` reads them from stdin, one per line
+    #[arg(
+        value_name = "SPEC_NUMBER",
+        required_unless_present_any = ["generate_completions", "spec_file", "spec_number_file"]
+    )]
+    spec_numbers: Vec<String>,
 
     /// Date string (optional) — format must be YYYY-MM
     #[arg(short, long)]
-    date: Option<DateFilter>,
+    date: Option<YearMonth>,
 
-    /// Release number (nonnegative integer)
-    #[arg(short, long, value_parser = clap::value_parser!(u32))]
+    /// Release number or name, e.g. 16, Rel-16, "Release 16" or R16; overrides a release given with the spec number
+    #[arg(short, long, value_name = "RELEASE", value_parser = get_3gpp_spec::parse_release)]
     release: Option<u32>,
 
+    /// Only specs dated within this duration before now, e.g. 30d, 6m or 1y
+    #[arg(long, value_name = "DURATION")]
+    since: Option<RelativeDuration>,
+
+    /// Only download items dated after the start of this day (UTC), reporting the others as skipped, e.g. 2024-01-31
+    #[arg(long, value_name = "YYYY-MM-DD", value_parser = parse_day)]
+    newer_than: Option<chrono::NaiveDate>,
+
+    /// Archive root to list specs from instead of the 3GPP site, e.g. a mirror
+    #[arg(long, value_name = "URL")]
+    base_url: Option<String>,
+
+    /// Proxy URL (http, https, socks5 or socks5h) for all requests; defaults to the HTTP(S)_PROXY environment
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Skip TLS certificate verification (unsafe; for mirrors with self-signed certificates)
+    #[arg(long, default_value_t = false)]
+    insecure: bool,
+
+    /// Trust the CA certificate in this PEM file, e.g. for a mirror with a private PKI
+    #[arg(long, value_name = "PATH")]
+    cacert: Option<PathBuf>,
+
+    /// Follow at most N redirects per request; 0 does not follow redirects (default: 10)
+    #[arg(long, value_name = "N")]
+    max_redirects: Option<usize>,
+
+    /// Offer HTTP/2 when connecting over https and use it if the server picks it, otherwise HTTP/1.1; without it, HTTP/1.1 is used
+    #[arg(long, default_value_t = false)]
+    http2: bool,
+
+    /// Speak HTTP/2 from the first byte of each connection without negotiating it, including over plain http (h2c); requests fail if the server does not speak HTTP/2
+    #[arg(long, default_value_t = false)]
+    http2_prior_knowledge: bool,
+
+    /// Close pooled connections idle for longer than DURATION, e.g. 30s (default: 90s)
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval)]
+    pool_idle_timeout: Option<Duration>,
+
+    /// Send TCP keep-alive probes on open connections every DURATION, e.g. 60s (default: none)
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval)]
+    tcp_keepalive: Option<Duration>,
+
+    /// Send this header with every request, e.g. "X-Api-Key: 1234"; may be repeated. Values of headers that look like credentials are redacted in --verbose output
+    #[arg(short = 'H', long = "header", value_name = "NAME: VALUE", value_parser = get_3gpp_spec::parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Timeout for each request, in seconds
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Time limit for the whole run, e.g. 10min, after which requests in flight are cancelled and the run fails with exit status 4
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval, conflicts_with = "watch")]
+    timeout_total: Option<Duration>,
+
+    /// Retry a failed listing or download up to N times, and reconnect a download whose connection drops up to N times, resuming from the bytes received if the server supports it (default: 0)
+    #[arg(long, value_name = "N")]
+    retries: Option<u32>,
+
+    /// List up to N spec directories in parallel
+    #[arg(
+        short = 'j',
+        long,
+        visible_alias = "jobs",
+        value_name = "N",
+        default_value_t = 8
+    )]
+    list_jobs: usize,
+
+    /// Download up to N files in parallel
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    download_jobs: usize,
+
+    /// Include draft versions (default)
+    #[arg(long, overrides_with = "exclude_drafts")]
+    include_drafts: bool,
+
+    /// Exclude draft versions (major version below 3 or "draft" in the filename)
+    #[arg(long, overrides_with = "include_drafts")]
+    exclude_drafts: bool,
+
+    /// Only versions matching VERSION, whose missing components match anything: 16 selects every 16.x.y and 16.4 every 16.4.x
+    #[arg(long, value_name = "VERSION")]
+    spec_version: Option<PartialVersion>,
+
+    /// Exclude versions whose editorial (last) component is below N
+    #[arg(long, value_name = "N")]
+    min_editorial: Option<u32>,
+
+    /// Keep only the highest editorial of this version of each spec, e.g. 16.4 for the latest 16.4.x
+    #[arg(long, value_name = "MAJOR.MINOR", value_parser = parse_major_minor)]
+    latest_editorial: Option<(u32, u32)>,
+
+    /// Keep only the merged results of specs in this series, e.g. 23
+    #[arg(long, value_name = "NN")]
+    only_series: Option<String>,
+
+    /// Keep only the merged results of specs with this number within their series, e.g. 501
+    #[arg(long, value_name = "X")]
+    only_number: Option<String>,
+
+    /// Keep only items whose listed size is at least SIZE, e.g. 500K or 2MB
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    min_size: Option<u64>,
+
+    /// Keep only items whose listed size is at most SIZE, e.g. 500K or 2MB
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    max_size_listed: Option<u64>,
+
+    /// With --min-size or --max-size-listed, also keep items whose size is not listed
+    #[arg(long, default_value_t = false)]
+    include_unsized: bool,
+
+    /// Order of the printed listing: version or size
+    #[arg(long, value_enum, value_name = "KEY", default_value_t = SortKey::Version)]
+    sort: SortKey,
+
+    /// Print the listing in groups, each sorted by version: release
+    #[arg(long, value_enum, value_name = "KEY", conflicts_with = "sort")]
+    group_by: Option<GroupBy>,
+
+    /// Descend into subdirectory rows, up to DEPTH levels (default: 1)
+    #[arg(long, value_name = "DEPTH", num_args = 0..=1, default_missing_value = "1")]
+    recursive: Option<u32>,
+
     /// List flag (default: false)
     #[arg(short, long, default_value_t = false)]
     list: bool,
+
+    /// Output format of the listing (default: text); `atom`, `json` and `yaml` imply --list
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// In --list text output, follow each date with how long ago it was, e.g. "3 days ago"
+    #[arg(long, default_value_t = false)]
+    show_age: bool,
+
+    /// Write dates in this strftime format, e.g. "%Y-%m-%d %H:%M" (default: %Y-%m-%d for text, RFC 3339 for json and yaml)
+    #[arg(long, value_name = "FORMAT")]
+    date_format: Option<DateFormat>,
+
+    /// Print the versions added and removed since a listing saved with `--format json`
+    #[arg(long, value_name = "PREVIOUS_JSON")]
+    diff: Option<PathBuf>,
+
+    /// Keep polling the listing and print each new version as it appears, until interrupted
+    #[arg(long, default_value_t = false)]
+    watch: bool,
+
+    /// Time between polls with --watch, in seconds or with a unit, e.g. 90s, 15min, 6h or 1d
+    #[arg(long, value_name = "DURATION", default_value = "1h", value_parser = parse_interval)]
+    interval: Duration,
+
+    /// With --watch, also download each new version
+    #[arg(long, default_value_t = false, requires = "watch")]
+    download_new: bool,
+
+    /// Keep only one item per version, preferring the newest date, then a file directly in the spec directory
+    #[arg(long, default_value_t = false)]
+    dedupe: bool,
+
+    /// Keep only one item per spec and version among the items of all the specs listed, e.g. the same file listed under two spec directories
+    #[arg(long, default_value_t = false)]
+    dedup_across_specs: bool,
+
+    /// Parse listings with unusual markup: header cells outside <thead>, or no headers at all, in which case the name, date and size columns are guessed from their content
+    #[arg(long, default_value_t = false)]
+    lenient_html: bool,
+
+    /// Path of a spec directory below the archive root, for mirrors with a different layout, e.g. {series}/{series}{number} [default: {series}_series/{series}.{number}]
+    #[arg(long, value_name = "TEMPLATE")]
+    path_template: Option<PathTemplate>,
+
+    /// Layout of the mirror's listing pages: 3gpp (tables, as on the 3GPP archive) or directory-index (a list of links, each followed by its date and size)
+    #[arg(long, value_name = "KIND", default_value_t = MirrorKind::ThreeGpp)]
+    mirror_kind: MirrorKind,
+
+    /// Ask the server to sort listing pages: name, namerev, date, daterev (newest first), size or sizerev
+    #[arg(long, value_name = "ORDER")]
+    server_sort: Option<ListingSort>,
+
+    /// Timezone the listing's dates are written in, as an IANA name like Europe/Paris (default: UTC, or the GET_3GPP_SOURCE_TZ environment variable)
+    #[arg(long, value_name = "TZ")]
+    source_tz: Option<String>,
+
+    /// Do not send cookies set by earlier responses, e.g. a mirror's session cookie, with later requests
+    #[arg(long, default_value_t = false)]
+    no_cookies: bool,
+
+    /// Cache listing pages in this directory and reuse them while they are fresh (see --cache-ttl)
+    #[arg(long, value_name = "PATH")]
+    cache_dir: Option<PathBuf>,
+
+    /// Reuse a cached listing for up to DURATION after it was fetched, e.g. 30min (default: 1h)
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval)]
+    cache_ttl: Option<Duration>,
+
+    /// Fetch a cached listing again if it is older than DURATION, even within --cache-ttl, e.g. 5min
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval)]
+    max_age: Option<Duration>,
+
+    /// Download every matching spec instead of only the highest version; same as --select all
+    #[arg(short, long, default_value_t = false)]
+    all: bool,
+
+    /// Which matching items to download: the highest version of each spec, the lowest, or all of them
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = Selection::Latest, conflicts_with = "all")]
+    select: Selection,
+
+    /// Print the URL of the spec's directory listing and exit without fetching it
+    #[arg(long, default_value_t = false)]
+    print_url: bool,
+
+    /// Print the series, number, series title and listing URL of each spec and exit without fetching anything
+    #[arg(long, default_value_t = false, conflicts_with = "print_url")]
+    spec_info: bool,
+
+    /// Check that the archive root and each spec directory answer, printing the HTTP status and round-trip time of a HEAD request to each, without listing or downloading; as JSON with --format json
+    #[arg(long, default_value_t = false, conflicts_with = "print_url")]
+    probe: bool,
+
+    /// Print whether each file of the spec directory is kept by the filters and, if not, which filter drops it; as JSON with --format json
+    #[arg(long, default_value_t = false)]
+    explain: bool,
+
+    /// Print only the URL of each selected spec, one per line, instead of downloading
+    #[arg(long, default_value_t = false)]
+    url_only: bool,
+
+    /// Print only the distinct versions of the matching specs, newest first, one per line
+    #[arg(long, default_value_t = false, conflicts_with = "url_only")]
+    list_versions: bool,
+
+    /// Report the status, size and modification date of each matching spec with a HEAD request instead of downloading
+    #[arg(long, default_value_t = false)]
+    head_only: bool,
+
+    /// Set the modification time of each downloaded file to its Last-Modified time, or else to its listing date
+    #[arg(long, default_value_t = false)]
+    preserve_mtime: bool,
+
+    /// Save downloads even if a .zip file's content is not a ZIP archive
+    #[arg(long, default_value_t = false)]
+    no_content_check: bool,
+
+    /// Check each download against the SHA-256 digest in the .sha256 file next to it on the server, warning if there is none
+    #[arg(long, default_value_t = false)]
+    verify_sidecar: bool,
+
+    /// With --verify-sidecar, fail downloads that have no .sha256 file
+    #[arg(long, default_value_t = false, requires = "verify_sidecar")]
+    require_sidecar: bool,
+
+    /// Directory to save downloads in (default: the current directory, or the GET_3GPP_OUTPUT_DIR environment variable)
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Save the download to this path instead of a name derived from its URL; `-` writes it to stdout
+    #[arg(short, long, value_name = "PATH", conflicts_with = "output_dir")]
+    output: Option<PathBuf>,
+
+    /// Name downloads after this template instead of their URL, e.g. "{spec}/{version}.zip"; placeholders: {spec}, {series}, {version}, {date}, {filename}
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_output_template)]
+    output_template: Option<String>,
+
+    /// Print the path each selected spec would be saved to, one per line, instead of downloading
+    #[arg(long, default_value_t = false)]
+    print_filename: bool,
+
+    /// Also download the change-request archive (e.g. 23501_CRs.zip) listed next to each downloaded spec
+    #[arg(long, default_value_t = false)]
+    with_crs: bool,
+
+    /// Download exactly the files recorded in this manifest (written by --manifest), verifying their sizes and SHA-256 digests, instead of listing specs
+    #[arg(long, value_name = "MANIFEST", conflicts_with_all = ["spec_numbers", "spec_number_file"])]
+    spec_file: Option<PathBuf>,
+
+    /// Also fetch the specs listed in this file, one per line, each optionally followed by filters that override the command line's for it, e.g. "23.501 --release 16"; accepts -r, -d, --since, --spec-version, --min-editorial, --include-drafts, --exclude-drafts and --recursive
+    #[arg(long, value_name = "PATH")]
+    spec_number_file: Option<PathBuf>,
+
+    /// Write a JSON manifest of the downloaded files to this path
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+
+    /// Record the files downloaded so far in this checkpoint file, and skip those it already records, so an interrupted batch can be run again where it stopped
+    #[arg(long, value_name = "PATH")]
+    resume_batch: Option<PathBuf>,
+
+    /// Write a JSON summary of the downloads to this path: the totals and, for each file, its URL, destination, status (ok, skipped or failed) and error
+    #[arg(long, value_name = "PATH")]
+    summary_json: Option<PathBuf>,
+
+    /// Exit with status 3 when no spec matches the filters
+    #[arg(long, default_value_t = false)]
+    fail_on_empty: bool,
+
+    /// Read default options from this TOML file instead of the one in the user's config directory
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Print failures to stderr as text or as one JSON object per line
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+
+    /// Color the newest versions in the listing and errors: auto (only on a terminal, unless NO_COLOR is set), always or never
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// Report details such as resolved aliases on stderr
+    #[arg(short, long, default_value_t = false)]
+    verbose: bool,
+
+    /// Print a shell completion script to stdout and exit
+    #[arg(long, hide = true, value_enum, value_name = "SHELL")]
+    generate_completions: Option<completions::Shell>,
 }
 
-fn main() {
-    let args = Args::parse();
-    match get_3gpp_spec::list(args.spec_number, args.release, args.date) {
-        Ok(items) => {
-            match args.list {
-                false => {
-                    if let Some(item) = items.first() {
-                        // Determine filename from URL path segment
-                        let filename = match reqwest::Url::parse(&item.url)
-                            .ok()
-                            .and_then(|u| u.path_segments().and_then(|s| s.last()).map(|s| s.to_string()))
-                        {
-                            Some(f) if !f.is_empty() => f,
-                            _ => "download.bin".to_string(),
-                        };
-
-                        let dest = Path::new(&filename);
-
-                        match download_url_to_path(&item.url, dest) {
-                            Ok(path) => println!("downloaded to {}", path.display()),
-                            Err(e) => eprintln!("{}", e),
-                        }
-                    } else {
-                        eprintln!("no matching item found");
+/// Parse a `--latest-editorial` version: `MAJOR.MINOR`, e.g. `16.4`.
+fn parse_major_minor(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid version '{}': must be MAJOR.MINOR, e.g. 16.4", s);
+    let (major, minor) = s.split_once('.').ok_or_else(invalid)?;
+    Ok((
+        major.parse().map_err(|_| invalid())?,
+        minor.parse().map_err(|_| invalid())?,
+    ))
+}
+
+fn parse_day(s: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|e| format!("invalid date '{}': {}, expected YYYY-MM-DD", s, e))
+}
+
+/// Split `items` into those dated after the start of `day` (UTC) and the others, for
+/// `--newer-than`.
+fn split_newer_than(
+    items: Vec<&SpecItem>,
+    day: chrono::NaiveDate,
+) -> (Vec<&SpecItem>, Vec<&SpecItem>) {
+    let cutoff = day.and_time(chrono::NaiveTime::MIN).and_utc();
+    items.into_iter().partition(|item| item.date > cutoff)
+}
+
+/// The items of `items` whose spec is in `series` and has `number`, each if given, for
+/// `--only-series` and `--only-number`.
+fn only_specs(items: Vec<SpecItem>, series: Option<&str>, number: Option<&str>) -> Vec<SpecItem> {
+    items
+        .into_iter()
+        .filter(|item| series.is_none_or(|series| item.spec.series == series))
+        .filter(|item| number.is_none_or(|number| item.spec.number == number))
+        .collect()
+}
+
+/// Parse a size given on the command line, e.g. `500K` or `2MB` (see `parse_size`).
+fn parse_size_arg(s: &str) -> Result<u64, String> {
+    get_3gpp_spec::parse_size(s).ok_or_else(|| {
+        format!(
+            "invalid size '{}': expected a number with an optional unit, e.g. 500K or 2MB",
+            s
+        )
+    })
+}
+
+/// The items of `items` whose listed size is within `min` and `max`, each if given, for
+/// `--min-size` and `--max-size-listed`. Items of unknown size are kept only with
+/// `include_unsized`, or if no bound is given.
+fn sized(
+    items: Vec<SpecItem>,
+    min: Option<u64>,
+    max: Option<u64>,
+    include_unsized: bool,
+) -> Vec<SpecItem> {
+    if min.is_none() && max.is_none() {
+        return items;
+    }
+    items
+        .into_iter()
+        .filter(|item| match item.size {
+            Some(size) => min.is_none_or(|min| size >= min) && max.is_none_or(|max| size <= max),
+            None => include_unsized,
+        })
+        .collect()
+}
+
+/// `items`, in canonical order, sorted by `key` for printing. The sort is stable, so items of
+/// the same size keep their canonical order.
+fn sorted(items: &[SpecItem], key: SortKey) -> Vec<SpecItem> {
+    let mut items = items.to_vec();
+    if key == SortKey::Size {
+        items.sort_by_key(|item| item.size);
+    }
+    items
+}
+
+/// The item with the highest editorial of `major`.`minor` of each spec in `items`, for
+/// `--latest-editorial`. Specs without that version are dropped.
+fn latest_editorials(items: &[SpecItem], (major, minor): (u32, u32)) -> Vec<SpecItem> {
+    let mut specs: Vec<&get_3gpp_spec::SpecNumber> = items.iter().map(|i| &i.spec).collect();
+    specs.dedup();
+    specs
+        .into_iter()
+        .filter_map(|spec| get_3gpp_spec::latest_editorial(items, spec, major, minor))
+        .cloned()
+        .collect()
+}
+
+/// Parse a `--interval` duration: a positive number of seconds, optionally followed by `s`,
+/// `min`, `h` or `d`.
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(digits);
+    let seconds = match unit {
+        "" | "s" => 1,
+        "min" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => {
+            return Err(format!(
+                "invalid interval '{}': must be a number followed by s, min, h or d (e.g. 15min)",
+                s
+            ));
+        }
+    };
+    match amount.parse::<u64>() {
+        Ok(amount) if amount > 0 => Ok(Duration::from_secs(amount.saturating_mul(seconds))),
+        _ => Err(format!(
+            "invalid interval '{}': must be a positive number",
+            s
+        )),
+    }
+}
+
+/// Create `dir` and its parents, unless it is empty for the current directory.
+fn create_output_dir(dir: &std::path::Path) -> Result<(), String> {
+    if dir.as_os_str().is_empty() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir).map_err(|e| {
+        format!(
+            "failed to create output directory '{}': {}",
+            dir.display(),
+            e
+        )
+    })
+}
+
+/// Placeholders of `--output-template`.
+const TEMPLATE_PLACEHOLDERS: &[&str] = &["spec", "series", "version", "date", "filename"];
+
+/// Check that every `{...}` placeholder in an `--output-template` is known.
+fn parse_output_template(template: &str) -> Result<String, String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed placeholder in template '{}'", template))?;
+        let name = &rest[start + 1..start + end];
+        if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder '{{{}}}' in template '{}': must be one of {}",
+                name,
+                template,
+                TEMPLATE_PLACEHOLDERS
+                    .iter()
+                    .map(|p| format!("{{{}}}", p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(template.to_string())
+}
+
+/// Determine the local filename for `item` from the last segment of its URL path, decoded with
+/// `decode_segment`.
+fn filename_for(item: &SpecItem) -> String {
+    match reqwest::Url::parse(&item.url).ok().and_then(|u| {
+        u.path_segments()
+            .and_then(|mut s| s.next_back())
+            .map(get_3gpp_spec::decode_segment)
+    }) {
+        Some(f) if !f.is_empty() => f,
+        _ => "download.bin".to_string(),
+    }
+}
+
+/// Destination of `item` in `out_dir`: the last segment of its URL path, or `template` with its
+/// placeholders replaced by the item's spec number, series, version (`unversioned` if it has
+/// none), date (`YYYY-MM-DD`) and URL filename. A template may contain `/` to save into
+/// subdirectories.
+fn derive_filename(item: &SpecItem, template: Option<&str>, out_dir: &Path) -> PathBuf {
+    let filename = filename_for(item);
+    let Some(template) = template else {
+        return out_dir.join(filename);
+    };
+    let version = item
+        .version
+        .map_or_else(|| "unversioned".to_string(), |v| v.to_string());
+    let name = template
+        .replace("{spec}", &item.spec.to_string())
+        .replace("{series}", &item.spec.series)
+        .replace("{version}", &version)
+        .replace("{date}", &item.date.format("%Y-%m-%d").to_string())
+        .replace("{filename}", &filename);
+    out_dir.join(name)
+}
+
+/// Items to act on: every item with `Selection::All`, otherwise only the highest or lowest
+/// version of each spec. `items` holds the items of each spec in a run, in the canonical order
+/// of `list` (by version, then date), so the highest version of a spec is the last of its run
+/// and the lowest its first versioned item. Unversioned items sort first, so a spec with only
+/// unversioned items has neither.
+fn select(items: &[SpecItem], selection: Selection) -> Vec<&SpecItem> {
+    items
+        .iter()
+        .enumerate()
+        .filter(|&(i, item)| match selection {
+            Selection::All => true,
+            Selection::Latest => {
+                item.version.is_some() && items.get(i + 1).is_none_or(|next| next.spec != item.spec)
+            }
+            Selection::Oldest => {
+                item.version.is_some()
+                    && (i == 0 || items[i - 1].spec != item.spec || items[i - 1].version.is_none())
+            }
+        })
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// `selected` followed by the change-request archive of each spec in it, for `--with-crs`.
+/// Specs without an archive in `items` are reported on stderr.
+fn with_change_requests<'a>(
+    items: &'a [SpecItem],
+    mut selected: Vec<&'a SpecItem>,
+) -> Vec<&'a SpecItem> {
+    let mut specs: Vec<&get_3gpp_spec::SpecNumber> = selected.iter().map(|i| &i.spec).collect();
+    specs.dedup();
+    for spec in specs {
+        match get_3gpp_spec::change_requests(items, spec) {
+            Some(crs) if !selected.iter().any(|i| std::ptr::eq(*i, crs)) => selected.push(crs),
+            Some(_) => {}
+            None => eprintln!("no change-request archive listed for {}", spec),
+        }
+    }
+    selected
+}
+
+/// The distinct versions of `items`, newest first, one per line for `--list-versions`.
+/// Unversioned items are left out.
+fn version_lines(items: &[SpecItem]) -> String {
+    let mut versions: Vec<_> = items.iter().filter_map(|item| item.version).collect();
+    versions.sort_by(|a, b| b.cmp(a));
+    versions.dedup();
+    versions.iter().map(|v| format!("{}\n", v)).collect()
+}
+
+/// Line of `--explain` output for a file of `spec`: the spec, `kept` or `dropped`, the reason
+/// (`-` if kept) and the filename, in aligned columns.
+fn explain_line(spec: &get_3gpp_spec::SpecNumber, explanation: &Explanation) -> String {
+    let status = match explanation.kept {
+        true => "kept",
+        false => "dropped",
+    };
+    let reason = explanation.reason.map_or("-", |r| r.as_str());
+    format!(
+        "{:<8} {:<7} {:<16} {}",
+        spec.to_string(),
+        status,
+        reason,
+        explanation.filename
+    )
+}
+
+/// One URL per line for `--url-only`.
+fn url_lines(items: &[&SpecItem]) -> String {
+    items.iter().map(|item| format!("{}\n", item.url)).collect()
+}
+
+/// Run `f`, retrying up to `retries` more times while it fails with an error for which
+/// `retryable` holds.
+fn retry<T, E: std::fmt::Display>(
+    retries: u32,
+    retryable: impl Fn(&E) -> bool,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(e) if attempt < retries && retryable(&e) => {
+                attempt += 1;
+                eprintln!("{} (retry {} of {})", e, attempt, retries);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Writer recording whether any byte was written through it, for `retry_unstarted`.
+struct StartedWriter<'a, W> {
+    inner: W,
+    started: &'a std::cell::Cell<bool>,
+}
+
+impl<W: std::io::Write> std::io::Write for StartedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if written > 0 {
+            self.started.set(true);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Run `download` writing to `out`, e.g. stdout for `--output -`, retrying up to `retries` more
+/// times only while nothing was written: bytes that reached `out` cannot be taken back, so a
+/// retry would write the body again after them.
+fn retry_unstarted<W: std::io::Write>(
+    retries: u32,
+    out: W,
+    mut download: impl FnMut(&mut StartedWriter<W>) -> Result<u64, String>,
+) -> Result<u64, String> {
+    let started = std::cell::Cell::new(false);
+    let mut out = StartedWriter {
+        inner: out,
+        started: &started,
+    };
+    retry(retries, |_| !started.get(), || download(&mut out))
+}
+
+/// Resolve the spec references in `reader`, one per line, for the `-` argument. Blank lines and
+/// lines starting with `#` are skipped. An invalid line yields an error naming its line number
+/// in place of its reference, so that the remaining lines are still processed.
+fn read_spec_refs(
+    reader: impl BufRead,
+    resolve: impl Fn(&str) -> Result<SpecRef, String>,
+) -> Vec<Result<SpecRef, String>> {
+    let mut refs = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                refs.push(Err(format!("failed to read stdin: {}", e)));
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        refs.push(resolve(line).map_err(|e| format!("stdin line {}: {}", i + 1, e)));
+    }
+    refs
+}
+
+/// Build the HTTP client with the options given on the command line or in the config file,
+/// warning if certificate verification is disabled. `cookies` is the client's cookie store.
+fn build_client(
+    args: &Args,
+    cookies: Option<&CookieJar>,
+) -> Result<reqwest::blocking::Client, String> {
+    if args.insecure {
+        eprintln!("WARNING: TLS certificate verification is disabled (--insecure)");
+    }
+    get_3gpp_spec::build_client(&ClientOptions {
+        proxy: args.proxy.clone(),
+        insecure: args.insecure,
+        cacert: args.cacert.clone(),
+        timeout: args.timeout.map(Duration::from_secs),
+        max_redirects: args.max_redirects,
+        http2: args.http2,
+        http2_prior_knowledge: args.http2_prior_knowledge,
+        pool_idle_timeout: args.pool_idle_timeout,
+        tcp_keepalive: args.tcp_keepalive,
+        headers: args.headers.clone(),
+        cookies: cookies.cloned(),
+    })
+}
+
+/// Download the files recorded in the manifest at `path` for `--spec-file`, verifying each
+/// against its recorded size and digest. Files are saved under their recorded names in
+/// `--output-dir`.
+fn fetch_spec_file(
+    args: &Args,
+    path: &Path,
+    deadline: Option<Instant>,
+    report: &dyn Fn(Report),
+) -> ExitCode {
+    let manifest = match Manifest::read(path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            report(Report::new("io", e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let cookies = cookie_jar(args);
+    let client = match build_client(args, cookies.as_ref()) {
+        Ok(client) => client,
+        Err(e) => {
+            report(Report::new("client", e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let output_dir = args.output_dir.clone().unwrap_or_default();
+    if let Err(e) = create_output_dir(&output_dir) {
+        report(Report::new("io", e));
+        return ExitCode::FAILURE;
+    }
+    let options = DownloadOptions {
+        check_content: !args.no_content_check,
+        deadline,
+        cookies,
+        sidecar: sidecar_check(args),
+        reconnects: args.retries.unwrap_or(0),
+    };
+
+    let results = get_3gpp_spec::parallel_map(&manifest.entries, args.download_jobs, |entry| {
+        let filename = Path::new(&entry.path)
+            .file_name()
+            .ok_or_else(|| format!("manifest entry for '{}' has no file name", entry.url))?;
+        let dest = output_dir.join(filename);
+        retry(
+            args.retries.unwrap_or(0),
+            |_| true,
+            || entry.fetch(&client, &dest, &options),
+        )
+    });
+    let mut failed = false;
+    let mut downloaded = 0;
+    for (entry, result) in manifest.entries.iter().zip(results) {
+        match result {
+            Ok(download) => {
+                println!("downloaded to {}", download.path.display());
+                preserve_mtime(args, &download, entry.date);
+                downloaded += 1;
+            }
+            Err(e) => {
+                report(Report {
+                    spec: Some(entry.spec.clone()),
+                    url: Some(entry.url.clone()),
+                    ..Report::new("download", e)
+                });
+                failed = true;
+            }
+        }
+    }
+    if failed && timed_out(deadline) {
+        report(timeout_report(
+            args.timeout_total,
+            format!(
+                "downloaded {} of {} files",
+                downloaded,
+                manifest.entries.len()
+            ),
+        ));
+        return ExitCode::from(EXIT_TIMEOUT);
+    }
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+/// With `--preserve-mtime`, set the modification time of `download` from its `Last-Modified`
+/// time or its listing date `listed`. A failure is only a warning, as the file is saved.
+fn preserve_mtime(
+    args: &Args,
+    download: &get_3gpp_spec::Download,
+    listed: chrono::DateTime<chrono::Utc>,
+) {
+    if args.preserve_mtime
+        && let Err(e) = get_3gpp_spec::preserve_mtime(download, Some(listed))
+    {
+        eprintln!("warning: {}", e);
+    }
+}
+
+/// Description of the spec of `query` for `--spec-info`: its series and number, the title of its
+/// series and the URL of its directory listing, one per line.
+fn spec_info(query: &ListQuery) -> Result<String, Error> {
+    let spec = &query.spec_number;
+    let mut lines = vec![
+        format!("spec:    {}", spec),
+        format!("series:  {}", spec.series),
+        format!("number:  {}", spec.number),
+        format!("title:   {}", spec.series_description().unwrap_or("-")),
+    ];
+    if let Some(release) = query.release {
+        lines.push(format!("release: {}", release));
+    }
+    lines.push(format!("listing: {}", query.listing_url()?));
+    Ok(lines.join("\n"))
+}
+
+/// Exit status after downloading a batch: failure if any download failed, even though the
+/// others were saved.
+fn batch_status(summary: &BatchSummary) -> ExitCode {
+    match summary.failed {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
+
+/// Jar shared by the requests of this invocation, unless `--no-cookies` is given.
+fn cookie_jar(args: &Args) -> Option<CookieJar> {
+    (!args.no_cookies).then(CookieJar::new)
+}
+
+/// The listing cache in `--cache-dir`, if given, with `--cache-ttl` and `--max-age`.
+fn listing_cache(args: &Args) -> Option<ListingCache> {
+    let dir = args.cache_dir.clone()?;
+    Some(ListingCache {
+        ttl: args.cache_ttl.unwrap_or(DEFAULT_CACHE_TTL),
+        max_age: args.max_age,
+        ..ListingCache::new(dir)
+    })
+}
+
+/// Timezone of listing dates: `--source-tz` if given, otherwise the one named by
+/// `GET_3GPP_SOURCE_TZ` as looked up by `env`.
+fn source_tz(
+    flag: Option<&str>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Option<SourceTz>, String> {
+    if let Some(name) = flag {
+        return SourceTz::named(name).map(Some);
+    }
+    match env(SOURCE_TZ_VAR).filter(|name| !name.is_empty()) {
+        Some(name) => SourceTz::named(&name)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", SOURCE_TZ_VAR, e)),
+        None => Ok(None),
+    }
+}
+
+/// Directory to save downloads in: `--output-dir` if given, otherwise the one named by
+/// `GET_3GPP_OUTPUT_DIR` as looked up by `env`. Neither applies when `--output` names the file.
+fn output_dir(
+    flag: Option<&Path>,
+    output: Option<&Path>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<PathBuf> {
+    match (flag, output) {
+        (Some(dir), _) => Some(dir.to_path_buf()),
+        (None, Some(_)) => None,
+        (None, None) => env(OUTPUT_DIR_VAR)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from),
+    }
+}
+
+/// Sidecar checking requested by `--verify-sidecar` and `--require-sidecar`.
+fn sidecar_check(args: &Args) -> SidecarCheck {
+    match (args.verify_sidecar, args.require_sidecar) {
+        (_, true) => SidecarCheck::Required,
+        (true, false) => SidecarCheck::IfPresent,
+        (false, false) => SidecarCheck::Off,
+    }
+}
+
+/// Whether the `--timeout-total` deadline is set and has passed.
+fn timed_out(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Report of a run cut short by `--timeout-total`, saying what it `completed` before.
+fn timeout_report(total: Option<Duration>, completed: String) -> Report {
+    let total = total.map_or_else(String::new, |t| format!(" of {:?}", t));
+    Report::new(
+        "timeout",
+        format!("total timeout{} exceeded: {}", total, completed),
+    )
+}
+
+/// Exit status for a successful run that matched `items`.
+fn exit_status(items: &[SpecItem], fail_on_empty: bool) -> ExitCode {
+    match items.is_empty() && fail_on_empty {
+        true => ExitCode::from(EXIT_EMPTY),
+        false => ExitCode::SUCCESS,
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    if args.all {
+        args.select = Selection::All;
+    }
+    let started = Instant::now();
+    if let Some(shell) = args.generate_completions {
+        print!("{}", completions::generate(shell, &Args::command()));
+        return ExitCode::SUCCESS;
+    }
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(match args.verbose {
+            true => log::LevelFilter::Info,
+            false => log::LevelFilter::Warn,
+        });
+    }
+    let errors = args.error_format;
+    let no_color = || std::env::var_os("NO_COLOR");
+    let color_stdout = args
+        .color
+        .enabled(std::io::stdout().is_terminal(), no_color());
+    let color_stderr = errors == ErrorFormat::Text
+        && args
+            .color
+            .enabled(std::io::stderr().is_terminal(), no_color());
+    let report = |report: Report| match color_stderr {
+        true => eprintln!("{}{}{}", RED, report.render(errors), RESET),
+        false => eprintln!("{}", report.render(errors)),
+    };
+    let config = match config::Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            report(Report::new("config", e));
+            return ExitCode::FAILURE;
+        }
+    };
+    args.output_dir = output_dir(args.output_dir.as_deref(), args.output.as_deref(), |name| {
+        std::env::var(name).ok()
+    });
+    config.apply(&mut args);
+    if let Some(path) = &args.spec_file {
+        let deadline = args.timeout_total.map(|total| started + total);
+        return fetch_spec_file(&args, path, deadline, &report);
+    }
+    // clap only allows a missing spec number together with `--generate-completions` or a file.
+    if args.spec_numbers.is_empty() && args.spec_number_file.is_none() {
+        return ExitCode::SUCCESS;
+    }
+    let resolve = |input: &str| {
+        let (spec_ref, alias) = config.resolve_spec_ref(input)?;
+        if let Some(alias) = alias
+            && args.verbose
+        {
+            eprintln!("alias '{}' resolved to {}", alias, spec_ref.spec_number);
+        }
+        Ok(spec_ref)
+    };
+    let mut failed = false;
+    let mut spec_refs = Vec::new();
+    for input in &args.spec_numbers {
+        if input == "-" {
+            for result in read_spec_refs(std::io::stdin().lock(), resolve) {
+                match result {
+                    Ok(spec_ref) => spec_refs.push((spec_ref, None)),
+                    Err(e) => {
+                        report(Report::new("input", e));
+                        failed = true;
+                    }
+                }
+            }
+            continue;
+        }
+        match resolve(input) {
+            Ok(spec_ref) => spec_refs.push((spec_ref, None)),
+            Err(e) => Args::command()
+                .error(clap::error::ErrorKind::ValueValidation, e)
+                .exit(),
+        }
+    }
+    if let Some(path) = &args.spec_number_file {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                report(Report::new(
+                    "io",
+                    format!("failed to open '{}': {}", path.display(), e),
+                ));
+                return ExitCode::FAILURE;
+            }
+        };
+        let name = path.display().to_string();
+        for result in batch::read_batch(std::io::BufReader::new(file), &name, resolve) {
+            match result {
+                Ok((spec_ref, line)) => spec_refs.push((spec_ref, Some(line))),
+                Err(e) => {
+                    report(Report::new("input", e));
+                    failed = true;
+                }
+            }
+        }
+    }
+    if spec_refs.is_empty() {
+        let source = match &args.spec_number_file {
+            Some(path) => format!("'{}'", path.display()),
+            None => "stdin".to_string(),
+        };
+        report(Report::new(
+            "input",
+            format!("no spec numbers given on {}", source),
+        ));
+        return ExitCode::FAILURE;
+    }
+
+    let deadline = args.timeout_total.map(|total| started + total);
+    let source_tz = match source_tz(args.source_tz.as_deref(), |name| std::env::var(name).ok()) {
+        Ok(source_tz) => source_tz,
+        Err(e) => {
+            report(Report::new("config", e));
+            return ExitCode::FAILURE;
+        }
+    };
+    let cookies = cookie_jar(&args);
+    let cache = listing_cache(&args);
+    let format = args.format.unwrap_or(Format::Text);
+    let text_dates = args
+        .date_format
+        .clone()
+        .unwrap_or_else(DateFormat::date_only);
+    let retries = args.retries.unwrap_or(0);
+    let clock = SharedClock::default();
+
+    let (queries, lines): (Vec<ListQuery>, Vec<Option<usize>>) = spec_refs
+        .into_iter()
+        .map(|(spec_ref, batch_line)| {
+            let mut query = ListQuery::from(spec_ref);
+            if let Some(base_url) = &args.base_url {
+                query.base_url = base_url.clone();
+            }
+            if let Some(template) = &args.path_template {
+                query.path_template = template.clone();
+            }
+            query.release = args.release.or(query.release);
+            query.date_filter = args.date;
+            query.since = args.since.and_then(|d| d.cutoff(&clock));
+            query.clock = clock.clone();
+            query.include_drafts = !args.exclude_drafts;
+            query.min_editorial = args.min_editorial;
+            query.version = args.spec_version;
+            query.max_depth = args.recursive.unwrap_or(0);
+            query.dedupe = args.dedupe;
+            query.deadline = deadline;
+            query.lenient_html = args.lenient_html;
+            query.cookies = cookies.clone();
+            query.cache = cache.clone();
+            query.source_tz = source_tz.clone();
+            query.sort = args.server_sort;
+            query.mirror_kind = args.mirror_kind;
+            match batch_line {
+                Some(batch_line) => {
+                    batch_line.options.apply(&mut query, &clock);
+                    (query, Some(batch_line.line))
+                }
+                None => (query, None),
+            }
+        })
+        .unzip();
+    // Per-line outcomes of `--spec-number-file` name the file and line of the spec.
+    let origin = |line: Option<usize>| {
+        let path = args.spec_number_file.as_ref()?;
+        Some(format!("{} line {}", path.display(), line?))
+    };
+    if args.print_url || args.spec_info {
+        for query in &queries {
+            let info = match args.spec_info {
+                true => spec_info(query),
+                false => query.listing_url().map(|url| url.to_string()),
+            };
+            match info {
+                Ok(info) => println!("{}", info),
+                Err(e) => {
+                    report(Report::from_error(&e));
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        return match failed {
+            true => ExitCode::FAILURE,
+            false => ExitCode::SUCCESS,
+        };
+    }
+    let client = match build_client(&args, cookies.as_ref()) {
+        Ok(client) => client,
+        Err(e) => {
+            report(Report::new("client", e));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.probe {
+        let mut probes: Vec<get_3gpp_spec::Probe> = Vec::new();
+        for query in &queries {
+            match get_3gpp_spec::probe(&client, query) {
+                // Specs listed from the same archive share its root.
+                Ok(found) => probes.extend(
+                    found
+                        .into_iter()
+                        .filter(|probe| probes.iter().all(|p| p.url != probe.url))
+                        .collect::<Vec<_>>(),
+                ),
+                Err(e) => {
+                    report(Report::from_error(&e));
+                    failed = true;
+                }
+            }
+        }
+        if format == Format::Json {
+            match serde_json::to_string_pretty(&probes) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    report(Report::new(
+                        "io",
+                        format!("failed to serialize probes: {}", e),
+                    ));
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            for probe in &probes {
+                println!("{}", probe);
+            }
+        }
+        return match failed || !probes.iter().all(get_3gpp_spec::Probe::found) {
+            true => ExitCode::FAILURE,
+            false => ExitCode::SUCCESS,
+        };
+    }
+
+    if args.explain {
+        let mut explained = Vec::new();
+        for query in &queries {
+            match get_3gpp_spec::explain(&client, query) {
+                Ok(explanations) => {
+                    explained.extend(explanations.into_iter().map(|e| (&query.spec_number, e)))
+                }
+                Err(e) => {
+                    report(Report::from_error(&e));
+                    failed = true;
+                }
+            }
+        }
+        if format == Format::Json {
+            let explanations: Vec<_> = explained.iter().map(|(_, e)| e).collect();
+            match serde_json::to_string_pretty(&explanations) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    report(Report::new(
+                        "io",
+                        format!("failed to serialize explanation: {}", e),
+                    ));
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            for (spec, explanation) in &explained {
+                println!("{}", explain_line(spec, explanation));
+            }
+        }
+        return match failed {
+            true => ExitCode::FAILURE,
+            false => ExitCode::SUCCESS,
+        };
+    }
+
+    let list_all = || {
+        let results = get_3gpp_spec::parallel_map(&queries, args.list_jobs, |query| {
+            retry(
+                retries,
+                |e| !matches!(e, Error::EmptyDirectory(_) | Error::ReleaseNotFound { .. }),
+                || get_3gpp_spec::list(&client, query),
+            )
+        });
+        let mut items = Vec::new();
+        let mut failed = false;
+        for ((query, line), result) in queries.iter().zip(&lines).zip(results) {
+            let origin = origin(*line);
+            match result {
+                Ok(found) => {
+                    if let Some(origin) = &origin {
+                        eprintln!("{}: {}: {} items", origin, query.spec_number, found.len());
                     }
-                    return;
+                    items.extend(found)
                 }
-                true => {
-                    for item in items.iter() {
-                        println!("{}", item);
+                Err(e) => {
+                    let spec = match &origin {
+                        Some(origin) => Some(format!("{}: {}", origin, query.spec_number)),
+                        None => (queries.len() > 1).then(|| query.spec_number.to_string()),
+                    };
+                    report(Report {
+                        spec,
+                        ..Report::from_error(&e)
+                    });
+                    // A release without items is an empty result, as for the other filters.
+                    failed |= !matches!(e, Error::ReleaseNotFound { .. });
+                }
+            }
+        }
+        let items = only_specs(
+            items,
+            args.only_series.as_deref(),
+            args.only_number.as_deref(),
+        );
+        let mut items = sized(
+            items,
+            args.min_size,
+            args.max_size_listed,
+            args.include_unsized,
+        );
+        if args.dedup_across_specs {
+            for dropped in get_3gpp_spec::dedupe_across_specs(&mut items) {
+                if let Some(version) = dropped.version {
+                    log::warn!(
+                        "dropped duplicate of {} version {}: {}",
+                        dropped.spec,
+                        version,
+                        dropped.url
+                    );
+                }
+            }
+        }
+        (items, failed)
+    };
+    let options = DownloadOptions {
+        check_content: !args.no_content_check,
+        deadline,
+        cookies: cookies.clone(),
+        sidecar: sidecar_check(&args),
+        reconnects: retries,
+    };
+    let output_dir = args.output_dir.clone().unwrap_or_default();
+
+    if args.watch {
+        if args.download_new
+            && let Err(e) = create_output_dir(&output_dir)
+        {
+            report(Report::new("io", e));
+            return ExitCode::FAILURE;
+        }
+        let mut watcher = get_3gpp_spec::Watcher::new();
+        loop {
+            // A failed listing would make the next poll report the spec's items as new.
+            if let (items, false) = list_all() {
+                for item in watcher.poll(items) {
+                    println!("new: {}", item.to_string_with(&text_dates));
+                    if !args.download_new {
+                        continue;
+                    }
+                    let dest = derive_filename(&item, args.output_template.as_deref(), &output_dir);
+                    let result = dest
+                        .parent()
+                        .map_or(Ok(()), create_output_dir)
+                        .and_then(|()| {
+                            get_3gpp_spec::download_url_to_path(&client, &item.url, &dest, &options)
+                        });
+                    match result {
+                        Ok(download) => {
+                            println!("downloaded to {}", download.path.display());
+                            preserve_mtime(&args, &download, item.date);
+                        }
+                        Err(e) => report(Report {
+                            url: Some(item.url.clone()),
+                            ..Report::new("download", e)
+                        }),
                     }
                 }
             }
+            std::thread::sleep(args.interval);
+        }
+    }
+
+    let (items, list_failed) = list_all();
+    failed |= list_failed;
+    let items = match args.latest_editorial {
+        Some(version) => latest_editorials(&items, version),
+        None => items,
+    };
+    if list_failed && timed_out(deadline) {
+        report(timeout_report(
+            args.timeout_total,
+            format!("listed {} items before the deadline", items.len()),
+        ));
+        return ExitCode::from(EXIT_TIMEOUT);
+    }
+    if failed && items.is_empty() {
+        return ExitCode::FAILURE;
+    }
+
+    let status = if args.url_only {
+        let selected = match args.list {
+            true => select(&items, Selection::All),
+            false => select(&items, args.select),
+        };
+        if selected.is_empty() {
+            return ExitCode::from(EXIT_EMPTY);
+        }
+        print!("{}", url_lines(&selected));
+        ExitCode::SUCCESS
+    } else if args.list_versions {
+        print!("{}", version_lines(&items));
+        exit_status(&items, args.fail_on_empty)
+    } else if args.print_filename {
+        let selected = select(&items, args.select);
+        if selected.is_empty() {
+            return exit_status(&items, args.fail_on_empty);
+        }
+        for item in selected {
+            let dest = match &args.output {
+                Some(output) => output.clone(),
+                None => derive_filename(item, args.output_template.as_deref(), &output_dir),
+            };
+            println!("{}", dest.display());
+        }
+        ExitCode::SUCCESS
+    } else if args.head_only {
+        let mut found = Vec::new();
+        for item in &items {
+            match retry(
+                retries,
+                |_| true,
+                || get_3gpp_spec::fetch_metadata(&client, &item.url),
+            ) {
+                Ok(metadata) => found.push(metadata),
+                Err(e) => {
+                    report(Report {
+                        url: Some(item.url.clone()),
+                        ..Report::new("head", e)
+                    });
+                    failed = true;
+                }
+            }
+        }
+        match format {
+            Format::Json => match serde_json::to_string_pretty(&found) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    report(Report::new(
+                        "io",
+                        format!("failed to serialize headers: {}", e),
+                    ));
+                    return ExitCode::FAILURE;
+                }
+            },
+            Format::Yaml => match get_3gpp_spec::to_yaml(&found) {
+                Ok(yaml) => print!("{}", yaml),
+                Err(e) => {
+                    report(Report::new("io", e));
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ => {
+                for metadata in &found {
+                    println!("{}", metadata);
+                }
+            }
+        }
+        exit_status(&items, args.fail_on_empty)
+    } else if let Some(path) = &args.diff {
+        let previous = match get_3gpp_spec::read_listing(path) {
+            Ok(previous) => previous,
+            Err(e) => {
+                report(Report::new("io", e));
+                return ExitCode::FAILURE;
+            }
+        };
+        print!("{}", get_3gpp_spec::diff(&previous, &items));
+        exit_status(&items, args.fail_on_empty)
+    } else if format == Format::Json {
+        let json = match args.group_by {
+            Some(GroupBy::Release) => ReleaseGroups::new(&items, args.date_format.as_ref())
+                .and_then(|groups| serde_json::to_string_pretty(&groups)),
+            None => dated_listing(&sorted(&items, args.sort), args.date_format.as_ref())
+                .and_then(|value| serde_json::to_string_pretty(&value)),
+        };
+        match json {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                report(Report::new(
+                    "io",
+                    format!("failed to serialize listing: {}", e),
+                ));
+                return ExitCode::FAILURE;
+            }
+        }
+        exit_status(&items, args.fail_on_empty)
+    } else if format == Format::Yaml {
+        match dated_listing(&sorted(&items, args.sort), args.date_format.as_ref())
+            .map_err(|e| format!("failed to serialize listing: {}", e))
+            .and_then(|value| get_3gpp_spec::to_yaml(&value))
+        {
+            Ok(yaml) => print!("{}", yaml),
+            Err(e) => {
+                report(Report::new("io", e));
+                return ExitCode::FAILURE;
+            }
+        }
+        exit_status(&items, args.fail_on_empty)
+    } else if format == Format::Atom {
+        let specs: Vec<_> = queries.iter().map(|q| q.spec_number.clone()).collect();
+        print!("{}", get_3gpp_spec::atom_feed(&specs, &items, clock.now()));
+        exit_status(&items, args.fail_on_empty)
+    } else if args.list {
+        for query in &queries {
+            if let Some(description) = query.spec_number.series_description() {
+                eprintln!("{} series: {}", query.spec_number.series, description);
+            }
+        }
+        let newest = select(&items, Selection::Latest);
+        let now = args.show_age.then(|| clock.now());
+        let line = |item: &SpecItem| {
+            let is_newest = newest.contains(&item);
+            list_line(item, &text_dates, now, is_newest, color_stdout)
+        };
+        match args.group_by {
+            Some(GroupBy::Release) => print!("{}", grouped_lines(&items, line)),
+            None => {
+                for item in &sorted(&items, args.sort) {
+                    println!("{}", line(item));
+                }
+            }
         }
-        Err(e) => eprintln!("{}", e),
+        exit_status(&items, args.fail_on_empty)
+    } else {
+        let selected = select(&items, args.select);
+        if selected.is_empty() {
+            eprintln!("no matching item found: the filters excluded all items");
+            return exit_status(&items, args.fail_on_empty);
+        }
+        let selected = match args.with_crs {
+            true => with_change_requests(&items, selected),
+            false => selected,
+        };
+        let selected = match args.newer_than {
+            Some(day) => {
+                let (newer, older) = split_newer_than(selected, day);
+                for item in older {
+                    eprintln!(
+                        "skipping {}: dated {}, not newer than {}",
+                        item.url,
+                        item.date.format("%Y-%m-%d"),
+                        day
+                    );
+                }
+                newer
+            }
+            None => selected,
+        };
+        if selected.is_empty() {
+            eprintln!("nothing to download: no matching item is newer than the cutoff");
+            return match failed {
+                true => ExitCode::FAILURE,
+                false => ExitCode::SUCCESS,
+            };
+        }
+
+        if args.output.is_some() && selected.len() > 1 {
+            report(Report::new(
+                "usage",
+                format!(
+                    "--output saves a single file, but {} items are selected",
+                    selected.len()
+                ),
+            ));
+            return ExitCode::FAILURE;
+        }
+        if args.output.as_deref() == Some(std::path::Path::new("-")) {
+            let item = selected[0];
+            let result = retry_unstarted(retries, std::io::stdout().lock(), |out| {
+                get_3gpp_spec::download_url_to_writer(&client, &item.url, out, &options)
+            });
+            return match result {
+                Ok(_) if failed => ExitCode::FAILURE,
+                Ok(_) => ExitCode::SUCCESS,
+                Err(e) => {
+                    report(Report {
+                        url: Some(item.url.clone()),
+                        ..Report::new("download", e)
+                    });
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        if let Err(e) = create_output_dir(&output_dir) {
+            report(Report::new("io", e));
+            return ExitCode::FAILURE;
+        }
+        let checkpoint = match args.resume_batch.as_deref().map(Checkpoint::open) {
+            Some(Ok(checkpoint)) => Some(checkpoint),
+            Some(Err(e)) => {
+                report(Report::new("io", e));
+                return ExitCode::FAILURE;
+            }
+            None => None,
+        };
+        let mut manifest = Manifest::default();
+        let mut summary = BatchSummary::default();
+        let started = Instant::now();
+        let (done, downloads): (Vec<_>, Vec<_>) = selected
+            .iter()
+            .map(|item| {
+                let dest = match &args.output {
+                    Some(output) => output.clone(),
+                    None => derive_filename(item, args.output_template.as_deref(), &output_dir),
+                };
+                (*item, dest)
+            })
+            .partition(|(item, _)| {
+                checkpoint
+                    .as_ref()
+                    .is_some_and(|c| c.is_completed(&item.url))
+            });
+        for (item, dest) in &done {
+            summary.record_skipped(&item.url, dest);
+        }
+        if !done.is_empty() {
+            eprintln!(
+                "skipping {} file{} already downloaded",
+                done.len(),
+                if done.len() == 1 { "" } else { "s" }
+            );
+        }
+        let results =
+            get_3gpp_spec::parallel_map(&downloads, args.download_jobs, |(item, dest)| {
+                if let Some(parent) = dest.parent() {
+                    create_output_dir(parent)?;
+                }
+                let download = retry(
+                    retries,
+                    |_| true,
+                    || get_3gpp_spec::download_url_to_path(&client, &item.url, dest, &options),
+                )?;
+                if let Some(checkpoint) = &checkpoint
+                    && let Err(e) = checkpoint.complete(&item.url)
+                {
+                    log::warn!("{}", e);
+                }
+                Ok(download)
+            });
+        for ((item, dest), result) in downloads.iter().zip(results) {
+            summary.record_item(&item.url, dest, &result);
+            match result {
+                Ok(download) => {
+                    println!("downloaded to {}", download.path.display());
+                    preserve_mtime(&args, &download, item.date);
+                    manifest.entries.push(ManifestEntry::new(item, &download));
+                }
+                Err(e) => report(Report {
+                    url: Some(item.url.clone()),
+                    ..Report::new("download", e)
+                }),
+            }
+        }
+        summary.elapsed = started.elapsed();
+        if args.select == Selection::All {
+            println!("{}", summary);
+        }
+
+        if let Some(path) = args.manifest
+            && let Err(e) = manifest.write(&path)
+        {
+            report(Report::new("io", e));
+        }
+        if let Some(path) = &args.summary_json
+            && let Err(e) = summary.write(path)
+        {
+            report(Report::new("io", e));
+        }
+        if summary.failed > 0 && timed_out(deadline) {
+            report(timeout_report(
+                args.timeout_total,
+                format!(
+                    "downloaded {} of {} files",
+                    summary.downloaded,
+                    selected.len()
+                ),
+            ));
+            return ExitCode::from(EXIT_TIMEOUT);
+        }
+        batch_status(&summary)
+    };
+
+    match failed {
+        true => ExitCode::FAILURE,
+        false => status,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Args, BatchSummary, ColorChoice, EXIT_EMPTY, ErrorFormat, ReleaseGroups, Report, Selection,
+        SortKey, batch_status, by_release, create_output_dir, dated_listing, derive_filename,
+        exit_status, explain_line, grouped_lines, humanize_age, latest_editorials, list_line,
+        only_specs, output_dir, parse_day, parse_interval, parse_major_minor,
+        parse_output_template, parse_size_arg, read_spec_refs, retry, retry_unstarted, select,
+        sized, sorted, source_tz, spec_info, split_newer_than, timed_out, timeout_report,
+        url_lines, version_lines, with_change_requests,
+    };
+    use chrono::{TimeZone, Utc};
+    use clap::Parser;
+    use get_3gpp_spec::{
+        DateFormat, Download, DownloadStatus, DropReason, Explanation, ListQuery, SpecItem,
+        Version, parse_spec_number, parse_spec_ref,
+    };
+    use std::path::{Path, PathBuf};
+    use std::process::ExitCode;
+    use std::time::{Duration, Instant};
+
+    /// Item of 23.501 with the given version, served from example.com.
+    fn item(major: u32, minor: u32, editorial: u32) -> SpecItem {
+        let version = Version {
+            major,
+            minor,
+            editorial,
+        };
+        SpecItem {
+            spec: parse_spec_number("23.501").unwrap(),
+            version: Some(version),
+            release: None,
+            date: Utc::now(),
+            url: format!("https://example.com/23501-{}.zip", version),
+            filename: String::new(),
+            draft: false,
+            size: None,
+        }
+    }
+
+    #[test]
+    fn fail_on_empty_exit_status() {
+        let items = vec![item(16, 4, 0)];
+
+        assert_eq!(exit_status(&[], true), ExitCode::from(EXIT_EMPTY));
+        assert_eq!(exit_status(&items, true), ExitCode::SUCCESS);
+        assert_eq!(exit_status(&[], false), ExitCode::SUCCESS);
+        assert_eq!(exit_status(&items, false), ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn listing_with_date_format() {
+        let items = vec![item(16, 4, 0)];
+        let rfc3339 = dated_listing(&items, None).unwrap();
+        assert_eq!(rfc3339, serde_json::to_value(&items).unwrap());
+        let dates: DateFormat = "%d.%m.%Y".parse().unwrap();
+        let custom = dated_listing(&items, Some(&dates)).unwrap();
+        assert_eq!(custom[0]["date"], dates.format(&items[0].date));
+        assert_eq!(custom[0]["url"], rfc3339[0]["url"]);
+    }
+
+    #[test]
+    fn partial_batch_exit_status() {
+        let mut summary = BatchSummary::default();
+        let dest = Path::new("23501-g40.zip");
+        let download = Download {
+            path: dest.to_path_buf(),
+            size: 4,
+            sha256: String::new(),
+            last_modified: None,
+        };
+        summary.record_item("https://example.com/23501-g40.zip", dest, &Ok(download));
+        assert_eq!(batch_status(&summary), ExitCode::SUCCESS);
+        summary.record_item(
+            "https://example.com/23501-g50.zip",
+            Path::new("23501-g50.zip"),
+            &Err("HTTP 404".to_string()),
+        );
+        assert_eq!(batch_status(&summary), ExitCode::FAILURE);
+        let statuses: Vec<_> = summary.items.iter().map(|i| i.status).collect();
+        assert_eq!(statuses, [DownloadStatus::Ok, DownloadStatus::Failed]);
+        assert_eq!(summary.downloaded, 1);
+    }
+
+    #[test]
+    fn url_only_output() {
+        let items = vec![item(15, 9, 0), item(16, 3, 0), item(16, 4, 0)];
+        assert_eq!(
+            url_lines(&select(&items, Selection::Latest)),
+            "https://example.com/23501-16.4.0.zip\n"
+        );
+        assert_eq!(
+            url_lines(&select(&items, Selection::All)),
+            "https://example.com/23501-15.9.0.zip\n\
+             https://example.com/23501-16.3.0.zip\n\
+             https://example.com/23501-16.4.0.zip\n"
+        );
+        assert_eq!(url_lines(&select(&[], Selection::All)), "");
+    }
+
+    #[test]
+    fn source_tz_from_env() {
+        let env = |value: &'static str| {
+            move |name: &str| (name == "GET_3GPP_SOURCE_TZ").then(|| value.to_string())
+        };
+        assert_eq!(source_tz(None, |_| None).unwrap(), None);
+        assert_eq!(source_tz(None, env("")).unwrap(), None);
+
+        let query = ListQuery {
+            source_tz: source_tz(None, env("Europe/Paris")).unwrap(),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let listed = Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap();
+        assert_eq!(
+            query.listing_date(listed),
+            Utc.with_ymd_and_hms(2020, 3, 27, 13, 30, 0).unwrap()
+        );
+        let flag = source_tz(Some("UTC"), env("Europe/Paris"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(flag.name(), "UTC");
+
+        let err = source_tz(None, env("Mars/Olympus_Mons")).unwrap_err();
+        assert!(
+            err.starts_with("GET_3GPP_SOURCE_TZ: unknown timezone 'Mars/Olympus_Mons'"),
+            "{}",
+            err
+        );
+        assert!(source_tz(Some("Europe/Pariss"), |_| None).is_err());
+    }
+
+    #[test]
+    fn output_dir_from_env() {
+        let base = std::env::temp_dir().join(format!("get-3gpp-output-dir-{}", std::process::id()));
+        let from_env = base.join("env");
+        let env = |name: &str| {
+            (name == "GET_3GPP_OUTPUT_DIR").then(|| from_env.to_string_lossy().into_owned())
+        };
+        let resolve = |argv: &[&str]| {
+            let args = Args::try_parse_from(argv).unwrap();
+            output_dir(args.output_dir.as_deref(), args.output.as_deref(), env)
+        };
+
+        let dir = resolve(&["get-3gpp-spec", "23.501"]).unwrap();
+        assert_eq!(dir, from_env);
+        create_output_dir(&dir).unwrap();
+        assert!(from_env.is_dir());
+        let dest = derive_filename(&item(16, 4, 0), None, &dir);
+        assert_eq!(dest, from_env.join("23501-16.4.0.zip"));
+
+        let flag = base.join("flag");
+        let flag_arg = flag.to_str().unwrap();
+        assert_eq!(
+            resolve(&["get-3gpp-spec", "23.501", "--output-dir", flag_arg]),
+            Some(flag.clone())
+        );
+        assert_eq!(resolve(&["get-3gpp-spec", "23.501", "-o", "x.zip"]), None);
+        assert_eq!(output_dir(None, None, |_| Some(String::new())), None);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn latest_editorial_selection() {
+        assert_eq!(parse_major_minor("16.4"), Ok((16, 4)));
+        assert!(parse_major_minor("16").is_err());
+        assert!(parse_major_minor("16.4.1").is_err());
+
+        let mut other = item(16, 4, 7);
+        other.spec = parse_spec_number("24.501").unwrap();
+        let items = vec![
+            item(16, 3, 9),
+            item(16, 4, 0),
+            item(16, 4, 2),
+            item(16, 5, 0),
+            other.clone(),
+        ];
+        let urls: Vec<String> = latest_editorials(&items, (16, 4))
+            .into_iter()
+            .map(|i| i.url)
+            .collect();
+        assert_eq!(urls, [items[2].url.clone(), other.url]);
+        assert_eq!(latest_editorials(&items, (17, 0)), vec![]);
+    }
+
+    #[test]
+    fn download_cutoff_date() {
+        assert!(parse_day("2024-13-01").is_err());
+        assert!(parse_day("2024-01").is_err());
+        let day = parse_day("2024-01-31").unwrap();
+
+        let dated = |date: &str| SpecItem {
+            date: chrono::DateTime::parse_from_rfc3339(date).unwrap().into(),
+            url: format!("https://example.com/{}.zip", date),
+            ..item(16, 4, 0)
+        };
+        let items = [
+            dated("2023-12-01T10:00:00Z"),
+            dated("2024-01-30T23:59:59Z"),
+            dated("2024-01-31T00:00:00Z"),
+            dated("2024-01-31T08:15:00Z"),
+            dated("2024-06-01T12:00:00Z"),
+        ];
+        let (newer, older) = split_newer_than(items.iter().collect(), day);
+        assert_eq!(newer, [&items[3], &items[4]]);
+        assert_eq!(older, [&items[0], &items[1], &items[2]]);
+    }
+
+    #[test]
+    fn merged_specs_filtered_by_number() {
+        // The merged results of 23.501, 23.502 and 38.501.
+        let mut other = item(17, 1, 0);
+        other.spec = parse_spec_number("23.502").unwrap();
+        let mut ran = item(15, 0, 0);
+        ran.spec = parse_spec_number("38.501").unwrap();
+        let items = vec![item(16, 4, 0), other.clone(), item(17, 0, 0), ran.clone()];
+
+        let only = |series, number| only_specs(items.clone(), series, number);
+        assert_eq!(only(None, Some("502")), [other.clone()]);
+        assert_eq!(only(None, Some("501")).len(), 3);
+        assert_eq!(only(Some("38"), None), [ran.clone()]);
+        assert_eq!(
+            only(Some("23"), Some("501")),
+            [items[0].clone(), items[2].clone()]
+        );
+        assert_eq!(only(Some("38"), Some("331")), []);
+        assert_eq!(only(None, None), items);
+    }
+
+    #[test]
+    fn size_filters() {
+        let sizes = [Some(2048), None, Some(5 * 1024 * 1024), Some(512 * 1024)];
+        let items: Vec<_> = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| SpecItem {
+                size,
+                ..item(16, i as u32, 0)
+            })
+            .collect();
+        let kept = |min, max, include_unsized| {
+            sized(items.clone(), min, max, include_unsized)
+                .iter()
+                .map(|item| item.size)
+                .collect::<Vec<_>>()
+        };
+        let min = parse_size_arg("500K").ok();
+        let max = parse_size_arg("1MB").ok();
+        assert_eq!(
+            kept(min, None, false),
+            [Some(5 * 1024 * 1024), Some(512 * 1024)]
+        );
+        assert_eq!(kept(None, max, false), [Some(2048), Some(512 * 1024)]);
+        assert_eq!(kept(min, max, false), [Some(512 * 1024)]);
+        assert_eq!(kept(min, max, true), [None, Some(512 * 1024)]);
+        assert_eq!(kept(None, None, false), sizes);
+        assert!(parse_size_arg("big").is_err());
+
+        let args = Args::try_parse_from([
+            "get-3gpp-spec",
+            "23.501",
+            "--min-size",
+            "1.5M",
+            "--max-size-listed",
+            "10 MB",
+        ])
+        .unwrap();
+        assert_eq!(args.min_size, Some(1536 * 1024));
+        assert_eq!(args.max_size_listed, Some(10 * 1024 * 1024));
+    }
+
+    #[test]
+    fn size_sort() {
+        let sizes = [Some(3000), None, Some(1000), Some(3000), None];
+        let items: Vec<_> = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| SpecItem {
+                size,
+                ..item(16, i as u32, 0)
+            })
+            .collect();
+        let minors = |key| {
+            sorted(&items, key)
+                .iter()
+                .map(|item| item.version.unwrap().minor)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(minors(SortKey::Version), [0, 1, 2, 3, 4]);
+        // Unknown sizes first, and ties in canonical order.
+        assert_eq!(minors(SortKey::Size), [1, 4, 2, 0, 3]);
+    }
+
+    #[test]
+    fn grouped_by_release() {
+        let mut named = item(15, 9, 0);
+        named.release = Some(16);
+        let unversioned = SpecItem {
+            version: None,
+            ..item(0, 0, 0)
+        };
+        let items = vec![
+            unversioned,
+            item(15, 1, 0),
+            named,
+            item(16, 0, 0),
+            item(16, 4, 1),
+            item(17, 2, 0),
+        ];
+
+        let groups: Vec<(Option<u32>, Vec<String>)> = by_release(&items)
+            .into_iter()
+            .map(|(release, group)| {
+                let versions = group
+                    .iter()
+                    .map(|item| item.version.map_or("-".to_string(), |v| v.to_string()))
+                    .collect();
+                (release, versions)
+            })
+            .collect();
+        let versions = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [
+                (Some(17), versions(&["17.2.0"])),
+                (Some(16), versions(&["15.9.0", "16.0.0", "16.4.1"])),
+                (Some(15), versions(&["15.1.0"])),
+                (None, versions(&["-"])),
+            ]
+        );
+
+        let json = serde_json::to_string(&ReleaseGroups::new(&items, None).unwrap()).unwrap();
+        let keys: Vec<_> = ["\"17\"", "\"16\"", "\"15\"", "\"unversioned\""]
+            .iter()
+            .map(|key| json.find(key).unwrap())
+            .collect();
+        assert!(keys.is_sorted(), "{}", json);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["16"].as_array().unwrap().len(), 3);
+        assert_eq!(value["16"][0]["release"], 16);
+        assert_eq!(value["16"][1]["version"]["minor"], 0);
+
+        let text = grouped_lines(&items, |item| item.url.clone());
+        assert_eq!(
+            text,
+            "Release 17:\n\
+             https://example.com/23501-17.2.0.zip\n\
+             \n\
+             Release 16:\n\
+             https://example.com/23501-15.9.0.zip\n\
+             https://example.com/23501-16.0.0.zip\n\
+             https://example.com/23501-16.4.1.zip\n\
+             \n\
+             Release 15:\n\
+             https://example.com/23501-15.1.0.zip\n\
+             \n\
+             Unversioned:\n\
+             https://example.com/23501-0.0.0.zip\n"
+        );
+    }
+
+    #[test]
+    fn spec_info_lines() {
+        let query = ListQuery::new(parse_spec_number("23.501").unwrap());
+        assert_eq!(
+            spec_info(&query).unwrap(),
+            "spec:    23.501\n\
+             series:  23\n\
+             number:  501\n\
+             title:   Technical realization (\"stage 2\")\n\
+             listing: https://www.3gpp.org/ftp/Specs/archive/23_series/23.501/"
+        );
+
+        let query = ListQuery::from(parse_spec_ref("99.001 Rel-16").unwrap());
+        let info = spec_info(&query).unwrap();
+        assert!(info.contains("title:   -\nrelease: 16\n"), "{}", info);
+    }
+
+    #[test]
+    fn explain_lines() {
+        let spec = parse_spec_number("23.501").unwrap();
+        let explanation = |kept, reason| Explanation {
+            filename: "23501-g40.zip".to_string(),
+            url: "https://example.com/23501-g40.zip".to_string(),
+            version: None,
+            date: None,
+            kept,
+            reason,
+        };
+        assert_eq!(
+            explain_line(&spec, &explanation(true, None)),
+            "23.501   kept    -                23501-g40.zip"
+        );
+        assert_eq!(
+            explain_line(
+                &spec,
+                &explanation(false, Some(DropReason::UnparseableDate))
+            ),
+            "23.501   dropped unparseable-date 23501-g40.zip"
+        );
+    }
+
+    #[test]
+    fn list_versions_output() {
+        let mut items = vec![
+            item(15, 9, 0),
+            item(16, 3, 0),
+            item(16, 4, 0),
+            item(16, 10, 1),
+            item(16, 4, 0),
+        ];
+        items.push(SpecItem {
+            version: None,
+            ..item(0, 0, 0)
+        });
+        assert_eq!(version_lines(&items), "16.10.1\n16.4.0\n16.3.0\n15.9.0\n");
+        assert_eq!(version_lines(&[]), "");
+    }
+
+    #[test]
+    fn select_highest_of_each_spec() {
+        let mut other = item(17, 1, 0);
+        other.spec = parse_spec_number("24.501").unwrap();
+        let mut unversioned = other.clone();
+        unversioned.spec = parse_spec_number("38.413").unwrap();
+        unversioned.version = None;
+        let mut items = vec![item(15, 9, 0), item(16, 4, 0), item(18, 0, 0), other];
+        items.insert(
+            0,
+            SpecItem {
+                version: None,
+                ..item(0, 0, 0)
+            },
+        );
+        items.push(unversioned);
+
+        let selected = |selection| -> Vec<_> {
+            select(&items, selection)
+                .iter()
+                .map(|i| format!("{} {}", i.spec, i.version.unwrap()))
+                .collect()
+        };
+        assert_eq!(
+            selected(Selection::Latest),
+            ["23.501 18.0.0", "24.501 17.1.0"]
+        );
+        assert_eq!(
+            selected(Selection::Oldest),
+            ["23.501 15.9.0", "24.501 17.1.0"]
+        );
+        assert_eq!(select(&items, Selection::All).len(), 6);
+    }
+
+    #[test]
+    fn selection_modes() {
+        // Several versions of one spec, one of them uploaded twice.
+        let reupload = SpecItem {
+            date: Utc::now() + chrono::Duration::days(1),
+            ..item(16, 4, 0)
+        };
+        let items = vec![
+            item(15, 9, 0),
+            item(16, 3, 0),
+            item(16, 4, 0),
+            reupload.clone(),
+            item(17, 0, 0),
+        ];
+        assert_eq!(select(&items, Selection::Latest), [&items[4]]);
+        assert_eq!(select(&items, Selection::Oldest), [&items[0]]);
+        assert_eq!(select(&items, Selection::All).len(), 5);
+
+        let items = &items[1..4];
+        assert_eq!(select(items, Selection::Latest), [&reupload]);
+        assert_eq!(select(items, Selection::Oldest), [&items[0]]);
+
+        let args =
+            |cli: &[&str]| Args::try_parse_from([&["get-3gpp-spec", "23.501"], cli].concat());
+        assert_eq!(args(&[]).unwrap().select, Selection::Latest);
+        assert_eq!(
+            args(&["--select", "oldest"]).unwrap().select,
+            Selection::Oldest
+        );
+        assert!(args(&["--all", "--select", "oldest"]).is_err());
+    }
+
+    #[test]
+    fn change_requests_follow_selection() {
+        let crs = SpecItem {
+            version: None,
+            url: "https://example.com/23501_CRs.zip".to_string(),
+            ..item(0, 0, 0)
+        };
+        let mut other = item(17, 1, 0);
+        other.spec = parse_spec_number("24.501").unwrap();
+        let items = vec![crs, item(16, 4, 0), other];
+
+        let urls = |selected: Vec<&SpecItem>| -> Vec<String> {
+            selected.iter().map(|i| i.url.clone()).collect()
+        };
+        assert_eq!(
+            urls(with_change_requests(
+                &items,
+                select(&items, Selection::Latest)
+            )),
+            [
+                "https://example.com/23501-16.4.0.zip",
+                "https://example.com/23501-17.1.0.zip",
+                "https://example.com/23501_CRs.zip"
+            ]
+        );
+        assert_eq!(
+            with_change_requests(&items, select(&items, Selection::All)).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn timeout_exit() {
+        assert!(!timed_out(None));
+        assert!(timed_out(Some(Instant::now())));
+        assert!(!timed_out(Some(Instant::now() + Duration::from_secs(60))));
+        let report = timeout_report(
+            Some(Duration::from_secs(5)),
+            "downloaded 1 of 3 files".into(),
+        );
+        assert_eq!(report.kind, "timeout");
+        assert_eq!(
+            report.message,
+            "total timeout of 5s exceeded: downloaded 1 of 3 files"
+        );
+    }
+
+    #[test]
+    fn retry_until_success_or_limit() {
+        let mut calls = 0;
+        let result = retry(
+            2,
+            |_: &String| true,
+            || {
+                calls += 1;
+                match calls {
+                    3 => Ok(calls),
+                    _ => Err("transient".to_string()),
+                }
+            },
+        );
+        assert_eq!(result, Ok(3));
+
+        let mut calls = 0;
+        let result: Result<(), String> = retry(
+            5,
+            |e: &String| e != "permanent",
+            || {
+                calls += 1;
+                Err("permanent".to_string())
+            },
+        );
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn output_retried_until_written() {
+        use std::io::Write;
+
+        // A failure before the first byte is retried.
+        let mut calls = 0;
+        let mut out = Vec::new();
+        let result = retry_unstarted(2, &mut out, |out| {
+            calls += 1;
+            match calls {
+                1 => Err("connection refused".to_string()),
+                _ => out
+                    .write_all(b"PK\x03\x04")
+                    .map(|()| 4)
+                    .map_err(|e| e.to_string()),
+            }
+        });
+        assert_eq!(result, Ok(4));
+        assert_eq!(calls, 2);
+        assert_eq!(out, b"PK\x03\x04");
+
+        // Once part of the body is out, a failure is final rather than writing it again.
+        let mut calls = 0;
+        let mut out = Vec::new();
+        let result = retry_unstarted(2, &mut out, |out| {
+            calls += 1;
+            out.write_all(b"PK\x03\x04").unwrap();
+            Err("connection dropped".to_string())
+        });
+        assert_eq!(result, Err("connection dropped".to_string()));
+        assert_eq!(calls, 1);
+        assert_eq!(out, b"PK\x03\x04");
+    }
+
+    #[test]
+    fn default_filename() {
+        let item = item(16, 4, 0);
+        assert_eq!(
+            derive_filename(&item, None, Path::new("")),
+            PathBuf::from("23501-16.4.0.zip")
+        );
+        assert_eq!(
+            derive_filename(&item, None, Path::new("specs")),
+            Path::new("specs").join("23501-16.4.0.zip")
+        );
+        let bare = SpecItem {
+            url: "https://example.com/".to_string(),
+            ..item.clone()
+        };
+        assert_eq!(
+            derive_filename(&bare, None, Path::new("")),
+            PathBuf::from("download.bin")
+        );
+        let encoded = SpecItem {
+            url: "https://example.com/23501%20g40%20(clean).zip".to_string(),
+            ..item.clone()
+        };
+        assert_eq!(
+            derive_filename(&encoded, None, Path::new("")),
+            PathBuf::from("23501 g40 (clean).zip")
+        );
+        let traversal = SpecItem {
+            url: "https://example.com/..%2F23501-g40.zip".to_string(),
+            ..item
+        };
+        assert_eq!(
+            derive_filename(&traversal, None, Path::new("")),
+            PathBuf::from("..%2F23501-g40.zip")
+        );
+    }
+
+    #[test]
+    fn templated_filename() {
+        let mut item = item(16, 4, 0);
+        item.date = Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap();
+        let template = parse_output_template("{series}/{spec}-{version}_{date}.zip").unwrap();
+        assert_eq!(
+            derive_filename(&item, Some(&template), Path::new("out")),
+            Path::new("out").join("23/23.501-16.4.0_2020-03-27.zip")
+        );
+        assert_eq!(
+            derive_filename(&item, Some("{spec}/{filename}"), Path::new("")),
+            Path::new("23.501/23501-16.4.0.zip")
+        );
+        item.version = None;
+        assert_eq!(
+            derive_filename(&item, Some("{version}"), Path::new("")),
+            PathBuf::from("unversioned")
+        );
+
+        let err = parse_output_template("{spec}-{release}.zip").unwrap_err();
+        assert!(err.contains("unknown placeholder '{release}'"), "{}", err);
+        assert!(parse_output_template("{spec").is_err());
+        assert!(parse_output_template("plain.zip").is_ok());
+    }
+
+    #[test]
+    fn humanized_age() {
+        let age = |minutes| humanize_age(chrono::Duration::minutes(minutes));
+        assert_eq!(age(-5), "in the future");
+        assert_eq!(age(0), "just now");
+        assert_eq!(age(1), "1 minute ago");
+        assert_eq!(age(59), "59 minutes ago");
+        assert_eq!(age(60), "1 hour ago");
+        assert_eq!(age(23 * 60 + 59), "23 hours ago");
+        let days = |days| humanize_age(chrono::Duration::days(days));
+        assert_eq!(days(1), "1 day ago");
+        assert_eq!(days(3), "3 days ago");
+        assert_eq!(days(59), "59 days ago");
+        assert_eq!(days(60), "2 months ago");
+        assert_eq!(days(729), "24 months ago");
+        assert_eq!(days(730), "2 years ago");
+
+        let mut item = item(16, 4, 0);
+        item.date = Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2020, 3, 30, 16, 0, 0).unwrap();
+        let dates = DateFormat::date_only();
+        assert_eq!(
+            list_line(&item, &dates, Some(now), false, false),
+            "23.501 16. 4. 0 @ 2020-03-27, 3 days ago (https://example.com/23501-16.4.0.zip)"
+        );
+        assert_eq!(
+            list_line(&item, &dates, None, false, false),
+            item.to_string_with(&dates)
+        );
+    }
+
+    #[test]
+    fn color_choice() {
+        let unset = || None;
+        let set = || Some("1".into());
+        assert!(!ColorChoice::Never.enabled(true, unset()));
+        assert!(!ColorChoice::Auto.enabled(false, unset()));
+        assert!(ColorChoice::Auto.enabled(true, unset()));
+        assert!(ColorChoice::Auto.enabled(true, Some("".into())));
+        assert!(!ColorChoice::Auto.enabled(true, set()));
+        assert!(ColorChoice::Always.enabled(false, set()));
+
+        let item = item(16, 4, 0);
+        let dates = DateFormat::date_only();
+        let plain = list_line(
+            &item,
+            &dates,
+            None,
+            true,
+            ColorChoice::Never.enabled(true, unset()),
+        );
+        assert!(!plain.contains('\x1b'), "{:?}", plain);
+        assert_eq!(plain, item.to_string_with(&dates));
+        let piped = list_line(
+            &item,
+            &dates,
+            None,
+            true,
+            ColorChoice::Auto.enabled(false, unset()),
+        );
+        assert!(!piped.contains('\x1b'), "{:?}", piped);
+
+        let colored = list_line(&item, &dates, None, true, true);
+        assert!(
+            colored.starts_with("23.501 \x1b[1;32m16. 4. 0\x1b[0m @ "),
+            "{:?}",
+            colored
+        );
+        assert_eq!(
+            list_line(&item, &dates, None, false, true),
+            item.to_string_with(&dates)
+        );
+
+        let draft = SpecItem {
+            version: Some(Version {
+                major: 2,
+                minor: 1,
+                editorial: 0,
+            }),
+            ..item
+        };
+        assert!(
+            list_line(&draft, &dates, None, false, false).starts_with("23.501  2. 1. 0* @ "),
+            "{:?}",
+            list_line(&draft, &dates, None, false, false)
+        );
+        assert!(
+            list_line(&draft, &dates, None, true, true)
+                .starts_with("23.501 \x1b[1;32m 2. 1. 0*\x1b[0m @ "),
+            "{:?}",
+            list_line(&draft, &dates, None, true, true)
+        );
+    }
+
+    #[test]
+    fn watch_intervals() {
+        assert_eq!(parse_interval("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_interval("15min"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_interval("6h"), Ok(Duration::from_secs(21600)));
+        assert_eq!(parse_interval("1d"), Ok(Duration::from_secs(86400)));
+        for invalid in ["", "0", "h", "5m", "1.5h", "-1s"] {
+            assert!(parse_interval(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn spec_refs_from_stdin() {
+        let input = "23.501\n\n# core network\n24.501 Rel-16\nnot-a-spec\n  38.413  \n";
+        let refs = read_spec_refs(input.as_bytes(), parse_spec_ref);
+        assert_eq!(refs.len(), 4);
+        let specs: Vec<_> = refs
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .map(|r| r.spec_number.to_string())
+            .collect();
+        assert_eq!(specs, ["23.501", "24.501", "38.413"]);
+        assert_eq!(refs[1].as_ref().unwrap().release, Some(16));
+        let err = refs[2].as_ref().unwrap_err();
+        assert!(err.starts_with("stdin line 5: "), "{}", err);
+
+        assert!(read_spec_refs("".as_bytes(), parse_spec_ref).is_empty());
+    }
+
+    #[test]
+    fn json_error_report() {
+        // Nothing listens on a port that was just released, so the connection is refused.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let query = ListQuery {
+            base_url: format!("http://127.0.0.1:{}/", port),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let err = get_3gpp_spec::list(&reqwest::blocking::Client::new(), &query).unwrap_err();
+
+        let json = Report::from_error(&err).render(ErrorFormat::Json);
+        assert!(!json.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["kind"], "connect");
+        let url = format!("http://127.0.0.1:{}/23_series/23.501/", port);
+        assert_eq!(value["url"], url.as_str());
+        assert!(
+            value["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("cannot connect to '127.0.0.1'")
+        );
+        assert!(value.get("status").is_none());
+        assert!(value.get("spec").is_none());
+
+        let report = Report {
+            spec: Some("23.501".to_string()),
+            status: Some(404),
+            ..Report::new("status", "not found")
+        };
+        assert_eq!(report.render(ErrorFormat::Text), "23.501: not found");
+        let value: serde_json::Value =
+            serde_json::from_str(&report.render(ErrorFormat::Json)).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "kind": "status",
+                "message": "not found",
+                "spec": "23.501",
+                "status": 404
+            })
+        );
     }
 }
@@ -0,0 +1,196 @@
+use crate::SpecItem;
+use crate::download::{Download, DownloadOptions, download_verified};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Record of one downloaded spec item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub spec: String,
+    /// Version of the item, empty if its filename carries none.
+    pub version: String,
+    pub date: DateTime<Utc>,
+    pub url: String,
+    /// Local path the file was written to.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl ManifestEntry {
+    pub fn new(item: &SpecItem, download: &Download) -> Self {
+        ManifestEntry {
+            spec: item.spec.to_string(),
+            version: item.version.map(|v| v.to_string()).unwrap_or_default(),
+            date: item.date,
+            url: item.url.clone(),
+            path: download.path.display().to_string(),
+            size: download.size,
+            sha256: download.sha256.clone(),
+        }
+    }
+
+    /// Check that `download` has the size and SHA-256 digest recorded in this entry.
+    pub fn verify(&self, download: &Download) -> Result<(), String> {
+        if download.size != self.size {
+            return Err(format!(
+                "size mismatch for '{}': expected {} bytes, got {}",
+                self.url, self.size, download.size
+            ));
+        }
+        if !download.sha256.eq_ignore_ascii_case(&self.sha256) {
+            return Err(format!(
+                "SHA-256 mismatch for '{}': expected {}, got {}",
+                self.url, self.sha256, download.sha256
+            ));
+        }
+        Ok(())
+    }
+
+    /// Download this entry's URL to `dest` again and verify it (see `verify`), for `--spec-file`.
+    /// A file that fails verification is left as the `.part` file and never written to `dest`.
+    pub fn fetch(
+        &self,
+        client: &Client,
+        dest: &Path,
+        options: &DownloadOptions,
+    ) -> Result<Download, String> {
+        download_verified(client, &self.url, dest, options, |download| {
+            self.verify(download)
+        })
+    }
+}
+
+/// JSON record of the files fetched by one invocation, written by `--manifest`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Write the manifest to `path` as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize manifest: {}", e))?;
+        std::fs::write(path, json)
+            .map_err(|e| format!("failed to write manifest '{}': {}", path.display(), e))
+    }
+
+    /// Read a manifest previously written by `write`.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read manifest '{}': {}", path.display(), e))?;
+        serde_json::from_str(&json)
+            .map_err(|e| format!("invalid manifest '{}': {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Manifest, ManifestEntry};
+    use crate::download::{DownloadOptions, download_url_to_path, part_path};
+    use crate::sha256::sha256_hex;
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use crate::{SpecItem, Version, parse_spec_number};
+    use chrono::{TimeZone, Utc};
+    use reqwest::blocking::Client;
+
+    #[test]
+    fn manifest_records_download() {
+        let payload = b"PK\x03\x04manifest".to_vec();
+        let body = payload.clone();
+        let server = MockServer::start(move |_| Response::ok(body.clone()));
+        let dir = scratch_dir("manifest-records-download");
+
+        let item = SpecItem {
+            spec: parse_spec_number("23.501").unwrap(),
+            version: Some(Version {
+                major: 16,
+                minor: 4,
+                editorial: 0,
+            }),
+            release: None,
+            date: Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap(),
+            url: server.url("/23501-g40.zip"),
+            filename: String::new(),
+            draft: false,
+            size: None,
+        };
+        let download = download_url_to_path(
+            &Client::new(),
+            &item.url,
+            &dir.join("23501-g40.zip"),
+            &DownloadOptions::default(),
+        )
+        .unwrap();
+        let manifest = Manifest {
+            entries: vec![ManifestEntry::new(&item, &download)],
+        };
+        let path = dir.join("manifest.json");
+        manifest.write(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let entry = &json["entries"][0];
+        assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+        assert_eq!(entry["spec"], "23.501");
+        assert_eq!(entry["version"], "16.4.0");
+        assert_eq!(entry["date"], "2020-03-27T14:30:00Z");
+        assert_eq!(entry["url"], item.url);
+        assert_eq!(entry["size"], payload.len());
+        assert_eq!(entry["sha256"], sha256_hex(&payload));
+
+        assert_eq!(Manifest::read(&path).unwrap(), manifest);
+    }
+
+    #[test]
+    fn fetch_from_manifest() {
+        let served = std::sync::Arc::new(std::sync::Mutex::new(b"PK\x03\x04v1".to_vec()));
+        let body = served.clone();
+        let server = MockServer::start(move |_| Response::ok(body.lock().unwrap().clone()));
+        let dir = scratch_dir("manifest-fetch");
+        let client = Client::new();
+
+        let item = SpecItem {
+            spec: parse_spec_number("23.501").unwrap(),
+            version: Some(Version {
+                major: 16,
+                minor: 4,
+                editorial: 0,
+            }),
+            release: None,
+            date: Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap(),
+            url: server.url("/23501-g40.zip"),
+            filename: String::new(),
+            draft: false,
+            size: None,
+        };
+        let options = DownloadOptions::default();
+        let download =
+            download_url_to_path(&client, &item.url, &dir.join("first.zip"), &options).unwrap();
+        let path = dir.join("manifest.json");
+        Manifest {
+            entries: vec![ManifestEntry::new(&item, &download)],
+        }
+        .write(&path)
+        .unwrap();
+
+        let entry = &Manifest::read(&path).unwrap().entries[0];
+        let dest = dir.join("again.zip");
+        let again = entry.fetch(&client, &dest, &options).unwrap();
+        assert_eq!(again.sha256, download.sha256);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"PK\x03\x04v1");
+
+        *served.lock().unwrap() = b"PK\x03\x04v2".to_vec();
+        let err = entry.fetch(&client, &dest, &options).unwrap_err();
+        assert!(err.contains("SHA-256 mismatch"), "{}", err);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"PK\x03\x04v1");
+        assert_eq!(std::fs::read(part_path(&dest)).unwrap(), b"PK\x03\x04v2");
+
+        *served.lock().unwrap() = b"PK\x03\x04v1.1".to_vec();
+        let err = entry.fetch(&client, &dest, &options).unwrap_err();
+        assert!(err.contains("expected 6 bytes, got 8"), "{}", err);
+    }
+}
@@ -0,0 +1,245 @@
+//! Bounded parallelism for listing several spec directories, and downloading several files, at
+//! once.
+
+use crate::{Download, DownloadOptions, Error, ListQuery, SpecItem, download_url_to_path, list};
+use reqwest::blocking::Client;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Apply `f` to every input on up to `jobs` threads and return the results in input order.
+/// With `jobs` of 0 or 1, the inputs are processed one after another on the calling thread.
+pub fn parallel_map<T, R, F>(inputs: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = jobs.min(inputs.len());
+    if workers <= 1 {
+        return inputs.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(inputs.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(i) else { break };
+                    let result = f(input);
+                    results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .map(|r| r.expect("every input is processed"))
+        .collect()
+}
+
+/// List every query with `list`, fetching up to `jobs` spec directories in parallel.
+/// Returns one result per query, in the order of `queries`, so that a failure for one spec does
+/// not hide the items of the others.
+pub fn list_many(
+    client: &Client,
+    queries: &[ListQuery],
+    jobs: usize,
+) -> Vec<Result<Vec<SpecItem>, Error>> {
+    parallel_map(queries, jobs, |query| list(client, query))
+}
+
+/// Download every `(url, destination)` pair with `download_url_to_path`, up to `jobs` at a time.
+/// Returns one result per download, in the order of `downloads`. The pool is separate from the
+/// listing one, so downloads can be limited independently of `list_many`.
+pub fn download_many(
+    client: &Client,
+    downloads: &[(String, PathBuf)],
+    jobs: usize,
+    options: &DownloadOptions,
+) -> Vec<Result<Download, String>> {
+    parallel_map(downloads, jobs, |(url, dest)| {
+        download_url_to_path(client, url, dest, options)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{download_many, list_many, parallel_map};
+    use crate::test_support::{MockServer, Response, scratch_dir};
+    use crate::{DownloadOptions, Error, ListQuery, parse_spec_number};
+    use reqwest::blocking::Client;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn results_in_input_order_with_bounded_workers() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let inputs: Vec<u64> = (0..12).collect();
+
+        let results = parallel_map(&inputs, 3, |&n| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20 - n));
+            running.fetch_sub(1, Ordering::SeqCst);
+            n * 10
+        });
+
+        assert_eq!(results, inputs.iter().map(|n| n * 10).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 3);
+        assert_eq!(parallel_map(&[] as &[u64], 4, |&n| n), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn lists_several_specs() {
+        let listing = |name: &str| {
+            format!(
+                "<table><thead><tr><th>name</th><th>date</th></tr></thead><tbody>\
+                 <tr><td><a href=\"https://example.com/{0}\">{0}</a></td>\
+                 <td>2020/03/27 14:30</td></tr></tbody></table>",
+                name
+            )
+        };
+        let server = MockServer::start(move |req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(listing("23501-g40.zip")),
+            "/24_series/24.501/" => Response::ok(listing("24501-g50.zip")),
+            "/38_series/38.413/" => Response::ok(listing("38413-g60.zip")),
+            _ => Response::not_found(),
+        });
+        let queries: Vec<_> = ["23.501", "24.501", "29.500", "38.413"]
+            .iter()
+            .map(|spec| ListQuery {
+                base_url: server.url("/"),
+                ..ListQuery::new(parse_spec_number(spec).unwrap())
+            })
+            .collect();
+
+        let results = list_many(&Client::new(), &queries, 2);
+        assert_eq!(results.len(), 4);
+        let urls: Vec<_> = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .flatten()
+            .map(|item| item.url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/23501-g40.zip",
+                "https://example.com/24501-g50.zip",
+                "https://example.com/38413-g60.zip"
+            ]
+        );
+        assert!(matches!(
+            &results[2],
+            Err(Error::Status { status: 404, .. })
+        ));
+        assert_eq!(results[1].as_ref().unwrap()[0].spec, queries[1].spec_number);
+    }
+
+    #[test]
+    fn separate_limits_for_listing_and_downloads() {
+        use std::sync::Arc;
+
+        // In-flight and peak request counts, for listings and for downloads.
+        let counts: Arc<[AtomicUsize; 4]> = Arc::new(Default::default());
+        let server_counts = counts.clone();
+        let server = MockServer::start(move |req| {
+            let (running, peak) = match req.path.ends_with(".zip") {
+                false => (&server_counts[0], &server_counts[1]),
+                true => (&server_counts[2], &server_counts[3]),
+            };
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(30));
+            running.fetch_sub(1, Ordering::SeqCst);
+            match req.path.ends_with(".zip") {
+                false => Response::ok(
+                    "<table><thead><tr><th>name</th><th>date</th></tr></thead><tbody>\
+                     <tr><td><a href=\"x/23501-g40.zip\">23501-g40.zip</a></td>\
+                     <td>2020/03/27 14:30</td></tr></tbody></table>",
+                ),
+                true => Response::ok(b"PK\x03\x04spec".to_vec()),
+            }
+        });
+        let client = Client::new();
+
+        let queries: Vec<_> = (1..=9)
+            .map(|n| ListQuery {
+                base_url: server.url("/"),
+                ..ListQuery::new(parse_spec_number(&format!("23.50{}", n)).unwrap())
+            })
+            .collect();
+        let listed = list_many(&client, &queries, 3);
+        assert!(listed.iter().all(|r| r.is_ok()));
+
+        let dir = scratch_dir("parallel-download-limits");
+        let downloads: Vec<_> = (0..6)
+            .map(|n| {
+                let name = format!("{}.zip", n);
+                (server.url(&format!("/{}", name)), dir.join(name))
+            })
+            .collect();
+        let results = download_many(&client, &downloads, 2, &DownloadOptions::default());
+        assert_eq!(results.len(), 6);
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        assert!(counts[1].load(Ordering::SeqCst) <= 3);
+        assert!(counts[3].load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn total_timeout_aborts_batch() {
+        use std::time::Instant;
+
+        let server = MockServer::start(|req| {
+            std::thread::sleep(Duration::from_millis(300));
+            match req.path.ends_with(".zip") {
+                true => Response::ok(b"PK\x03\x04spec".to_vec()),
+                false => Response::not_found(),
+            }
+        });
+        let client = Client::new();
+        let dir = scratch_dir("parallel-total-timeout");
+        let downloads: Vec<_> = (0..4)
+            .map(|n| {
+                let name = format!("{}.zip", n);
+                (server.url(&format!("/{}", name)), dir.join(name))
+            })
+            .collect();
+
+        let started = Instant::now();
+        let options = DownloadOptions {
+            deadline: Some(started + Duration::from_millis(450)),
+            ..DownloadOptions::default()
+        };
+        let results = download_many(&client, &downloads, 1, &options);
+        assert!(started.elapsed() < Duration::from_millis(900));
+        assert!(results[0].is_ok());
+        for (result, (url, dest)) in results[1..].iter().zip(&downloads[1..]) {
+            let err = result.as_ref().unwrap_err();
+            assert_eq!(
+                *err,
+                format!("total timeout exceeded while fetching '{}'", url)
+            );
+            assert!(!dest.exists());
+        }
+
+        let query = ListQuery {
+            base_url: server.url("/"),
+            deadline: Some(Instant::now() + Duration::from_millis(100)),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let results = list_many(&client, std::slice::from_ref(&query), 1);
+        assert!(
+            matches!(&results[0], Err(e @ Error::Timeout { .. }) if e.kind() == "timeout"),
+            "{:?}",
+            results[0]
+        );
+    }
+}
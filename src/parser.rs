@@ -0,0 +1,269 @@
+//! Parsers extracting the rows of listing pages, one per kind of mirror, as chosen with
+//! `--mirror-kind`. Mirrors hosted elsewhere than the 3GPP archive may lay out their listings
+//! differently, e.g. as a plain list of links rather than a table.
+
+use crate::{
+    Error, LINKS, ListingRow, SpecNumber, href_filename, listing_rows, parse_size, resolve_href,
+};
+use scraper::{Html, Selector};
+use std::sync::LazyLock;
+
+/// Extraction of the rows of a listing page.
+pub trait ListingParser {
+    /// Extract the rows with an anchor from the listing page `body` of the directory of `spec`,
+    /// served from `url`, in document order. A page without any row is an
+    /// `Error::EmptyDirectory`.
+    fn parse(
+        &self,
+        body: &str,
+        url: &reqwest::Url,
+        spec: &SpecNumber,
+    ) -> Result<Vec<ListingRow>, Error>;
+}
+
+/// Parser of the listing tables of the 3GPP archive: a `<thead>` naming the `name`, `date` and
+/// optionally `size` columns, and a `<tbody>` row per file or directory linked from its name
+/// cell. With `lenient`, the columns are guessed for tables without such headers (see
+/// `guess_header_indexes`). Hrefs are kept as written, as the archive links absolutely.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreeGppFtpParser {
+    pub lenient: bool,
+}
+
+impl ListingParser for ThreeGppFtpParser {
+    fn parse(
+        &self,
+        body: &str,
+        _url: &reqwest::Url,
+        spec: &SpecNumber,
+    ) -> Result<Vec<ListingRow>, Error> {
+        listing_rows(body, spec, self.lenient)
+    }
+}
+
+static LIST_ITEMS: LazyLock<Selector> = LazyLock::new(|| crate::selector("li"));
+
+/// Parser of directory indexes listing each file as an item of a `<ul>`: the link followed by
+/// the date and optionally the size, e.g.
+/// `<li><a href="23501-g40.zip">23501-g40.zip</a> 2020-03-27 14:30 1.2M</li>`. Items without a
+/// link are skipped. Such indexes usually link relatively, so hrefs are resolved against the
+/// listing URL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectoryIndexParser;
+
+impl ListingParser for DirectoryIndexParser {
+    fn parse(
+        &self,
+        body: &str,
+        url: &reqwest::Url,
+        spec: &SpecNumber,
+    ) -> Result<Vec<ListingRow>, Error> {
+        let document = Html::parse_document(body);
+        let mut rows = Vec::new();
+        for item in document.select(&LIST_ITEMS) {
+            let Some(anchor) = item.select(&LINKS).next() else {
+                continue;
+            };
+            // The text of the item outside its link: the date, then the size if any.
+            let rest: Vec<&str> = item
+                .descendants()
+                .filter(|node| !node.ancestors().any(|a| a.id() == anchor.id()))
+                .filter_map(|node| node.value().as_text())
+                .flat_map(|text| text.split_whitespace())
+                .collect();
+            let (date, size) = match rest.split_last() {
+                Some((last, date)) if !date.is_empty() && parse_size(last).is_some() => {
+                    (date.join(" "), parse_size(last))
+                }
+                _ => (rest.join(" "), None),
+            };
+            let href = anchor.value().attr("href").unwrap_or("");
+            rows.push(ListingRow {
+                name: href_filename(href).unwrap_or_else(|| anchor.text().collect()),
+                href: resolve_href(url, href),
+                date,
+                size,
+                release: None,
+            });
+        }
+        if rows.is_empty() {
+            return Err(Error::EmptyDirectory(spec.clone()));
+        }
+        Ok(rows)
+    }
+}
+
+/// Layout of the listing pages of a mirror, selecting the `ListingParser` used to read them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MirrorKind {
+    /// Tables of the 3GPP archive, read with `ThreeGppFtpParser`.
+    #[default]
+    ThreeGpp,
+    /// Lists of links, read with `DirectoryIndexParser`.
+    DirectoryIndex,
+}
+
+impl MirrorKind {
+    pub const ALL: [MirrorKind; 2] = [MirrorKind::ThreeGpp, MirrorKind::DirectoryIndex];
+
+    /// Name of the kind, as given to `--mirror-kind`.
+    pub fn name(self) -> &'static str {
+        match self {
+            MirrorKind::ThreeGpp => "3gpp",
+            MirrorKind::DirectoryIndex => "directory-index",
+        }
+    }
+
+    /// Parser for listings of this kind; `lenient` applies to the 3GPP tables only.
+    pub fn parser(self, lenient: bool) -> Box<dyn ListingParser> {
+        match self {
+            MirrorKind::ThreeGpp => Box::new(ThreeGppFtpParser { lenient }),
+            MirrorKind::DirectoryIndex => Box::new(DirectoryIndexParser),
+        }
+    }
+}
+
+impl std::fmt::Display for MirrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for MirrorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MirrorKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "invalid mirror kind '{}': expected 3gpp or directory-index",
+                    s
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirectoryIndexParser, ListingParser, MirrorKind, ThreeGppFtpParser};
+    use crate::test_support::{MockServer, Response};
+    use crate::{Error, ListQuery, ListingRow, parse_spec_number};
+    use reqwest::blocking::Client;
+
+    fn rows(parser: &dyn ListingParser, body: &str) -> Result<Vec<ListingRow>, Error> {
+        let url = reqwest::Url::parse("https://mirror.example.com/23.501/").unwrap();
+        parser.parse(body, &url, &parse_spec_number("23.501").unwrap())
+    }
+
+    fn row(name: &str, href: &str, date: &str, size: Option<u64>) -> ListingRow {
+        ListingRow {
+            href: href.to_string(),
+            name: name.to_string(),
+            date: date.to_string(),
+            size,
+            release: None,
+        }
+    }
+
+    #[test]
+    fn three_gpp_tables() {
+        let body = "<table><thead><tr><th>name</th><th>date</th><th>size</th></tr></thead><tbody>\
+                    <tr><td><a href=\"/a/23501-g40.zip\">23501-g40.zip</a></td>\
+                    <td>2020/03/27 14:30</td><td>1k</td></tr>\
+                    <tr><td><a href=\"/a/Versions/\">Versions</a></td><td>2021/01/01 10:00</td>\
+                    <td></td></tr>\
+                    <tr><td>no link</td><td>2021/01/01 10:00</td><td></td></tr>\
+                    </tbody></table>";
+        assert_eq!(
+            rows(&ThreeGppFtpParser::default(), body).unwrap(),
+            [
+                row(
+                    "23501-g40.zip",
+                    "/a/23501-g40.zip",
+                    "2020/03/27 14:30",
+                    Some(1024)
+                ),
+                row("Versions", "/a/Versions/", "2021/01/01 10:00", None),
+            ]
+        );
+        let list = "<ul><li><a href=\"x.zip\">x.zip</a> 2020-03-27 14:30</li></ul>";
+        assert!(rows(&ThreeGppFtpParser::default(), list).is_err());
+    }
+
+    #[test]
+    fn directory_index_lists() {
+        let body = "<h1>Index of /23.501</h1><ul>\
+                    <li><a href=\"../\">Parent Directory</a></li>\
+                    <li><a href=\"23501-g40.zip\">23501-g40.zip</a> 2020-03-27 14:30 1.5M</li>\
+                    <li><a href=\"23501-h10.zip\">23501-h1…</a> <span>2021-03-27 08:00</span></li>\
+                    <li>README, no link</li>\
+                    <li><a href=\"Versions/\">Versions/</a> 2021-01-01 10:00 -</li>\
+                    </ul>";
+        assert_eq!(
+            rows(&DirectoryIndexParser, body).unwrap(),
+            [
+                row("Parent Directory", "https://mirror.example.com/", "", None),
+                row(
+                    "23501-g40.zip",
+                    "https://mirror.example.com/23.501/23501-g40.zip",
+                    "2020-03-27 14:30",
+                    Some(1572864)
+                ),
+                row(
+                    "23501-h10.zip",
+                    "https://mirror.example.com/23.501/23501-h10.zip",
+                    "2021-03-27 08:00",
+                    None
+                ),
+                row(
+                    "Versions/",
+                    "https://mirror.example.com/23.501/Versions/",
+                    "2021-01-01 10:00 -",
+                    None
+                ),
+            ]
+        );
+        assert!(matches!(
+            rows(&DirectoryIndexParser, "<ul><li>empty</li></ul>"),
+            Err(Error::EmptyDirectory(_))
+        ));
+    }
+
+    #[test]
+    fn list_with_mirror_kind() {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/23_series/23.501/" => Response::ok(
+                "<ul><li><a href=\"23501-g40.zip\">23501-g40.zip</a> 2020-03-27 14:30</li>\
+                 <li><a href=\"23501-h10.zip\">23501-h10.zip</a> 2021-03-27 08:00 2k</li></ul>",
+            ),
+            _ => Response::not_found(),
+        });
+        let query = ListQuery {
+            base_url: server.url("/"),
+            mirror_kind: MirrorKind::DirectoryIndex,
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let items = crate::list(&Client::new(), &query).unwrap();
+        let urls: Vec<_> = items.iter().map(|item| item.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                server.url("/23_series/23.501/23501-g40.zip"),
+                server.url("/23_series/23.501/23501-h10.zip"),
+            ]
+        );
+        assert_eq!(items[1].size, Some(2048));
+
+        let three_gpp = ListQuery {
+            mirror_kind: MirrorKind::ThreeGpp,
+            ..query
+        };
+        assert!(crate::list(&Client::new(), &three_gpp).is_err());
+
+        assert_eq!("directory-index".parse(), Ok(MirrorKind::DirectoryIndex));
+        assert_eq!(MirrorKind::ThreeGpp.to_string(), "3gpp");
+        assert!("gitlab".parse::<MirrorKind>().is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::client::error_chain;
+use crate::{Error, ListQuery};
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Whether a URL answered a probe, for `--probe`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Probe {
+    pub url: String,
+    /// HTTP status of the response, or `None` if the request failed.
+    pub status: Option<u16>,
+    /// Time until the response headers arrived or the request failed, in milliseconds.
+    pub latency_ms: u64,
+    /// Why the request failed.
+    pub error: Option<String>,
+}
+
+impl Probe {
+    /// Whether the server answered at all, whatever the status.
+    pub fn reachable(&self) -> bool {
+        self.status.is_some()
+    }
+
+    /// Whether the server answered with a success status, i.e. the URL exists.
+    pub fn found(&self) -> bool {
+        self.status
+            .is_some_and(|status| (200..300).contains(&status))
+    }
+}
+
+impl std::fmt::Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.status, &self.error) {
+            (Some(status), _) => {
+                write!(f, "reachable {} {}ms {}", status, self.latency_ms, self.url)
+            }
+            (None, error) => write!(
+                f,
+                "unreachable - {}ms {}: {}",
+                self.latency_ms,
+                self.url,
+                error.as_deref().unwrap_or("no response")
+            ),
+        }
+    }
+}
+
+/// Send a `HEAD` request to `url` and time it, without reading a body. A server that rejects
+/// `HEAD` is asked again with `GET`, whose body is dropped unread.
+pub fn probe_url(client: &Client, url: &str) -> Probe {
+    let started = Instant::now();
+    let result = client
+        .head(url)
+        .send()
+        .and_then(|resp| match resp.status() {
+            reqwest::StatusCode::METHOD_NOT_ALLOWED | reqwest::StatusCode::NOT_IMPLEMENTED => {
+                client.get(url).send()
+            }
+            _ => Ok(resp),
+        });
+    let latency_ms = millis(started.elapsed());
+    match result {
+        Ok(resp) => Probe {
+            url: url.to_string(),
+            status: Some(resp.status().as_u16()),
+            latency_ms,
+            error: None,
+        },
+        Err(e) => Probe {
+            url: url.to_string(),
+            status: None,
+            latency_ms,
+            error: Some(error_chain(&e)),
+        },
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// Probe the archive root of `query` and its spec directory with `probe_url`, in that order.
+/// FTP archives are not supported.
+pub fn probe(client: &Client, query: &ListQuery) -> Result<Vec<Probe>, Error> {
+    let url = query.listing_url()?;
+    if url.scheme() == "ftp" {
+        return Err(Error::Other(format!(
+            "cannot probe '{}': FTP archives are not supported",
+            url
+        )));
+    }
+    Ok(vec![
+        probe_url(client, &query.base_url),
+        probe_url(client, url.as_str()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::probe;
+    use crate::test_support::{MockServer, Response};
+    use crate::{ListQuery, parse_spec_number};
+    use reqwest::blocking::Client;
+    use std::time::Duration;
+
+    #[test]
+    fn probe_reports_status_and_latency() {
+        let server = MockServer::start(|req| match (req.method.as_str(), req.path.as_str()) {
+            (_, "/") => Response::ok(""),
+            ("HEAD", "/23_series/23.501/") => {
+                std::thread::sleep(Duration::from_millis(50));
+                Response::ok("")
+            }
+            ("HEAD", "/23_series/23.502/") => Response::new(405, ""),
+            ("GET", "/23_series/23.502/") => Response::ok("listing"),
+            _ => Response::not_found(),
+        });
+        let probes = |spec| {
+            let query = ListQuery {
+                base_url: server.url("/"),
+                ..ListQuery::new(parse_spec_number(spec).unwrap())
+            };
+            probe(&Client::new(), &query).unwrap()
+        };
+
+        let [root, dir] = <[_; 2]>::try_from(probes("23.501")).unwrap();
+        assert_eq!(root.url, server.url("/"));
+        assert_eq!(root.status, Some(200));
+        assert!(root.found());
+        assert_eq!(dir.url, server.url("/23_series/23.501/"));
+        assert_eq!(dir.status, Some(200));
+        assert!(dir.latency_ms >= 50, "{}", dir.latency_ms);
+        assert!(dir.to_string().starts_with("reachable 200 "), "{}", dir);
+
+        assert_eq!(probes("23.502")[1].status, Some(200));
+        let missing = &probes("23.503")[1];
+        assert!(missing.reachable() && !missing.found());
+        assert_eq!(missing.status, Some(404));
+
+        let query = ListQuery {
+            base_url: "http://127.0.0.1:1/".to_string(),
+            ..ListQuery::new(parse_spec_number("23.501").unwrap())
+        };
+        let unreachable = &probe(&Client::new(), &query).unwrap()[0];
+        assert!(!unreachable.reachable());
+        assert!(unreachable.error.is_some());
+        assert!(unreachable.to_string().starts_with("unreachable - "));
+    }
+}
@@ -0,0 +1,401 @@
+//! Helpers shared by unit tests: tiny blocking HTTP and FTP servers and scratch directories.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Request as seen by a `MockServer` handler.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Value of the first header called `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Response returned by a `MockServer` handler.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Number of bytes of `body` sent before the connection is dropped, if not all of them.
+    pub truncate: Option<usize>,
+}
+
+impl Response {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            truncate: None,
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(200, body)
+    }
+
+    pub fn not_found() -> Self {
+        Self::new(404, "not found")
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Send only the first `len` bytes of the body, with the `Content-Length` of the whole, then
+    /// close the connection, as if it dropped mid-stream.
+    pub fn truncated(mut self, len: usize) -> Self {
+        self.truncate = Some(len);
+        self
+    }
+}
+
+/// HTTP/1.1 server on an ephemeral localhost port, answering each request with `handler`.
+/// Every connection serves one request and is then closed, unless started with
+/// `start_keep_alive`. The server lives until the test exits.
+pub struct MockServer {
+    base: String,
+    connections: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let (port, connections) = serve(handler, Ok::<_, ()>, false);
+        MockServer {
+            base: format!("http://127.0.0.1:{}", port),
+            connections,
+        }
+    }
+
+    /// Like `start`, but keeping connections open to serve any number of requests each.
+    pub fn start_keep_alive<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let (port, connections) = serve(handler, Ok::<_, ()>, true);
+        MockServer {
+            base: format!("http://127.0.0.1:{}", port),
+            connections,
+        }
+    }
+
+    /// Like `start`, but serving HTTPS for `localhost` with the certificate in `resources/tls`.
+    pub fn start_tls<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let identity = native_tls::Identity::from_pkcs8(
+            include_bytes!("../resources/tls/server.pem"),
+            include_bytes!("../resources/tls/server.key"),
+        )
+        .expect("load test TLS identity");
+        let acceptor = native_tls::TlsAcceptor::new(identity).expect("build TLS acceptor");
+        let (port, connections) = serve(
+            handler,
+            move |stream| acceptor.accept(stream).map_err(|_| ()),
+            false,
+        );
+        MockServer {
+            base: format!("https://localhost:{}", port),
+            connections,
+        }
+    }
+
+    /// FTP server answering `LIST` and `RETR` commands with `handler`, called with the command
+    /// as the request method and its argument as the path. A non-200 response is sent as a
+    /// `550` reply. Logins are accepted with any user and password, and only passive mode is
+    /// supported.
+    pub fn start_ftp<F>(handler: F) -> Self
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock FTP server");
+        let port = listener
+            .local_addr()
+            .expect("mock FTP server address")
+            .port();
+        let handler = Arc::new(handler);
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let handler = handler.clone();
+                std::thread::spawn(move || serve_ftp(stream, &*handler));
+            }
+        });
+        MockServer {
+            base: format!("ftp://127.0.0.1:{}", port),
+            connections,
+        }
+    }
+
+    /// SOCKS5 proxy without authentication, connecting every `CONNECT` request to `upstream`
+    /// (a `host:port` address) whatever its target, which is passed to `on_connect` as
+    /// `host:port`, with the host as sent by the client: a domain name for `socks5h://`.
+    pub fn start_socks5<F>(upstream: String, on_connect: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock SOCKS5 server");
+        let port = listener
+            .local_addr()
+            .expect("mock SOCKS5 server address")
+            .port();
+        let on_connect = Arc::new(on_connect);
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let (upstream, on_connect) = (upstream.clone(), on_connect.clone());
+                std::thread::spawn(move || serve_socks5(stream, &upstream, &*on_connect));
+            }
+        });
+        MockServer {
+            base: format!("socks5h://127.0.0.1:{}", port),
+            connections,
+        }
+    }
+
+    /// Number of connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Absolute URL for `path` (which should start with `/`) on this server.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
+    }
+}
+
+/// Accept connections on an ephemeral port, wrap each with `wrap` and answer its request, or
+/// with `keep_alive` all of its requests, with `handler`. Returns the port and the counter of
+/// accepted connections.
+fn serve<F, W, S, E>(handler: F, wrap: W, keep_alive: bool) -> (u16, Arc<AtomicUsize>)
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+    W: Fn(std::net::TcpStream) -> Result<S, E> + Send + Sync + 'static,
+    S: Read + Write,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+    let port = listener.local_addr().expect("mock server address").port();
+    let handler = Arc::new(handler);
+    let wrap = Arc::new(wrap);
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let handler = handler.clone();
+            let wrap = wrap.clone();
+            std::thread::spawn(move || {
+                let Ok(stream) = wrap(stream) else { return };
+                let mut reader = BufReader::new(stream);
+                while let Some(request) = read_request(&mut reader) {
+                    let response = handler(&request);
+                    write_response(reader.get_mut(), &request, &response, keep_alive);
+                    if !keep_alive || response.truncate.is_some() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    (port, connections)
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut content_length = 0;
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request {
+        method,
+        path,
+        headers,
+    })
+}
+
+fn write_response(
+    stream: &mut impl Write,
+    request: &Request,
+    response: &Response,
+    keep_alive: bool,
+) {
+    let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: {}\r\n\r\n",
+        response.body.len(),
+        if keep_alive { "keep-alive" } else { "close" }
+    ));
+    let _ = stream.write_all(head.as_bytes());
+    if request.method != "HEAD" {
+        let len = response.truncate.unwrap_or(response.body.len());
+        let _ = stream.write_all(&response.body[..len.min(response.body.len())]);
+    }
+    let _ = stream.flush();
+}
+
+/// Negotiate one SOCKS5 connection, then relay it to `upstream` in both directions.
+fn serve_socks5(mut client: std::net::TcpStream, upstream: &str, on_connect: &dyn Fn(&str)) {
+    let mut read = |len: usize| {
+        let mut buf = vec![0; len];
+        client.read_exact(&mut buf).ok().map(|()| buf)
+    };
+    // Greeting: version, number of methods, methods. Only "no authentication" is offered.
+    if read(2).and_then(|head| read(head[1].into())).is_none() || client.write_all(&[5, 0]).is_err()
+    {
+        return;
+    }
+    let mut read = |len: usize| {
+        let mut buf = vec![0; len];
+        client.read_exact(&mut buf).ok().map(|()| buf)
+    };
+    // Request: version, command, reserved, address type, address, port.
+    let Some(head) = read(4) else { return };
+    let host = match head[3] {
+        1 => read(4).map(|ip| format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3])),
+        3 => read(1)
+            .and_then(|len| read(len[0].into()))
+            .map(|name| String::from_utf8_lossy(&name).into_owned()),
+        _ => None,
+    };
+    let (Some(host), Some(port)) = (host, read(2)) else {
+        return;
+    };
+    on_connect(&format!(
+        "{}:{}",
+        host,
+        u16::from_be_bytes([port[0], port[1]])
+    ));
+    let Ok(server) = std::net::TcpStream::connect(upstream) else {
+        let _ = client.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]);
+        return;
+    };
+    if client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).is_err() {
+        return;
+    }
+    let (Ok(mut client_reader), Ok(mut server_writer)) = (client.try_clone(), server.try_clone())
+    else {
+        return;
+    };
+    std::thread::spawn(move || {
+        let _ = std::io::copy(&mut client_reader, &mut server_writer);
+        let _ = server_writer.shutdown(std::net::Shutdown::Write);
+    });
+    let mut server = server;
+    let _ = std::io::copy(&mut server, &mut client);
+}
+
+/// Answer the FTP commands of one control connection.
+fn serve_ftp(stream: std::net::TcpStream, handler: &dyn Fn(&Request) -> Response) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    let mut data_listener = None;
+    let _ = writer.write_all(b"220 mock FTP server\r\n");
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let line = line.trim_end();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let reply = match command.to_ascii_uppercase().as_str() {
+            "USER" => "331 password required".to_string(),
+            "PASS" => "230 logged in".to_string(),
+            "TYPE" => "200 type set".to_string(),
+            "PASV" => {
+                let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock FTP data");
+                let port = listener.local_addr().expect("mock FTP data address").port();
+                data_listener = Some(listener);
+                format!(
+                    "227 Entering Passive Mode (127,0,0,1,{},{})",
+                    port / 256,
+                    port % 256
+                )
+            }
+            method @ ("LIST" | "RETR") => {
+                let response = handler(&Request {
+                    method: method.to_string(),
+                    path: argument.to_string(),
+                    headers: Vec::new(),
+                });
+                match (response.status, data_listener.take()) {
+                    (200, Some(listener)) => {
+                        let _ = writer.write_all(b"150 opening data connection\r\n");
+                        if let Ok((mut data, _)) = listener.accept() {
+                            let _ = data.write_all(&response.body);
+                        }
+                        "226 transfer complete".to_string()
+                    }
+                    (200, None) => "425 use PASV first".to_string(),
+                    _ => "550 file not found".to_string(),
+                }
+            }
+            "QUIT" => {
+                let _ = writer.write_all(b"221 bye\r\n");
+                return;
+            }
+            _ => "502 command not implemented".to_string(),
+        };
+        let _ = writer.write_all(format!("{}\r\n", reply).as_bytes());
+    }
+}
+
+/// Create a fresh, empty scratch directory unique to `name` and this process.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("get-3gpp-spec-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
+}
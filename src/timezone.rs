@@ -0,0 +1,521 @@
+//! Timezone the dates of a listing are written in, for `--source-tz`.
+//!
+//! Listings give local times without an offset. Zones are read from the system's compiled tz
+//! database (`/usr/share/zoneinfo`, or `$TZDIR`), whose TZif files hold the zone's transitions
+//! and, in a footer, the POSIX TZ rule for instants after the last one. A tz database crate is
+//! not part of this build.
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, Utc};
+use std::path::{Path, PathBuf};
+
+/// Directory of the system tz database, unless `TZDIR` is set.
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// A named timezone loaded from the tz database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceTz {
+    name: String,
+    /// Instants (Unix seconds) at which the UTC offset changes, ascending, with the offset (in
+    /// seconds east of UTC) in effect from each.
+    transitions: Vec<(i64, i32)>,
+    /// Offset before the first transition.
+    initial: i32,
+    /// Rule for instants after the last transition.
+    rule: Option<Rule>,
+}
+
+impl SourceTz {
+    /// Load the zone with the IANA `name`, e.g. `Europe/Paris`, from the system tz database.
+    pub fn named(name: &str) -> Result<Self, String> {
+        let dir =
+            std::env::var_os("TZDIR").map_or_else(|| PathBuf::from(ZONEINFO_DIR), PathBuf::from);
+        SourceTz::load(&dir, name)
+    }
+
+    /// Load the zone with the IANA `name` from the tz database in `dir`.
+    pub fn load(dir: &Path, name: &str) -> Result<Self, String> {
+        let valid = !name.is_empty()
+            && !name.starts_with('/')
+            && name
+                .split('/')
+                .all(|part| !part.is_empty() && part != "." && part != "..")
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "/_-+".contains(c));
+        if !valid {
+            return Err(format!(
+                "unknown timezone '{}': expected an IANA name like Europe/Paris",
+                name
+            ));
+        }
+        let data = std::fs::read(dir.join(name)).map_err(|e| {
+            format!(
+                "unknown timezone '{}': expected an IANA name like Europe/Paris ({})",
+                name, e
+            )
+        })?;
+        let tz = parse_tzif(&data).map_err(|e| format!("invalid timezone '{}': {}", name, e))?;
+        Ok(SourceTz {
+            name: name.to_string(),
+            ..tz
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// UTC offset in seconds in effect at the Unix time `t`.
+    fn offset_at(&self, t: i64) -> i32 {
+        let n = self.transitions.partition_point(|&(at, _)| at <= t);
+        match (n, &self.rule) {
+            (n, Some(rule)) if n == self.transitions.len() => rule.offset_at(t),
+            (0, _) => self.initial,
+            (n, _) => self.transitions[n - 1].1,
+        }
+    }
+
+    /// The instant of the local time `local` in this zone. A time skipped by a change to summer
+    /// time is taken with the offset in effect before it, and a repeated time as the earlier
+    /// of the two.
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let t = local.and_utc().timestamp();
+        // Offsets either side of any change near `t`; the larger one gives the earlier instant.
+        let before = self.offset_at(t - 86400);
+        let after = self.offset_at(t + 86400);
+        let offset = [before.max(after), before.min(after)]
+            .into_iter()
+            .find(|&offset| self.offset_at(t - i64::from(offset)) == offset)
+            .unwrap_or(before);
+        local.and_utc() - chrono::Duration::seconds(i64::from(offset))
+    }
+}
+
+/// Day of the year a POSIX TZ rule switches on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RuleDay {
+    /// `Jn`: day 1 to 365, not counting February 29.
+    Julian(u64),
+    /// `n`: day 0 to 365, counting February 29.
+    Ordinal(u64),
+    /// `Mm.w.d`: weekday `d` (0 is Sunday) of week `w` (1 to 5, 5 being the last) of month `m`.
+    Weekday { month: u32, week: u32, weekday: u32 },
+}
+
+impl RuleDay {
+    fn date(self, year: i32) -> Option<NaiveDate> {
+        let january = NaiveDate::from_ymd_opt(year, 1, 1)?;
+        match self {
+            RuleDay::Julian(n) => {
+                let leap = u64::from(january.leap_year() && n >= 60);
+                january.checked_add_days(Days::new(n - 1 + leap))
+            }
+            RuleDay::Ordinal(n) => january.checked_add_days(Days::new(n)),
+            RuleDay::Weekday {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+                let first_weekday = first.weekday().num_days_from_sunday();
+                let day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+                NaiveDate::from_ymd_opt(year, month, day)
+                    .or_else(|| NaiveDate::from_ymd_opt(year, month, day - 7))
+            }
+        }
+    }
+}
+
+/// Summer time part of a POSIX TZ rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dst {
+    offset: i32,
+    /// Day and local time (seconds after midnight, standard time) summer time starts.
+    start: (RuleDay, i32),
+    /// Day and local time (seconds after midnight, summer time) summer time ends.
+    end: (RuleDay, i32),
+}
+
+/// POSIX TZ rule such as `CET-1CEST,M3.5.0,M10.5.0/3`, from the footer of a TZif file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rule {
+    /// Standard offset in seconds east of UTC (POSIX offsets count west).
+    offset: i32,
+    dst: Option<Dst>,
+}
+
+impl Rule {
+    fn parse(s: &str) -> Option<Rule> {
+        let rest = skip_name(s)?;
+        let (std, rest) = parse_time(rest)?;
+        if rest.is_empty() {
+            return Some(Rule {
+                offset: -std,
+                dst: None,
+            });
+        }
+        let rest = skip_name(rest)?;
+        let (dst, rest) = match rest.starts_with(',') {
+            true => (-std + 3600, rest),
+            false => {
+                let (dst, rest) = parse_time(rest)?;
+                (-dst, rest)
+            }
+        };
+        let (start, rest) = parse_switch(rest.strip_prefix(',')?)?;
+        let (end, rest) = parse_switch(rest.strip_prefix(',')?)?;
+        rest.is_empty().then_some(Rule {
+            offset: -std,
+            dst: Some(Dst {
+                offset: dst,
+                start,
+                end,
+            }),
+        })
+    }
+
+    fn offset_at(&self, t: i64) -> i32 {
+        let Some(dst) = self.dst else {
+            return self.offset;
+        };
+        let Some(year) = DateTime::from_timestamp(t + i64::from(self.offset), 0).map(|d| d.year())
+        else {
+            return self.offset;
+        };
+        let switch = |(day, time): (RuleDay, i32), offset: i32| {
+            day.date(year).map(|date| {
+                date.and_hms_opt(0, 0, 0)
+                    .expect("midnight")
+                    .and_utc()
+                    .timestamp()
+                    + i64::from(time - offset)
+            })
+        };
+        let (Some(start), Some(end)) =
+            (switch(dst.start, self.offset), switch(dst.end, dst.offset))
+        else {
+            return self.offset;
+        };
+        let summer = match start < end {
+            true => start <= t && t < end,
+            false => !(end <= t && t < start),
+        };
+        match summer {
+            true => dst.offset,
+            false => self.offset,
+        }
+    }
+}
+
+/// Skip a zone abbreviation: letters, or any text in angle brackets.
+fn skip_name(s: &str) -> Option<&str> {
+    match s.strip_prefix('<') {
+        Some(quoted) => quoted.split_once('>').map(|(_, rest)| rest),
+        None => {
+            let end = s
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(s.len());
+            (end >= 3).then(|| &s[end..])
+        }
+    }
+}
+
+/// Parse `[+-]hh[:mm[:ss]]` into seconds.
+fn parse_time(s: &str) -> Option<(i32, &str)> {
+    let (sign, s) = match s.as_bytes().first() {
+        Some(b'-') => (-1, &s[1..]),
+        Some(b'+') => (1, &s[1..]),
+        _ => (1, s),
+    };
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(s.len());
+    let mut seconds = 0;
+    for (i, part) in s[..end].split(':').enumerate() {
+        if i > 2 || part.is_empty() {
+            return None;
+        }
+        seconds += part.parse::<i32>().ok()? * [3600, 60, 1][i];
+    }
+    Some((sign * seconds, &s[end..]))
+}
+
+/// Parse the day and optional `/time` of a switch to or from summer time.
+fn parse_switch(s: &str) -> Option<((RuleDay, i32), &str)> {
+    let end = s.find([',', '/']).unwrap_or(s.len());
+    let day = match &s[..end] {
+        day if day.starts_with('J') => {
+            RuleDay::Julian(day[1..].parse().ok().filter(|n| (1..=365).contains(n))?)
+        }
+        day if day.starts_with('M') => {
+            let fields: Vec<u32> = day[1..]
+                .split('.')
+                .map(|f| f.parse().ok())
+                .collect::<Option<_>>()?;
+            match fields[..] {
+                [month @ 1..=12, week @ 1..=5, weekday @ 0..=6] => RuleDay::Weekday {
+                    month,
+                    week,
+                    weekday,
+                },
+                _ => return None,
+            }
+        }
+        day => RuleDay::Ordinal(day.parse().ok().filter(|n| *n <= 365)?),
+    };
+    match s[end..].strip_prefix('/') {
+        Some(time) => {
+            let (time, rest) = parse_time(time)?;
+            Some(((day, time), rest))
+        }
+        None => Some(((day, 7200), &s[end..])),
+    }
+}
+
+/// Reader of the big-endian fields of a TZif file.
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| "truncated TZif data".to_string())?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn int(&mut self, n: usize) -> Result<i64, String> {
+        let bytes = self.take(n)?;
+        let value = bytes.iter().fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+        // Sign-extend from `n` bytes.
+        let shift = 64 - 8 * n as u32;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    /// Read a header and return its counts: isutcnt, isstdcnt, leapcnt, timecnt, typecnt,
+    /// charcnt.
+    fn header(&mut self) -> Result<(u8, [usize; 6]), String> {
+        if self.take(4)? != b"TZif" {
+            return Err("not a TZif file".to_string());
+        }
+        let version = self.take(16)?[0];
+        let mut counts = [0; 6];
+        for count in &mut counts {
+            *count = self.int(4)? as u32 as usize;
+        }
+        Ok((version, counts))
+    }
+}
+
+/// Parse a TZif file into an unnamed zone.
+fn parse_tzif(data: &[u8]) -> Result<SourceTz, String> {
+    let mut cursor = Cursor { data, pos: 0 };
+    let (version, counts) = cursor.header()?;
+    let (time_size, counts) = match version {
+        0 => (4, counts),
+        _ => {
+            // Skip the 32-bit data block in favour of the 64-bit one that follows it.
+            let [isut, isstd, leap, time, types, chars] = counts;
+            cursor.take(time * 5 + types * 6 + chars + leap * 8 + isstd + isut)?;
+            (8, cursor.header()?.1)
+        }
+    };
+    let [isut, isstd, leap, time, types, chars] = counts;
+    if types == 0 {
+        return Err("no local time types".to_string());
+    }
+    let times: Vec<i64> = (0..time)
+        .map(|_| cursor.int(time_size))
+        .collect::<Result<_, _>>()?;
+    let indexes = cursor.take(time)?.to_vec();
+    let offsets: Vec<i32> = (0..types)
+        .map(|_| {
+            let offset = cursor.int(4)? as i32;
+            cursor.take(2)?;
+            Ok(offset)
+        })
+        .collect::<Result<_, String>>()?;
+    cursor.take(chars + leap * (time_size + 4) + isstd + isut)?;
+    let transitions = times
+        .into_iter()
+        .zip(indexes)
+        .map(|(at, index)| {
+            offsets
+                .get(usize::from(index))
+                .map(|offset| (at, *offset))
+                .ok_or_else(|| "invalid local time type".to_string())
+        })
+        .collect::<Result<_, _>>()?;
+    let rule = match version {
+        0 => None,
+        _ => std::str::from_utf8(&data[cursor.pos..])
+            .ok()
+            .and_then(|footer| footer.strip_prefix('\n')?.split('\n').next())
+            .filter(|footer| !footer.is_empty())
+            .and_then(Rule::parse),
+    };
+    Ok(SourceTz {
+        name: String::new(),
+        transitions,
+        initial: offsets[0],
+        rule,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::SourceTz;
+    use crate::test_support::scratch_dir;
+    use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+    use std::path::Path;
+
+    /// Write a version 2 TZif file for `name` into `dir`, with the given transitions (Unix time
+    /// and index into `offsets`) and footer rule.
+    pub(crate) fn write_tzif(
+        dir: &Path,
+        name: &str,
+        transitions: &[(i64, u8)],
+        offsets: &[i32],
+        footer: &str,
+    ) {
+        let header = |time_count: usize| {
+            let mut header = b"TZif2".to_vec();
+            header.extend([0; 15]);
+            for count in [0, 0, 0, time_count, offsets.len(), 4] {
+                header.extend((count as u32).to_be_bytes());
+            }
+            header
+        };
+        let mut data = header(0);
+        data.extend(
+            offsets
+                .iter()
+                .flat_map(|o| o.to_be_bytes().into_iter().chain([0, 0])),
+        );
+        data.extend(b"LMT\0");
+        data.extend(header(transitions.len()));
+        data.extend(transitions.iter().flat_map(|(at, _)| at.to_be_bytes()));
+        data.extend(transitions.iter().map(|(_, index)| *index));
+        data.extend(
+            offsets
+                .iter()
+                .flat_map(|o| o.to_be_bytes().into_iter().chain([0, 0])),
+        );
+        data.extend(b"LMT\0");
+        data.extend(format!("\n{}\n", footer).bytes());
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn footer_rule_applies_summer_time() {
+        let dir = scratch_dir("timezone-rule");
+        write_tzif(
+            &dir,
+            "Europe/Paris",
+            &[],
+            &[3600],
+            "CET-1CEST,M3.5.0,M10.5.0/3",
+        );
+        let tz = SourceTz::load(&dir, "Europe/Paris").unwrap();
+        assert_eq!(tz.name(), "Europe/Paris");
+        assert_eq!(
+            tz.to_utc(local(2020, 3, 27, 14, 30)),
+            Utc.with_ymd_and_hms(2020, 3, 27, 13, 30, 0).unwrap()
+        );
+        assert_eq!(
+            tz.to_utc(local(2020, 7, 1, 12, 0)),
+            Utc.with_ymd_and_hms(2020, 7, 1, 10, 0, 0).unwrap()
+        );
+        // Summer time starts on the last Sunday of March at 02:00 and ends on the last Sunday
+        // of October at 03:00.
+        assert_eq!(
+            tz.to_utc(local(2020, 3, 29, 3, 0)),
+            Utc.with_ymd_and_hms(2020, 3, 29, 1, 0, 0).unwrap()
+        );
+        assert_eq!(
+            tz.to_utc(local(2020, 3, 29, 2, 30)),
+            Utc.with_ymd_and_hms(2020, 3, 29, 1, 30, 0).unwrap()
+        );
+        assert_eq!(
+            tz.to_utc(local(2020, 10, 25, 2, 30)),
+            Utc.with_ymd_and_hms(2020, 10, 25, 0, 30, 0).unwrap()
+        );
+        assert_eq!(
+            tz.to_utc(local(2020, 10, 25, 3, 30)),
+            Utc.with_ymd_and_hms(2020, 10, 25, 2, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn transitions_and_southern_rules() {
+        let dir = scratch_dir("timezone-transitions");
+        // UTC+3 until 2011-03-27 00:00 UTC, then UTC+4 with no rule.
+        write_tzif(
+            &dir,
+            "Europe/Moscow",
+            &[(1301184000, 1)],
+            &[10800, 14400],
+            "MSK-4",
+        );
+        let tz = SourceTz::load(&dir, "Europe/Moscow").unwrap();
+        assert_eq!(
+            tz.to_utc(local(2010, 1, 1, 12, 0)),
+            Utc.with_ymd_and_hms(2010, 1, 1, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            tz.to_utc(local(2012, 1, 1, 12, 0)),
+            Utc.with_ymd_and_hms(2012, 1, 1, 8, 0, 0).unwrap()
+        );
+
+        write_tzif(
+            &dir,
+            "Australia/Sydney",
+            &[],
+            &[36000],
+            "AEST-10AEDT,M10.1.0,M4.1.0/3",
+        );
+        let tz = SourceTz::load(&dir, "Australia/Sydney").unwrap();
+        assert_eq!(
+            tz.to_utc(local(2020, 1, 15, 12, 0)),
+            Utc.with_ymd_and_hms(2020, 1, 15, 1, 0, 0).unwrap()
+        );
+        assert_eq!(
+            tz.to_utc(local(2020, 7, 15, 12, 0)),
+            Utc.with_ymd_and_hms(2020, 7, 15, 2, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_timezone() {
+        let dir = scratch_dir("timezone-unknown");
+        let err = SourceTz::load(&dir, "Mars/Olympus_Mons").unwrap_err();
+        assert!(
+            err.starts_with("unknown timezone 'Mars/Olympus_Mons'"),
+            "{}",
+            err
+        );
+        let err = SourceTz::load(&dir, "../etc/passwd").unwrap_err();
+        assert!(
+            err.starts_with("unknown timezone '../etc/passwd'"),
+            "{}",
+            err
+        );
+
+        std::fs::write(dir.join("Garbage"), "not a zone").unwrap();
+        let err = SourceTz::load(&dir, "Garbage").unwrap_err();
+        assert_eq!(err, "invalid timezone 'Garbage': not a TZif file");
+    }
+}
@@ -0,0 +1,137 @@
+//! YAML rendering of listings for `--format yaml`, with the same field shapes as the JSON output.
+//!
+//! Values are first serialized to JSON values and then written as block-style YAML. Strings are
+//! written as JSON strings, which are valid YAML double-quoted scalars, so no escaping rules of
+//! YAML's own are needed.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Render `value` as a YAML document. Mapping keys are sorted, as in a `serde_json::Value`.
+/// Empty sequences and mappings are written in flow style, e.g. an empty listing as `[]`.
+pub fn to_yaml<T: Serialize + ?Sized>(value: &T) -> Result<String, String> {
+    let value = serde_json::to_value(value).map_err(|e| format!("failed to serialize: {}", e))?;
+    Ok(lines(&value).into_iter().map(|line| line + "\n").collect())
+}
+
+/// Whether `value` is written over several lines, below its key or sequence dash.
+fn is_block(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+        _ => false,
+    }
+}
+
+/// `value` written on one line: a scalar or an empty collection.
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        scalar => scalar.to_string(),
+    }
+}
+
+/// A mapping key, quoted unless it is a plain word.
+fn key(name: &str) -> String {
+    let plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match plain {
+        true => name.to_string(),
+        false => Value::from(name).to_string(),
+    }
+}
+
+/// Lines of `value`, unindented.
+fn lines(value: &Value) -> Vec<String> {
+    let mut out = Vec::new();
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                match is_block(item) {
+                    true => {
+                        for (i, line) in lines(item).into_iter().enumerate() {
+                            let prefix = if i == 0 { "- " } else { "  " };
+                            out.push(format!("{}{}", prefix, line));
+                        }
+                    }
+                    false => out.push(format!("- {}", inline(item))),
+                }
+            }
+        }
+        Value::Object(fields) if !fields.is_empty() => {
+            for (name, field) in fields {
+                match is_block(field) {
+                    true => {
+                        out.push(format!("{}:", key(name)));
+                        out.extend(lines(field).into_iter().map(|line| format!("  {}", line)));
+                    }
+                    false => out.push(format!("{}: {}", key(name), inline(field))),
+                }
+            }
+        }
+        other => out.push(inline(other)),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_yaml;
+    use crate::{SpecItem, Version, parse_spec_number};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn listing_as_yaml() {
+        let item = SpecItem {
+            spec: parse_spec_number("23.501").unwrap(),
+            version: Some(Version {
+                major: 16,
+                minor: 4,
+                editorial: 0,
+            }),
+            release: None,
+            date: Utc.with_ymd_and_hms(2020, 3, 27, 14, 30, 0).unwrap(),
+            url: "https://example.com/23501-g40.zip".to_string(),
+            filename: "23501-g40.zip".to_string(),
+            draft: false,
+            size: Some(1536),
+        };
+        assert_eq!(
+            to_yaml(&[item]).unwrap(),
+            "- date: \"2020-03-27T14:30:00Z\"\n\
+             \x20 draft: false\n\
+             \x20 filename: \"23501-g40.zip\"\n\
+             \x20 release: null\n\
+             \x20 size: 1536\n\
+             \x20 spec:\n\
+             \x20   number: \"501\"\n\
+             \x20   series: \"23\"\n\
+             \x20 url: \"https://example.com/23501-g40.zip\"\n\
+             \x20 version:\n\
+             \x20   editorial: 0\n\
+             \x20   major: 16\n\
+             \x20   minor: 4\n"
+        );
+        assert_eq!(to_yaml::<[SpecItem]>(&[]).unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn nested_and_quoted_values() {
+        let value = serde_json::json!({
+            "list": [[1, 2], [], {"a b": "x\"y\nz"}],
+            "empty": {},
+        });
+        assert_eq!(
+            to_yaml(&value).unwrap(),
+            "empty: {}\n\
+             list:\n\
+             \x20 - - 1\n\
+             \x20   - 2\n\
+             \x20 - []\n\
+             \x20 - \"a b\": \"x\\\"y\\nz\"\n"
+        );
+    }
+}